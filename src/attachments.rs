@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{notifications::AttachmentKind, tenants::default_tenant};
+
+// Telegram upload limits
+pub const MAX_PHOTO_SIZE: usize = 10 * 1024 * 1024;
+pub const MAX_DOCUMENT_SIZE: usize = 50 * 1024 * 1024;
+// Room for multipart headers on top of the largest file
+pub const MAX_UPLOAD_BODY_SIZE: usize = MAX_DOCUMENT_SIZE + 1024 * 1024;
+// Album size limit
+pub const MAX_ATTACHMENTS: usize = 10;
+pub const MAX_CAPTION_LENGTH: usize = 1024;
+
+const PHOTO_CONTENT_TYPES: [&str; 3] = ["image/jpeg", "image/png", "image/webp"];
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+// Uploaded file. Contents are stored separately, notifications refer to it by id
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Attachment {
+    pub id: String,
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
+    pub file_name: String,
+    pub content_type: String,
+    pub size: usize,
+    pub created_at: String, // Stringified UTC date
+}
+
+pub fn validate_upload(file_name: &str, data: &[u8]) -> Result<(), String> {
+    if file_name.trim().is_empty() {
+        return Err("file name can't be empty".to_string());
+    }
+
+    if data.is_empty() {
+        return Err("file can't be empty".to_string());
+    }
+
+    if data.len() > MAX_DOCUMENT_SIZE {
+        return Err(format!(
+            "file can't be larger than {} bytes",
+            MAX_DOCUMENT_SIZE
+        ));
+    }
+
+    return Ok(());
+}
+
+// Checks uploaded file can be sent as the given kind
+pub fn check_kind(attachment: &Attachment, kind: &AttachmentKind) -> Result<(), String> {
+    if *kind == AttachmentKind::Document {
+        return Ok(());
+    }
+
+    if !PHOTO_CONTENT_TYPES.contains(&attachment.content_type.as_str()) {
+        return Err(format!(
+            "attachment {} of type {} can't be sent as photo. Expected one of: {}",
+            attachment.id,
+            attachment.content_type,
+            PHOTO_CONTENT_TYPES.join(", ")
+        ));
+    }
+
+    if attachment.size > MAX_PHOTO_SIZE {
+        return Err(format!(
+            "attachment {} is too large for a photo, limit is {} bytes",
+            attachment.id, MAX_PHOTO_SIZE
+        ));
+    }
+
+    return Ok(());
+}
+
+// Content type of upload without one, guessed by extension
+pub fn guess_content_type(file_name: &str) -> String {
+    let extension = match file_name.rsplit_once('.') {
+        Some((_, e)) => e.to_lowercase(),
+        None => return DEFAULT_CONTENT_TYPE.to_string(),
+    };
+
+    return match extension.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "webp" => "image/webp",
+        "gif" => "image/gif",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "csv" => "text/csv",
+        "txt" => "text/plain",
+        _ => DEFAULT_CONTENT_TYPE,
+    }
+    .to_string();
+}
@@ -0,0 +1,374 @@
+use axum::{
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    AppState,
+    endpoints::MessageResponse,
+    notifications::{Notification, NotificationPlatform},
+    recipients::Recipient,
+    tenants::{DEFAULT_TENANT, default_tenant},
+    utils::ResponseFabric,
+};
+
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+// Raw keys look like "ntf_<64 hex chars>"
+const KEY_PREFIX: &str = "ntf_";
+// Length of the raw key beginning kept for identification
+const VISIBLE_KEY_LENGTH: usize = 12;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    // Instant notifications, replay of dead letters
+    Send,
+    // Create, edit, pause and delete scheduled notifications, templates and attachments.
+    // Delete topic subscriptions
+    Schedule,
+    // Read notifications, templates, attachments, deliveries, presses,
+    // subscriptions, jobs, telegram queue and dead letters
+    Read,
+    // Manage api keys. Implies all other scopes
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            Scope::Send => "send",
+            Scope::Schedule => "schedule",
+            Scope::Read => "read",
+            Scope::Admin => "admin",
+        };
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    // sha256 of the raw key, hex encoded. Raw key is never stored
+    pub key_hash: String,
+    // Beginning of the raw key, so it can be recognized in the list
+    pub key_prefix: String,
+    pub scopes: Vec<Scope>,
+    // None means any platform / recipient
+    pub platforms: Option<Vec<NotificationPlatform>>,
+    pub recipients: Option<Vec<String>>,
+    // Key sees and manages only notifications of its tenant
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
+    pub created_at: String, // Stringified UTC date
+}
+
+// Api key as shown to admins, without the hash
+#[derive(Debug, Serialize, Clone)]
+pub struct ApiKeyInfo {
+    pub id: String,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: Vec<Scope>,
+    pub platforms: Option<Vec<NotificationPlatform>>,
+    pub recipients: Option<Vec<String>>,
+    pub tenant: String,
+    pub created_at: String,
+}
+
+impl From<&ApiKey> for ApiKeyInfo {
+    fn from(key: &ApiKey) -> Self {
+        ApiKeyInfo {
+            id: key.id.clone(),
+            name: key.name.clone(),
+            key_prefix: key.key_prefix.clone(),
+            scopes: key.scopes.clone(),
+            platforms: key.platforms.clone(),
+            recipients: key.recipients.clone(),
+            tenant: key.tenant.clone(),
+            created_at: key.created_at.clone(),
+        }
+    }
+}
+
+impl ApiKey {
+    // Creates key record along with the raw key, which is shown to the caller once
+    pub fn generate(
+        name: String,
+        scopes: Vec<Scope>,
+        platforms: Option<Vec<NotificationPlatform>>,
+        recipients: Option<Vec<String>>,
+        tenant: String,
+    ) -> (Self, String) {
+        let bytes: [u8; 32] = rand::rng().random();
+        let raw = format!("{}{}", KEY_PREFIX, hex::encode(bytes));
+
+        let key = ApiKey {
+            id: uuid::Uuid::new_v4().to_string(),
+            name,
+            key_hash: hash_key(&raw),
+            key_prefix: raw[..VISIBLE_KEY_LENGTH].to_string(),
+            scopes,
+            platforms,
+            recipients,
+            tenant,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+
+        return (key, raw);
+    }
+
+    // Key from ADMIN_API_KEY env. It's not stored anywhere
+    fn bootstrap_admin(key_hash: String) -> Self {
+        return ApiKey {
+            id: "bootstrap".to_string(),
+            name: "ADMIN_API_KEY".to_string(),
+            key_hash,
+            key_prefix: String::new(),
+            scopes: vec![Scope::Admin],
+            platforms: None,
+            recipients: None,
+            tenant: default_tenant(),
+            created_at: String::new(),
+        };
+    }
+
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        return self.scopes.contains(&Scope::Admin) || self.scopes.contains(&scope);
+    }
+
+    // Err with a message suitable for 403 response
+    pub fn require(&self, scope: Scope) -> Result<(), String> {
+        if self.has_scope(scope.clone()) {
+            return Ok(());
+        }
+
+        return Err(format!("Api key has no \"{}\" scope", scope.as_str()));
+    }
+
+    // Whether key may target this recipient on this platform
+    pub fn allows(&self, platform: &NotificationPlatform, recipient: &Recipient) -> bool {
+        let platform_allowed = self
+            .platforms
+            .as_ref()
+            .is_none_or(|platforms| platforms.contains(platform));
+        let recipient_allowed = self
+            .recipients
+            .as_ref()
+            .is_none_or(|recipients| recipients.iter().any(|r| recipient.matches(r)));
+
+        return platform_allowed && recipient_allowed;
+    }
+
+    // Admin of the default tenant, manages tenants and keys of all tenants
+    pub fn is_root(&self) -> bool {
+        return self.has_scope(Scope::Admin) && self.tenant == DEFAULT_TENANT;
+    }
+
+    // Keys created by this key can't have more access than it has.
+    // Err with a message suitable for 403 response
+    pub fn can_grant(
+        &self,
+        scopes: &[Scope],
+        platforms: Option<&Vec<NotificationPlatform>>,
+        recipients: Option<&Vec<String>>,
+    ) -> Result<(), String> {
+        if let Some(scope) = scopes.iter().find(|s| !self.has_scope((*s).clone())) {
+            return Err(format!(
+                "Can't grant \"{}\" scope the api key doesn't have",
+                scope.as_str()
+            ));
+        }
+
+        if let Some(allowed) = &self.platforms {
+            let granted = match platforms {
+                Some(p) => p,
+                None => return Err("Can't grant access to all platforms".to_string()),
+            };
+            if let Some(p) = granted.iter().find(|p| !allowed.contains(p)) {
+                return Err(format!("Can't grant access to platform {:?}", p));
+            }
+        }
+
+        if let Some(allowed) = &self.recipients {
+            let granted = match recipients {
+                Some(r) => r,
+                None => return Err("Can't grant access to all recipients".to_string()),
+            };
+            // "telegram:123" is narrower than "123", which matches any platform
+            let covered = |r: &String| {
+                allowed.contains(r)
+                    || r.split_once(':')
+                        .is_some_and(|(_, id)| allowed.iter().any(|a| a == id))
+            };
+            if let Some(r) = granted.iter().find(|r| !covered(r)) {
+                return Err(format!("Can't grant access to recipient \"{}\"", r));
+            }
+        }
+
+        return Ok(());
+    }
+
+    pub fn has_unrestricted_access(&self) -> bool {
+        return self.platforms.is_none() && self.recipients.is_none();
+    }
+
+    pub fn allows_notification(&self, notification: &Notification) -> bool {
+        return self.allows(&notification.platform, &notification.send_to);
+    }
+}
+
+pub fn hash_key(raw: &str) -> String {
+    return hex::encode(Sha256::digest(raw.as_bytes()));
+}
+
+// Key is taken from "Authorization: Bearer <key>" or X-Api-Key header
+fn extract_key(request: &Request) -> Option<&str> {
+    let headers = request.headers();
+
+    if let Some(value) = headers.get(header::AUTHORIZATION)
+        && let Ok(value) = value.to_str()
+        && let Some(key) = value.strip_prefix("Bearer ")
+    {
+        return Some(key.trim());
+    }
+
+    return headers
+        .get(API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim);
+}
+
+// Authenticates request and passes the caller key to handlers
+// as an extension. Scopes are checked by handlers themselves
+pub async fn require_api_key(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let key_hash = match extract_key(&request) {
+        Some(raw) if !raw.is_empty() => hash_key(raw),
+        _ => {
+            return ResponseFabric::unauthorized::<MessageResponse>("Api key required")
+                .into_response();
+        }
+    };
+
+    let api_key = if state.admin_key_hash.as_deref() == Some(key_hash.as_str()) {
+        ApiKey::bootstrap_admin(key_hash)
+    } else {
+        match state.storage.get_api_key_by_hash(&key_hash).await {
+            Ok(Some(k)) => k,
+            Ok(None) => {
+                return ResponseFabric::unauthorized::<MessageResponse>("Invalid api key")
+                    .into_response();
+            }
+            Err(e) => {
+                tracing::error!("failed to look up api key: {}", e);
+                return ResponseFabric::internal_server_error::<MessageResponse>(
+                    "Failed to check api key",
+                )
+                .into_response();
+            }
+        }
+    };
+
+    request.extensions_mut().insert(api_key);
+    return next.run(request).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn restricted_admin() -> ApiKey {
+        let (key, _) = ApiKey::generate(
+            "ops".to_string(),
+            vec![Scope::Admin],
+            Some(vec![NotificationPlatform::Telegram]),
+            Some(vec!["123".to_string(), "456".to_string()]),
+            default_tenant(),
+        );
+        return key;
+    }
+
+    #[test]
+    fn restricted_admin_cant_grant_unrestricted_key() {
+        let admin = restricted_admin();
+        let recipients = vec!["123".to_string()];
+        let platforms = vec![NotificationPlatform::Telegram];
+
+        assert!(
+            admin
+                .can_grant(&[Scope::Send], None, Some(&recipients))
+                .is_err()
+        );
+        assert!(
+            admin
+                .can_grant(&[Scope::Send], Some(&platforms), None)
+                .is_err()
+        );
+        assert!(admin.can_grant(&[Scope::Admin], None, None).is_err());
+    }
+
+    #[test]
+    fn restricted_admin_cant_widen_platforms_or_recipients() {
+        let admin = restricted_admin();
+        let telegram = vec![NotificationPlatform::Telegram];
+        let email = vec![NotificationPlatform::Telegram, NotificationPlatform::Email];
+        let known = vec!["123".to_string()];
+        let unknown = vec!["123".to_string(), "789".to_string()];
+
+        assert!(
+            admin
+                .can_grant(&[Scope::Send], Some(&email), Some(&known))
+                .is_err()
+        );
+        assert!(
+            admin
+                .can_grant(&[Scope::Send], Some(&telegram), Some(&unknown))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn restricted_admin_grants_subset() {
+        let admin = restricted_admin();
+        let platforms = vec![NotificationPlatform::Telegram];
+        let recipients = vec!["456".to_string(), "telegram:123".to_string()];
+
+        assert!(
+            admin
+                .can_grant(
+                    &[Scope::Admin, Scope::Read],
+                    Some(&platforms),
+                    Some(&recipients)
+                )
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn unrestricted_admin_grants_anything() {
+        let admin = ApiKey::bootstrap_admin(String::new());
+        assert!(admin.can_grant(&[Scope::Admin], None, None).is_ok());
+    }
+
+    #[test]
+    fn scopes_are_limited_to_own() {
+        let (reader, _) = ApiKey::generate(
+            "reader".to_string(),
+            vec![Scope::Read],
+            None,
+            None,
+            default_tenant(),
+        );
+        assert!(reader.can_grant(&[Scope::Read], None, None).is_ok());
+        assert!(reader.can_grant(&[Scope::Send], None, None).is_err());
+    }
+}
@@ -0,0 +1,829 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use teloxide::{
+    dispatching::{ShutdownToken, UpdateHandler},
+    error_handlers::LoggingErrorHandler,
+    prelude::*,
+    types::{CallbackQuery, InlineKeyboardButtonKind},
+    update_listeners,
+    utils::command::BotCommands,
+};
+use tokio::{task::JoinHandle, time::sleep};
+use uuid::Uuid;
+
+use crate::{
+    notifications::{ButtonAction, Notification, NotificationKind},
+    recipients::Recipient,
+    scheduler::Scheduler,
+    storage::{self, NotificationStore},
+    tenants::{DEFAULT_TENANT, default_tenant},
+};
+
+const POLL_ERROR_DELAY: Duration = Duration::from_secs(5);
+// Notifications shown by /list, so the reply fits one message
+const MAX_LISTED: usize = 20;
+const LIST_PREVIEW_LENGTH: usize = 40;
+const MAX_TOPIC_LENGTH: usize = 64;
+
+// Press of notification callback button
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ButtonPress {
+    pub id: String,
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
+    pub notification_id: String,
+    pub action: ButtonAction,
+    pub button_text: Option<String>,
+    // Telegram user, who pressed the button
+    pub user_id: u64,
+    pub username: Option<String>,
+    pub chat_id: Option<i64>,
+    pub message_id: Option<i32>,
+    // Notification created by snooze button
+    pub snoozed_notification_id: Option<String>,
+    pub pressed_at: String, // Stringified UTC date
+}
+
+// Chat subscribed to a topic with /subscribe. Notifications sent
+// to the topic are delivered to every subscribed chat
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Subscription {
+    pub id: String,
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
+    pub topic: String,
+    pub chat_id: i64,
+    // Forum topic the command was sent from
+    pub thread_id: Option<i32>,
+    // Telegram user, who subscribed the chat
+    pub user_id: Option<u64>,
+    pub username: Option<String>,
+    pub created_at: String, // Stringified UTC date
+}
+
+impl Subscription {
+    pub fn recipient(&self) -> Recipient {
+        return Recipient::Telegram {
+            chat_id: self.chat_id,
+            thread_id: self.thread_id,
+        };
+    }
+}
+
+#[derive(BotCommands, Clone)]
+#[command(rename_rule = "lowercase", description = "Available commands:")]
+enum Command {
+    #[command(description = "show id of this chat")]
+    Start,
+    #[command(description = "show this message")]
+    Help,
+    #[command(description = "list notifications of this chat")]
+    List,
+    #[command(description = "pause notification by id")]
+    Pause(String),
+    #[command(description = "resume notification by id")]
+    Resume(String),
+    #[command(description = "delete notification by id")]
+    Stop(String),
+    #[command(description = "subscribe this chat to topic")]
+    Subscribe(String),
+    #[command(description = "unsubscribe this chat from topic")]
+    Unsubscribe(String),
+}
+
+// Topic is chosen by users, so it's kept short and simple
+pub fn validate_topic(topic: &str) -> Result<(), String> {
+    if topic.is_empty() || topic.len() > MAX_TOPIC_LENGTH {
+        return Err(format!(
+            "topic must be 1 to {} characters long",
+            MAX_TOPIC_LENGTH
+        ));
+    }
+
+    if !topic
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+    {
+        return Err(
+            "topic may contain only lowercase latin letters, digits, \"-\" and \"_\"".to_string(),
+        );
+    }
+
+    return Ok(());
+}
+
+// Callback data of notification button: "<uuid>:ack" or "<uuid>:snooze:<minutes>".
+// Telegram limits it to 64 bytes, so it carries ids only
+pub fn callback_data(notification_id: &str, action: &ButtonAction, snooze_minutes: u32) -> String {
+    return match action {
+        ButtonAction::Ack => format!("{}:{}", notification_id, action.as_str()),
+        ButtonAction::Snooze => {
+            format!("{}:{}:{}", notification_id, action.as_str(), snooze_minutes)
+        }
+    };
+}
+
+fn parse_callback_data(data: &str) -> Option<(String, ButtonAction, u32)> {
+    let mut parts = data.split(':');
+    let notification_id = Uuid::try_parse(parts.next()?).ok()?.to_string();
+
+    let (action, minutes) = match (parts.next()?, parts.next()) {
+        ("ack", None) => (ButtonAction::Ack, 0),
+        ("snooze", Some(minutes)) => (ButtonAction::Snooze, minutes.parse().ok()?),
+        _ => return None,
+    };
+
+    if parts.next().is_some() {
+        return None;
+    }
+
+    return Some((notification_id, action, minutes));
+}
+
+// Tenant of the bot, passed to dispatcher handlers. None is the server bot
+#[derive(Clone)]
+struct BotTenant(Option<String>);
+
+// Running dispatcher of one bot
+struct Poller {
+    token: String,
+    shutdown: ShutdownToken,
+    handle: JoinHandle<()>,
+}
+
+impl Poller {
+    // Graceful stop lets polling confirm received updates to telegram, so
+    // they aren't received again after restart. Dispatcher that didn't
+    // reach telegram yet has nothing to confirm and is aborted
+    async fn stop(self) {
+        match self.shutdown.shutdown() {
+            Ok(stopped) => stopped.await,
+            Err(_) => self.handle.abort(),
+        }
+
+        let _ = self.handle.await;
+    }
+}
+
+// Receives callback queries and commands of the server bot and
+// tenant bots with teloxide dispatcher, one dispatcher per bot token
+pub struct BotUpdates {
+    storage: Arc<dyn NotificationStore>,
+    scheduler: Arc<Scheduler>,
+    // Disabled with TELEGRAM_POLLING=false, e.g. when other
+    // instance of the service already polls the same bots
+    enabled: bool,
+    // Keyed by tenant, None is the server bot
+    pollers: Mutex<HashMap<Option<String>, Poller>>,
+}
+
+impl BotUpdates {
+    pub fn new(storage: Arc<dyn NotificationStore>, scheduler: Arc<Scheduler>) -> Self {
+        let enabled = !matches!(
+            env::var("TELEGRAM_POLLING").map(|v| v.trim().to_lowercase()),
+            Ok(v) if v == "false" || v == "0"
+        );
+
+        if !enabled {
+            tracing::info!("TELEGRAM_POLLING is disabled. Button presses are not received");
+        }
+
+        return BotUpdates {
+            storage,
+            scheduler,
+            enabled,
+            pollers: Mutex::new(HashMap::new()),
+        };
+    }
+
+    // Starts polling bot of the tenant, replacing the previous poller.
+    // None token stops polling. Token already polled for other tenant
+    // isn't polled twice, telegram allows one getUpdates consumer per bot
+    pub fn watch(self: &Arc<Self>, tenant: Option<String>, token: Option<String>) {
+        if !self.enabled {
+            return;
+        }
+
+        let mut pollers = match self.pollers.lock() {
+            Ok(p) => p,
+            Err(e) => e.into_inner(),
+        };
+
+        let previous = pollers.remove(&tenant);
+
+        let token = match token {
+            Some(t) => t,
+            None => {
+                if let Some(poller) = previous {
+                    tokio::spawn(poller.stop());
+                }
+                return;
+            }
+        };
+
+        if let Some((owner, _)) = pollers.iter().find(|(_, p)| p.token == token) {
+            tracing::warn!(
+                "bot of {} is already polled for {}, not polling it twice",
+                tenant.as_deref().unwrap_or("server"),
+                owner.as_deref().unwrap_or("server")
+            );
+            if let Some(poller) = previous {
+                tokio::spawn(poller.stop());
+            }
+            return;
+        }
+
+        let bot = Bot::new(token.clone());
+        let mut dispatcher = Dispatcher::builder(bot.clone(), handler())
+            .dependencies(dptree::deps![self.clone(), BotTenant(tenant.clone())])
+            .default_handler(|_| async {})
+            .error_handler(LoggingErrorHandler::with_custom_text(
+                "failed to handle bot update",
+            ))
+            .build();
+        let shutdown = dispatcher.shutdown_token();
+
+        let name = tenant.clone().unwrap_or("server".to_string());
+        let handle = tokio::spawn(async move {
+            // Previous dispatcher of the same bot must stop polling first
+            if let Some(poller) = previous {
+                poller.stop().await;
+            }
+
+            if let Err(e) = bot.set_my_commands(Command::bot_commands()).await {
+                tracing::warn!("failed to set commands of {} bot: {}", name, e);
+            }
+
+            // Fails only if bot info can't be received, e.g. telegram is unreachable
+            loop {
+                let listener = update_listeners::polling_default(bot.clone()).await;
+                match dispatcher
+                    .try_dispatch_with_listener(
+                        listener,
+                        LoggingErrorHandler::with_custom_text("failed to get bot updates"),
+                    )
+                    .await
+                {
+                    Ok(()) => break,
+                    Err(e) => {
+                        tracing::error!("failed to get {} bot info: {}", name, e);
+                        sleep(POLL_ERROR_DELAY).await;
+                    }
+                }
+            }
+        });
+
+        pollers.insert(
+            tenant,
+            Poller {
+                token,
+                shutdown,
+                handle,
+            },
+        );
+    }
+
+    // Stops all dispatchers, waiting for them to confirm received updates
+    pub async fn shutdown(&self) {
+        let pollers = {
+            let mut pollers = match self.pollers.lock() {
+                Ok(p) => p,
+                Err(e) => e.into_inner(),
+            };
+            pollers.drain().map(|(_, p)| p).collect::<Vec<_>>()
+        };
+
+        for poller in pollers {
+            poller.stop().await;
+        }
+    }
+
+    async fn handle_command(
+        &self,
+        tenant: Option<&str>,
+        bot: &Bot,
+        message: Message,
+        command: Command,
+    ) {
+        let answer = match self.command(tenant, bot, &message, command).await {
+            Ok(a) => a,
+            Err(e) => {
+                tracing::error!(
+                    "failed to handle command in chat {}: {}",
+                    message.chat.id,
+                    e
+                );
+                "Failed to handle command".to_string()
+            }
+        };
+
+        let mut request = bot.send_message(message.chat.id, answer);
+        if let Some(thread_id) = topic_thread(&message) {
+            request = request.message_thread_id(thread_id);
+        }
+
+        if let Err(e) = request.await {
+            tracing::warn!(
+                "failed to answer command in chat {}: {}",
+                message.chat.id,
+                e
+            );
+        }
+    }
+
+    // Performs command. Ok contains answer shown to the user
+    async fn command(
+        &self,
+        tenant: Option<&str>,
+        bot: &Bot,
+        message: &Message,
+        command: Command,
+    ) -> Result<String, String> {
+        let chat_id = message.chat.id.0;
+
+        // Anyone in the chat may look, only admins change anything in groups
+        let changes = !matches!(command, Command::Start | Command::Help | Command::List);
+        if changes && !self.is_admin(bot, message).await? {
+            return Ok("Only chat admins can do that".to_string());
+        }
+
+        return match command {
+            Command::Start => Ok(match topic_thread(message) {
+                Some(thread_id) => format!("Chat id: {}\nTopic id: {}", chat_id, thread_id),
+                None => format!("Chat id: {}", chat_id),
+            }),
+            Command::Help => Ok(Command::descriptions().to_string()),
+            Command::List => self.list(tenant, chat_id).await,
+            Command::Pause(id) => self.set_paused(tenant, chat_id, id.trim(), true).await,
+            Command::Resume(id) => self.set_paused(tenant, chat_id, id.trim(), false).await,
+            Command::Stop(id) => self.stop(tenant, chat_id, id.trim()).await,
+            Command::Subscribe(topic) => self.subscribe(tenant, message, topic.trim()).await,
+            Command::Unsubscribe(topic) => self.unsubscribe(tenant, message, topic.trim()).await,
+        };
+    }
+
+    async fn is_admin(&self, bot: &Bot, message: &Message) -> Result<bool, String> {
+        if message.chat.is_private() {
+            return Ok(true);
+        }
+
+        let user = match &message.from {
+            Some(u) => u,
+            None => return Ok(false),
+        };
+
+        let member = bot
+            .get_chat_member(message.chat.id, user.id)
+            .await
+            .map_err(|e| format!("failed to get chat member: {}", e))?;
+
+        return Ok(member.kind.is_privileged());
+    }
+
+    async fn list(&self, tenant: Option<&str>, chat_id: i64) -> Result<String, String> {
+        let mut notifications = Vec::new();
+        for tenant in self.served_tenants(tenant).await? {
+            for notification in self.storage.get_all_notifications(&tenant).await? {
+                if !notification.completed && sent_to_chat(&notification.send_to, Some(chat_id)) {
+                    notifications.push(notification);
+                }
+            }
+        }
+
+        if notifications.is_empty() {
+            return Ok("There are no notifications in this chat".to_string());
+        }
+
+        notifications.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+
+        let mut lines = Vec::new();
+        for notification in notifications.iter().take(MAX_LISTED) {
+            let mut line = format!("{} - {:?}", notification.uuid, notification.kind);
+            if notification.deactivated_reason.is_some() {
+                line.push_str(", deactivated");
+            } else if notification.paused {
+                line.push_str(", paused");
+            }
+
+            let preview = match &notification.template_id {
+                Some(template_id) => format!("template {}", template_id),
+                None => preview(&notification.text),
+            };
+            lines.push(format!("{}\n{}", line, preview));
+        }
+
+        if notifications.len() > MAX_LISTED {
+            lines.push(format!("...and {} more", notifications.len() - MAX_LISTED));
+        }
+
+        return Ok(lines.join("\n\n"));
+    }
+
+    async fn set_paused(
+        &self,
+        tenant: Option<&str>,
+        chat_id: i64,
+        id: &str,
+        paused: bool,
+    ) -> Result<String, String> {
+        let mut notification = match self.find(tenant, chat_id, id).await? {
+            Some(n) => n,
+            None => return Ok(not_found(id)),
+        };
+
+        notification.set_paused(paused);
+        self.storage.persist_notification(&notification).await?;
+
+        let found = match paused {
+            true => self.scheduler.pause(&notification.uuid)?,
+            false => self.scheduler.resume(&notification.uuid)?,
+        };
+
+        // Notification may be missing in scheduler if it failed
+        // to schedule on startup, so register it again
+        if !found {
+            self.scheduler.add_notification(&notification)?;
+        }
+
+        return Ok(match paused {
+            true => format!("Paused {}", notification.uuid),
+            false => format!("Resumed {}", notification.uuid),
+        });
+    }
+
+    async fn stop(&self, tenant: Option<&str>, chat_id: i64, id: &str) -> Result<String, String> {
+        let notification = match self.find(tenant, chat_id, id).await? {
+            Some(n) => n,
+            None => return Ok(not_found(id)),
+        };
+
+        self.scheduler.cancel(&notification.uuid)?;
+        self.storage
+            .delete_notification(&notification.tenant, &notification.uuid)
+            .await?;
+        storage::delete_history(
+            self.storage.as_ref(),
+            &notification.tenant,
+            &notification.uuid,
+        )
+        .await;
+
+        return Ok(format!("Deleted {}", notification.uuid));
+    }
+
+    async fn subscribe(
+        &self,
+        tenant: Option<&str>,
+        message: &Message,
+        topic: &str,
+    ) -> Result<String, String> {
+        let topic = topic.to_lowercase();
+        if let Err(e) = validate_topic(&topic) {
+            return Ok(format!("Usage: /subscribe <topic>. The {}", e));
+        }
+
+        let tenant = tenant.unwrap_or(DEFAULT_TENANT);
+        let thread_id = topic_thread(message).map(|t| t.0.0);
+
+        let subscriptions = self.storage.get_all_subscriptions(tenant).await?;
+        if subscriptions
+            .iter()
+            .any(|s| s.topic == topic && s.chat_id == message.chat.id.0 && s.thread_id == thread_id)
+        {
+            return Ok(format!("Already subscribed to {}", topic));
+        }
+
+        let subscription = Subscription {
+            id: Uuid::new_v4().to_string(),
+            tenant: tenant.to_string(),
+            topic: topic.clone(),
+            chat_id: message.chat.id.0,
+            thread_id,
+            user_id: message.from.as_ref().map(|u| u.id.0),
+            username: message.from.as_ref().and_then(|u| u.username.clone()),
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+        self.storage.persist_subscription(&subscription).await?;
+
+        return Ok(format!("Subscribed to {}", topic));
+    }
+
+    async fn unsubscribe(
+        &self,
+        tenant: Option<&str>,
+        message: &Message,
+        topic: &str,
+    ) -> Result<String, String> {
+        let topic = topic.to_lowercase();
+        let tenant = tenant.unwrap_or(DEFAULT_TENANT);
+        let thread_id = topic_thread(message).map(|t| t.0.0);
+
+        let mut removed = false;
+        for subscription in self.storage.get_all_subscriptions(tenant).await? {
+            if subscription.topic == topic
+                && subscription.chat_id == message.chat.id.0
+                && subscription.thread_id == thread_id
+            {
+                removed |= self
+                    .storage
+                    .delete_subscription(tenant, &subscription.id)
+                    .await?;
+            }
+        }
+
+        return Ok(match removed {
+            true => format!("Unsubscribed from {}", topic),
+            false => format!("Not subscribed to {}", topic),
+        });
+    }
+
+    // Stored notification sent to the chat
+    async fn find(
+        &self,
+        tenant: Option<&str>,
+        chat_id: i64,
+        id: &str,
+    ) -> Result<Option<Notification>, String> {
+        if Uuid::try_parse(id).is_err() {
+            return Ok(None);
+        }
+
+        for tenant in self.served_tenants(tenant).await? {
+            if self.storage.exists(&tenant, id).await? {
+                let notification = self.storage.get_notification(&tenant, id).await?;
+                if sent_to_chat(&notification.send_to, Some(chat_id)) {
+                    return Ok(Some(notification));
+                }
+                return Ok(None);
+            }
+        }
+
+        return Ok(None);
+    }
+
+    // Tenant bots serve their tenant only, server bot serves everyone
+    async fn served_tenants(&self, tenant: Option<&str>) -> Result<Vec<String>, String> {
+        if let Some(t) = tenant {
+            return Ok(vec![t.to_string()]);
+        }
+
+        let mut tenants = vec![DEFAULT_TENANT.to_string()];
+        for t in self.storage.get_all_tenants().await? {
+            if t.id != DEFAULT_TENANT {
+                tenants.push(t.id);
+            }
+        }
+
+        return Ok(tenants);
+    }
+
+    async fn handle_callback(&self, tenant: Option<&str>, bot: &Bot, query: CallbackQuery) {
+        let answer = match self.press(tenant, bot, &query).await {
+            Ok(a) => a,
+            Err(e) => {
+                tracing::error!("failed to handle button press {}: {}", query.id, e);
+                "Failed to handle button press".to_string()
+            }
+        };
+
+        if let Err(e) = bot
+            .answer_callback_query(query.id.clone())
+            .text(answer)
+            .await
+        {
+            tracing::warn!("failed to answer callback query {}: {}", query.id, e);
+        }
+    }
+
+    // Records press and performs it's action. Ok contains answer shown to the user
+    async fn press(
+        &self,
+        tenant: Option<&str>,
+        bot: &Bot,
+        query: &CallbackQuery,
+    ) -> Result<String, String> {
+        let (notification_id, action, minutes) =
+            match query.data.as_deref().and_then(parse_callback_data) {
+                Some(d) => d,
+                None => return Ok("Unknown button".to_string()),
+            };
+
+        let chat_id = query.message.as_ref().map(|m| m.chat().id.0);
+        let (tenant, notification) = match self.resolve(tenant, &notification_id, chat_id).await? {
+            Some(r) => r,
+            None => return Ok("Notification no longer exists".to_string()),
+        };
+
+        let mut press = ButtonPress {
+            id: Uuid::new_v4().to_string(),
+            tenant,
+            notification_id,
+            action: action.clone(),
+            button_text: button_text(query),
+            user_id: query.from.id.0,
+            username: query.from.username.clone(),
+            chat_id,
+            message_id: query.message.as_ref().map(|m| m.id().0),
+            snoozed_notification_id: None,
+            pressed_at: chrono::Utc::now().to_rfc3339(),
+        };
+
+        let answer = match action {
+            ButtonAction::Ack => "Acknowledged".to_string(),
+            ButtonAction::Snooze => {
+                let notification = match notification {
+                    Some(n) => n,
+                    None => return Ok("Notification no longer exists".to_string()),
+                };
+
+                let snoozed = snooze(&notification, minutes)?;
+                self.storage.persist_notification(&snoozed).await?;
+                self.scheduler.add_notification(&snoozed)?;
+                press.snoozed_notification_id = Some(snoozed.uuid);
+                format!("Snoozed for {} min", minutes)
+            }
+        };
+
+        self.storage.persist_button_press(&press).await?;
+
+        // Buttons are one-off, so the keyboard is removed from the message
+        if let Some(message) = &query.message
+            && let Err(e) = bot
+                .edit_message_reply_markup(message.chat().id, message.id())
+                .await
+        {
+            tracing::warn!("failed to remove buttons of message: {}", e);
+        }
+
+        return Ok(answer);
+    }
+
+    // Finds tenant of the pressed notification. Instant notifications aren't stored,
+    // so they are found by deliveries. Press must come from the chat notification was sent to
+    async fn resolve(
+        &self,
+        tenant: Option<&str>,
+        notification_id: &str,
+        chat_id: Option<i64>,
+    ) -> Result<Option<(String, Option<Notification>)>, String> {
+        for tenant in self.served_tenants(tenant).await? {
+            if self.storage.exists(&tenant, notification_id).await? {
+                let mut notification = self
+                    .storage
+                    .get_notification(&tenant, notification_id)
+                    .await?;
+                // Snooze of topic notification is sent to the pressing chat only
+                notification.send_to = match self
+                    .chat_recipient(&tenant, &notification.send_to, chat_id)
+                    .await?
+                {
+                    Some(r) => r,
+                    None => return Ok(None),
+                };
+                return Ok(Some((tenant, Some(notification))));
+            }
+
+            let (deliveries, _) = self
+                .storage
+                .get_deliveries(&tenant, notification_id, 0, 1)
+                .await?;
+            if let Some(delivery) = deliveries.first() {
+                if !sent_to_chat(&delivery.recipient, chat_id) {
+                    return Ok(None);
+                }
+                return Ok(Some((tenant, None)));
+            }
+        }
+
+        return Ok(None);
+    }
+
+    // Recipient of the notification in the chat. Topic notifications
+    // reach chats subscribed to the topic
+    async fn chat_recipient(
+        &self,
+        tenant: &str,
+        recipient: &Recipient,
+        chat_id: Option<i64>,
+    ) -> Result<Option<Recipient>, String> {
+        if let Recipient::Topic { topic } = recipient {
+            let subscriptions = self.storage.get_all_subscriptions(tenant).await?;
+            return Ok(subscriptions
+                .iter()
+                .find(|s| &s.topic == topic && Some(s.chat_id) == chat_id)
+                .map(|s| s.recipient()));
+        }
+
+        return Ok(sent_to_chat(recipient, chat_id).then(|| recipient.clone()));
+    }
+}
+
+// Commands of the bot and presses of notification buttons, other updates are ignored
+fn handler() -> UpdateHandler<String> {
+    return dptree::entry()
+        .branch(
+            Update::filter_message()
+                .filter_command::<Command>()
+                .endpoint(on_command),
+        )
+        .branch(Update::filter_callback_query().endpoint(on_callback));
+}
+
+async fn on_command(
+    updates: Arc<BotUpdates>,
+    tenant: BotTenant,
+    bot: Bot,
+    message: Message,
+    command: Command,
+) -> Result<(), String> {
+    updates
+        .handle_command(tenant.0.as_deref(), &bot, message, command)
+        .await;
+    return Ok(());
+}
+
+async fn on_callback(
+    updates: Arc<BotUpdates>,
+    tenant: BotTenant,
+    bot: Bot,
+    query: CallbackQuery,
+) -> Result<(), String> {
+    updates
+        .handle_callback(tenant.0.as_deref(), &bot, query)
+        .await;
+    return Ok(());
+}
+
+fn sent_to_chat(recipient: &Recipient, chat_id: Option<i64>) -> bool {
+    return match recipient {
+        Recipient::Telegram { chat_id: id, .. } => Some(*id) == chat_id,
+        _ => false,
+    };
+}
+
+// Forum topic of the message. Replies in regular groups have thread id too
+fn topic_thread(message: &Message) -> Option<teloxide::types::ThreadId> {
+    return match message.is_topic_message {
+        true => message.thread_id,
+        false => None,
+    };
+}
+
+fn preview(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= LIST_PREVIEW_LENGTH {
+        return text;
+    }
+
+    return format!(
+        "{}...",
+        text.chars().take(LIST_PREVIEW_LENGTH).collect::<String>()
+    );
+}
+
+fn not_found(id: &str) -> String {
+    if id.is_empty() {
+        return "Notification id is required, see /list".to_string();
+    }
+
+    return format!("Notification {} not found in this chat", id);
+}
+
+// Text of the pressed button, taken from the message keyboard
+fn button_text(query: &CallbackQuery) -> Option<String> {
+    let data = query.data.as_ref()?;
+    let markup = query.regular_message()?.reply_markup()?;
+
+    return markup
+        .inline_keyboard
+        .iter()
+        .flatten()
+        .find(|b| matches!(&b.kind, InlineKeyboardButtonKind::CallbackData(d) if d == data))
+        .map(|b| b.text.clone());
+}
+
+// One-off copy of notification, sent again in `minutes`
+fn snooze(notification: &Notification, minutes: u32) -> Result<Notification, String> {
+    let tz = notification.tz()?;
+    let send_at =
+        (chrono::Utc::now() + chrono::Duration::minutes(minutes as i64)).with_timezone(&tz);
+
+    let mut snoozed = notification.clone();
+    snoozed.uuid = Uuid::new_v4().to_string();
+    snoozed.kind = NotificationKind::Scheduled;
+    snoozed.send_at = Some(send_at.to_rfc3339());
+    snoozed.daily_send_timestamps = Vec::new();
+    snoozed.cron = None;
+    snoozed.completed = false;
+    snoozed.set_paused(false);
+    snoozed.send_count = 0;
+    snoozed.last_sent = None;
+    snoozed.created_at = chrono::Local::now().to_string();
+
+    return Ok(snoozed);
+}
@@ -0,0 +1,922 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{env, sync::Arc, time::Duration};
+use tokio::time::sleep;
+use uuid::Uuid;
+
+use crate::{
+    events::{Event, Events},
+    notifications::{Button, Notification, NotificationKind, NotificationPlatform},
+    notificators::{Notificators, SendError, SendReceipt},
+    recipients::Recipient,
+    storage::NotificationStore,
+    templates,
+    tenants::default_tenant,
+};
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+const DEFAULT_BASE_DELAY_MS: u64 = 1000;
+const DEFAULT_MAX_DELAY_MS: u64 = 60_000;
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    // Total number of tries, including the first one
+    pub max_attempts: u32,
+    // Delay before the second try, doubled for every next one
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    // Randomize delays, so failed sends don't retry in lockstep
+    pub jitter: bool,
+}
+
+impl RetryPolicy {
+    pub fn from_env() -> Result<Self, String> {
+        let max_attempts = parse_env("RETRY_MAX_ATTEMPTS", DEFAULT_MAX_ATTEMPTS)?;
+        if max_attempts == 0 {
+            return Err("RETRY_MAX_ATTEMPTS must be at least 1".to_string());
+        }
+
+        let jitter = match env::var("RETRY_JITTER") {
+            Ok(v) => match v.trim().to_lowercase().as_str() {
+                "true" | "1" => true,
+                "false" | "0" => false,
+                _ => return Err(format!("invalid RETRY_JITTER: \"{}\"", v)),
+            },
+            Err(_) => true,
+        };
+
+        return Ok(RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(parse_env(
+                "RETRY_BASE_DELAY_MS",
+                DEFAULT_BASE_DELAY_MS,
+            )?),
+            max_delay: Duration::from_millis(parse_env(
+                "RETRY_MAX_DELAY_MS",
+                DEFAULT_MAX_DELAY_MS,
+            )?),
+            jitter,
+        });
+    }
+
+    // Delay after failed attempt number `attempt`. Platform's retry_after
+    // wins, since retrying earlier is pointless. None if the platform asks
+    // to wait longer than max_delay, then the delivery isn't retried
+    pub fn delay(&self, attempt: u32, error: &SendError) -> Option<Duration> {
+        if let Some(retry_after) = error.retry_after {
+            return (retry_after <= self.max_delay).then_some(retry_after);
+        }
+
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+
+        if !self.jitter || delay.is_zero() {
+            return Some(delay);
+        }
+
+        // "Equal jitter": somewhere between half and full delay
+        let half = delay / 2;
+        return Some(half + rand::rng().random_range(Duration::ZERO..=half));
+    }
+}
+
+fn parse_env<T: std::str::FromStr>(name: &str, default: T) -> Result<T, String> {
+    return match env::var(name) {
+        Ok(v) => v
+            .trim()
+            .parse::<T>()
+            .map_err(|_| format!("invalid {}: \"{}\"", name, v)),
+        Err(_) => Ok(default),
+    };
+}
+
+// Delivery that failed after all retries
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeadLetter {
+    pub id: String,
+    pub notification: Notification,
+    pub error: String,
+    pub attempts: u32,
+    pub failed_at: String, // Stringified UTC date
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum DeliveryOutcome {
+    Sent,
+    Failed,
+}
+
+// Single attempt to deliver notification
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeliveryRecord {
+    pub id: String,
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
+    pub notification_id: String,
+    pub attempt: u32,
+    pub platform: NotificationPlatform,
+    pub recipient: Recipient,
+    pub outcome: DeliveryOutcome,
+    pub error: Option<String>,
+    // Ids of sent messages on the platform side, e.g. telegram message_id
+    pub provider_message_ids: Vec<String>,
+    pub attempted_at: String, // Stringified UTC date
+    // Edits and deletion of sent messages, oldest first
+    #[serde(default)]
+    pub actions: Vec<DeliveryAction>,
+    // Leading provider messages, which carry attachments. Text is in their
+    // caption, unless it was sent in messages after them
+    #[serde(default)]
+    pub attachment_messages: usize,
+    // Inline keyboard of the last message, kept when text is edited
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buttons: Vec<Vec<Button>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryActionKind {
+    Edit,
+    Delete,
+}
+
+// Change of already sent messages
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeliveryAction {
+    pub kind: DeliveryActionKind,
+    pub outcome: DeliveryOutcome,
+    pub error: Option<String>,
+    // New text of edited messages
+    pub text: Option<String>,
+    pub performed_at: String, // Stringified UTC date
+}
+
+impl DeliveryRecord {
+    // Messages were deleted, nothing left to change
+    pub fn is_deleted(&self) -> bool {
+        return self
+            .actions
+            .iter()
+            .any(|a| a.kind == DeliveryActionKind::Delete && a.outcome == DeliveryOutcome::Sent);
+    }
+}
+
+// Outcome of successful delivery
+#[derive(Debug, Clone, PartialEq)]
+pub enum Delivered {
+    Sent,
+    // First attempt failed, the rest are made in background
+    Retrying,
+}
+
+// Sends notifications through notificators, applying retry policy.
+// Deliveries that still fail are moved into dead letters
+pub struct Dispatcher {
+    pub notificators: Arc<Notificators>,
+    storage: Arc<dyn NotificationStore>,
+    policy: RetryPolicy,
+    events: Arc<Events>,
+}
+
+impl Dispatcher {
+    pub fn new(
+        notificators: Arc<Notificators>,
+        storage: Arc<dyn NotificationStore>,
+        policy: RetryPolicy,
+        events: Arc<Events>,
+    ) -> Self {
+        Dispatcher {
+            notificators,
+            storage,
+            policy,
+            events,
+        }
+    }
+
+    // Tries to send notification until success or attempts up to `last_attempt`
+    // are exhausted. Returns number of the last attempt along with the result
+    async fn try_deliver(
+        &self,
+        notification: &Notification,
+        first_attempt: u32,
+        last_attempt: u32,
+    ) -> (u32, Result<(), SendError>) {
+        // Rendered once, so all retries send the same text.
+        // Failed render counts as a failed attempt, retrying won't help it
+        let mut notification = match self.render(notification).await {
+            Ok(n) => n,
+            Err(e) => {
+                let error = SendError::from(e);
+                self.record_attempt(notification, first_attempt, &Err(error.clone()))
+                    .await;
+                return (first_attempt, Err(error));
+            }
+        };
+        let mut attempt = first_attempt;
+        let mut migrated = false;
+
+        loop {
+            let result = self.notificators.send(&notification, attempt).await;
+            self.record_attempt(&notification, attempt, &result).await;
+
+            let error = match result {
+                Ok(_) => return (attempt, Ok(())),
+                Err(e) => e,
+            };
+
+            // Sent again to the new chat right away, the old one is gone
+            if let Some(chat_id) = error.migrated_to
+                && !migrated
+            {
+                self.migrate(&mut notification, chat_id).await;
+                migrated = true;
+                attempt += 1;
+                continue;
+            }
+
+            if !error.retryable || attempt >= last_attempt {
+                return (attempt, Err(error));
+            }
+
+            let delay = match self.policy.delay(attempt, &error) {
+                Some(d) => d,
+                None => {
+                    return (
+                        attempt,
+                        Err(SendError {
+                            message: format!(
+                                "{}. Platform asked to wait {:?}, more than retry delay limit",
+                                error,
+                                error.retry_after.unwrap_or_default()
+                            ),
+                            ..error
+                        }),
+                    );
+                }
+            };
+            tracing::warn!(
+                "attempt {} to send notification {} failed: {}. Retrying in {:?}",
+                attempt,
+                notification.uuid,
+                error,
+                delay
+            );
+
+            sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    // Group was upgraded to supergroup, which has new chat id. Stored
+    // notification and subscriptions of the old chat are moved to it
+    async fn migrate(&self, notification: &mut Notification, chat_id: i64) {
+        let old_chat_id = match &mut notification.send_to {
+            Recipient::Telegram { chat_id: id, .. } => std::mem::replace(id, chat_id),
+            _ => return,
+        };
+
+        tracing::warn!(
+            "chat {} was upgraded to supergroup {}, notification {} is sent there",
+            old_chat_id,
+            chat_id,
+            notification.uuid
+        );
+
+        if notification.kind.is_scheduled() {
+            match self
+                .storage
+                .get_notification(&notification.tenant, &notification.uuid)
+                .await
+            {
+                Ok(mut stored) => {
+                    if let Recipient::Telegram { chat_id: id, .. } = &mut stored.send_to
+                        && *id == old_chat_id
+                    {
+                        *id = chat_id;
+                        if let Err(e) = self.storage.persist_notification(&stored).await {
+                            tracing::error!(
+                                "failed to save new chat id of notification {}: {}",
+                                notification.uuid,
+                                e
+                            );
+                        }
+                    }
+                }
+                Err(e) => tracing::warn!(
+                    "failed to load notification {} to update chat id: {}",
+                    notification.uuid,
+                    e
+                ),
+            }
+        }
+
+        let subscriptions = match self
+            .storage
+            .get_all_subscriptions(&notification.tenant)
+            .await
+        {
+            Ok(s) => s,
+            Err(e) => {
+                tracing::error!("failed to get subscriptions to update chat id: {}", e);
+                return;
+            }
+        };
+
+        for mut subscription in subscriptions
+            .into_iter()
+            .filter(|s| s.chat_id == old_chat_id)
+        {
+            subscription.chat_id = chat_id;
+            if let Err(e) = self.storage.persist_subscription(&subscription).await {
+                tracing::error!(
+                    "failed to save new chat id of subscription {}: {}",
+                    subscription.id,
+                    e
+                );
+            }
+        }
+    }
+
+    // Copy of notification with text of its template, if it has one
+    async fn render(&self, notification: &Notification) -> Result<Notification, String> {
+        let mut rendered = notification.clone();
+        let template_id = match &notification.template_id {
+            Some(id) => id,
+            None => return Ok(rendered),
+        };
+
+        let template = match self
+            .storage
+            .get_template(&notification.tenant, template_id)
+            .await?
+        {
+            Some(t) => t,
+            None => return Err(format!("Template {} not found", template_id)),
+        };
+
+        // Scheduler jobs keep a copy made when they were armed,
+        // so the up to date counter is taken from storage
+        if notification.kind.is_scheduled()
+            && let Ok(stored) = self
+                .storage
+                .get_notification(&notification.tenant, &notification.uuid)
+                .await
+        {
+            rendered.send_count = stored.send_count;
+        }
+
+        rendered.text = templates::render(&template.body, &rendered)?;
+        return Ok(rendered);
+    }
+
+    // Recording is best effort: failing to save history must not fail the send
+    async fn record_attempt(
+        &self,
+        notification: &Notification,
+        attempt: u32,
+        result: &Result<SendReceipt, SendError>,
+    ) {
+        let (outcome, error, provider_message_ids) = match result {
+            Ok(receipt) => (DeliveryOutcome::Sent, None, receipt.message_ids.clone()),
+            Err(e) => (
+                DeliveryOutcome::Failed,
+                Some(e.to_string()),
+                e.message_ids.clone(),
+            ),
+        };
+
+        let record = DeliveryRecord {
+            id: Uuid::new_v4().to_string(),
+            tenant: notification.tenant.clone(),
+            notification_id: notification.uuid.clone(),
+            attempt,
+            platform: notification.platform.clone(),
+            recipient: notification.send_to.clone(),
+            outcome,
+            error,
+            provider_message_ids,
+            attempted_at: chrono::Utc::now().to_rfc3339(),
+            actions: Vec::new(),
+            attachment_messages: notification.attachments.len(),
+            buttons: notification.buttons.clone(),
+        };
+
+        if let Err(e) = self.storage.persist_delivery(&record).await {
+            tracing::error!(
+                "failed to record delivery of notification {}: {}",
+                notification.uuid,
+                e
+            );
+        }
+    }
+
+    // Only scheduled notifications are persisted. Fresh copy is loaded
+    // from storage, so edits made while sending are not overwritten
+    async fn update_last_sent(&self, notification: &Notification) {
+        if !notification.kind.is_scheduled() {
+            return;
+        }
+
+        let mut stored = match self
+            .storage
+            .get_notification(&notification.tenant, &notification.uuid)
+            .await
+        {
+            Ok(n) => n,
+            Err(e) => {
+                tracing::warn!(
+                    "failed to load notification {} to update last_sent: {}",
+                    notification.uuid,
+                    e
+                );
+                return;
+            }
+        };
+
+        stored.last_sent = Some(chrono::Utc::now().to_rfc3339());
+        stored.send_count += 1;
+        // Replayed dead letter of a one-shot notification completes it
+        if stored.kind == NotificationKind::Scheduled {
+            stored.completed = true;
+        }
+        if let Err(e) = self.storage.persist_notification(&stored).await {
+            tracing::error!(
+                "failed to save last_sent of notification {}: {}",
+                notification.uuid,
+                e
+            );
+        }
+    }
+
+    // Sends notification, retrying failed attempts. Delivery that failed
+    // after all retries is saved as dead letter, error contains it's id if
+    // it was saved. Permanent failure deactivates the notification.
+    // Notification sent to a topic is delivered to every subscribed chat
+    pub async fn deliver(self: &Arc<Self>, notification: &Notification) -> Result<(), SendError> {
+        return self.deliver_with(notification, false).await.map(|_| ());
+    }
+
+    // Instant send answers the api request, so only the first attempt is made
+    // in place. Retries go on in background, failing the same way as deliver
+    pub async fn deliver_instant(
+        self: &Arc<Self>,
+        notification: &Notification,
+    ) -> Result<Delivered, SendError> {
+        return self.deliver_with(notification, true).await;
+    }
+
+    async fn deliver_with(
+        self: &Arc<Self>,
+        notification: &Notification,
+        retry_in_background: bool,
+    ) -> Result<Delivered, SendError> {
+        if let Recipient::Topic { topic } = &notification.send_to {
+            return self
+                .deliver_to_subscribers(notification, topic, retry_in_background)
+                .await;
+        }
+
+        let result = self
+            .deliver_to(notification, None, retry_in_background)
+            .await;
+        if let Ok(Delivered::Sent) = result {
+            self.update_last_sent(notification).await;
+        }
+
+        return result;
+    }
+
+    // Every subscriber gets own copy with own delivery history and dead
+    // letter. Unreachable chat is unsubscribed instead of deactivating
+    // the notification, which the other subscribers still receive
+    async fn deliver_to_subscribers(
+        self: &Arc<Self>,
+        notification: &Notification,
+        topic: &str,
+        retry_in_background: bool,
+    ) -> Result<Delivered, SendError> {
+        let subscriptions = self
+            .storage
+            .get_all_subscriptions(&notification.tenant)
+            .await
+            .map_err(SendError::from)?;
+
+        let mut sent = false;
+        let mut retrying = false;
+        let mut failures = Vec::new();
+        for subscription in subscriptions.iter().filter(|s| s.topic == topic) {
+            let mut copy = notification.clone();
+            copy.send_to = subscription.recipient();
+
+            match self
+                .deliver_to(&copy, Some(&subscription.id), retry_in_background)
+                .await
+            {
+                Ok(Delivered::Sent) => sent = true,
+                Ok(Delivered::Retrying) => retrying = true,
+                Err(e) => failures.push(format!("{}: {}", copy.send_to, e)),
+            }
+        }
+
+        if sent {
+            self.update_last_sent(notification).await;
+        } else if !retrying && failures.is_empty() {
+            tracing::info!(
+                "notification {} is not sent, no chats are subscribed to topic {}",
+                notification.uuid,
+                topic
+            );
+        }
+
+        if !failures.is_empty() {
+            return Err(SendError::from(format!(
+                "failed to send to {} subscribers of topic {}: {}",
+                failures.len(),
+                topic,
+                failures.join("; ")
+            )));
+        }
+
+        return Ok(match retrying {
+            true => Delivered::Retrying,
+            false => Delivered::Sent,
+        });
+    }
+
+    // Delivers to concrete recipient. Subscription is set for a topic subscriber
+    async fn deliver_to(
+        self: &Arc<Self>,
+        notification: &Notification,
+        subscription_id: Option<&str>,
+        retry_in_background: bool,
+    ) -> Result<Delivered, SendError> {
+        let last_attempt = match retry_in_background {
+            true => 1,
+            false => self.policy.max_attempts,
+        };
+
+        let (attempt, result) = self.try_deliver(notification, 1, last_attempt).await;
+        let error = match result {
+            Ok(_) => return Ok(Delivered::Sent),
+            Err(e) => e,
+        };
+
+        if retry_in_background
+            && error.retryable
+            && attempt < self.policy.max_attempts
+            && let Some(delay) = self.policy.delay(attempt, &error)
+        {
+            tracing::warn!(
+                "attempt {} to send notification {} failed: {}. Retrying in background in {:?}",
+                attempt,
+                notification.uuid,
+                error,
+                delay
+            );
+
+            let dispatcher = self.clone();
+            let notification = notification.clone();
+            let subscription_id = subscription_id.map(|id| id.to_string());
+            tokio::spawn(async move {
+                sleep(delay).await;
+                let (attempt, result) = dispatcher
+                    .try_deliver(&notification, attempt + 1, dispatcher.policy.max_attempts)
+                    .await;
+                if let Err(e) = result {
+                    let _ = dispatcher
+                        .fail(&notification, subscription_id.as_deref(), attempt, e)
+                        .await;
+                }
+            });
+
+            return Ok(Delivered::Retrying);
+        }
+
+        return Err(self
+            .fail(notification, subscription_id, attempt, error)
+            .await);
+    }
+
+    // Handles delivery that failed for good. Permanent failure deactivates
+    // the notification or drops the subscription, retryable one is saved
+    // as dead letter
+    async fn fail(
+        &self,
+        notification: &Notification,
+        subscription_id: Option<&str>,
+        attempts: u32,
+        error: SendError,
+    ) -> SendError {
+        tracing::error!(
+            "failed to send notification {} to {} after {} attempts: {}",
+            notification.uuid,
+            notification.send_to,
+            attempts,
+            error
+        );
+
+        if error.permanent {
+            match subscription_id {
+                Some(id) => self.unsubscribe(notification, id, &error.message).await,
+                None => self.deactivate(notification, &error.message).await,
+            }
+        }
+
+        // Replaying won't help either, failure is kept in delivery history
+        if !error.retryable {
+            return error;
+        }
+
+        let dead_letter = DeadLetter {
+            id: Uuid::new_v4().to_string(),
+            notification: notification.clone(),
+            error: error.to_string(),
+            attempts,
+            failed_at: chrono::Utc::now().to_rfc3339(),
+        };
+
+        if let Err(e) = self.storage.persist_dead_letter(&dead_letter).await {
+            tracing::error!("failed to save dead letter: {}", e);
+            return error;
+        }
+
+        return SendError {
+            message: format!("{}. Saved as dead letter {}", error, dead_letter.id),
+            ..error
+        };
+    }
+
+    async fn unsubscribe(&self, notification: &Notification, subscription_id: &str, reason: &str) {
+        match self
+            .storage
+            .delete_subscription(&notification.tenant, subscription_id)
+            .await
+        {
+            Ok(_) => tracing::warn!(
+                "{} is unsubscribed from topic: {}",
+                notification.send_to,
+                reason
+            ),
+            Err(e) => tracing::error!("failed to delete subscription {}: {}", subscription_id, e),
+        }
+
+        self.events.emit(
+            &notification.tenant,
+            Event::RecipientUnreachable {
+                notification_id: notification.uuid.clone(),
+                kind: notification.kind.clone(),
+                recipient: notification.send_to.clone(),
+                reason: reason.to_string(),
+                deactivated: false,
+            },
+        );
+    }
+
+    // Pauses stored notification with the reason and tells the owning
+    // system about it. Scheduler job is disarmed by the scheduler itself
+    async fn deactivate(&self, notification: &Notification, reason: &str) {
+        let mut deactivated = false;
+
+        if notification.kind.is_scheduled() {
+            match self
+                .storage
+                .get_notification(&notification.tenant, &notification.uuid)
+                .await
+            {
+                Ok(mut stored) => {
+                    stored.paused = true;
+                    stored.deactivated_reason = Some(reason.to_string());
+                    stored.deactivated_at = Some(chrono::Utc::now().to_rfc3339());
+
+                    match self.storage.persist_notification(&stored).await {
+                        Ok(_) => deactivated = true,
+                        Err(e) => tracing::error!(
+                            "failed to deactivate notification {}: {}",
+                            notification.uuid,
+                            e
+                        ),
+                    }
+                }
+                Err(e) => tracing::warn!(
+                    "failed to load notification {} to deactivate it: {}",
+                    notification.uuid,
+                    e
+                ),
+            }
+        }
+
+        if deactivated {
+            tracing::warn!(
+                "notification {} is deactivated: {}",
+                notification.uuid,
+                reason
+            );
+        }
+
+        self.events.emit(
+            &notification.tenant,
+            Event::RecipientUnreachable {
+                notification_id: notification.uuid.clone(),
+                kind: notification.kind.clone(),
+                recipient: notification.send_to.clone(),
+                reason: reason.to_string(),
+                deactivated,
+            },
+        );
+    }
+
+    // Sends dead letter again. It's removed on success,
+    // otherwise updated with the new error
+    pub async fn replay(&self, mut dead_letter: DeadLetter) -> Result<(), String> {
+        let (attempts, result) = self
+            .try_deliver(&dead_letter.notification, 1, self.policy.max_attempts)
+            .await;
+        match result {
+            Ok(_) => {
+                self.update_last_sent(&dead_letter.notification).await;
+                self.storage
+                    .delete_dead_letter(&dead_letter.notification.tenant, &dead_letter.id)
+                    .await?;
+                return Ok(());
+            }
+            Err(e) => {
+                dead_letter.attempts += attempts;
+                dead_letter.error = e.to_string();
+                dead_letter.failed_at = chrono::Utc::now().to_rfc3339();
+                self.storage.persist_dead_letter(&dead_letter).await?;
+                return Err(e.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bot::Subscription, notifications::NotificationBuilder, notificators::TelegramNotificator,
+        storage::memory::MemoryStore,
+    };
+
+    fn policy(jitter: bool) -> RetryPolicy {
+        return RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            jitter,
+        };
+    }
+
+    fn error(retry_after: Option<Duration>) -> SendError {
+        return SendError {
+            retry_after,
+            ..SendError::from("failed".to_string())
+        };
+    }
+
+    #[test]
+    fn delay_doubles_up_to_max_delay() {
+        let policy = policy(false);
+        let delays: Vec<u128> = (1..=6)
+            .map(|attempt| policy.delay(attempt, &error(None)).unwrap().as_millis())
+            .collect();
+        assert_eq!(delays, vec![100, 200, 400, 800, 1000, 1000]);
+
+        // Large attempt numbers don't overflow
+        assert_eq!(
+            policy.delay(u32::MAX, &error(None)),
+            Some(Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn delay_with_jitter_stays_between_half_and_full() {
+        let jittered = policy(true);
+        let exact = policy(false);
+        for attempt in 1..=5 {
+            let full = exact.delay(attempt, &error(None)).unwrap();
+            let delay = jittered.delay(attempt, &error(None)).unwrap();
+            assert!(delay >= full / 2 && delay <= full, "{:?}", delay);
+        }
+    }
+
+    #[test]
+    fn delay_uses_platform_retry_after_within_max_delay() {
+        let policy = policy(true);
+        let retry_after = Some(Duration::from_millis(700));
+        assert_eq!(policy.delay(1, &error(retry_after)), retry_after);
+
+        // Waiting longer than allowed fails the delivery instead
+        let retry_after = Some(Duration::from_secs(30));
+        assert_eq!(policy.delay(1, &error(retry_after)), None);
+    }
+
+    fn dispatcher(storage: Arc<dyn NotificationStore>) -> Arc<Dispatcher> {
+        let notificators = Arc::new(Notificators {
+            telegram: Arc::new(TelegramNotificator::new(
+                "123:abc".to_string(),
+                storage.clone(),
+            )),
+            email: None,
+            webhook: None,
+        });
+        let events = Arc::new(Events::new(None, storage.clone()).unwrap());
+        return Arc::new(Dispatcher::new(
+            notificators,
+            storage,
+            policy(false),
+            events,
+        ));
+    }
+
+    fn subscription(topic: &str, chat_id: i64) -> Subscription {
+        return Subscription {
+            id: Uuid::new_v4().to_string(),
+            tenant: default_tenant(),
+            topic: topic.to_string(),
+            chat_id,
+            thread_id: None,
+            user_id: None,
+            username: None,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+    }
+
+    #[tokio::test]
+    async fn topic_reaches_only_its_subscribers() {
+        let storage: Arc<dyn NotificationStore> = Arc::new(MemoryStore::new());
+        let dispatcher = dispatcher(storage.clone());
+
+        let subscription = subscription("releases", 42);
+        storage.persist_subscription(&subscription).await.unwrap();
+
+        let notification = NotificationBuilder::new()
+            .platform(NotificationPlatform::Telegram)
+            .send_to(Recipient::Topic {
+                topic: "deploys".to_string(),
+            })
+            .text("hello".to_string())
+            .build();
+
+        // Nobody is subscribed to the topic, so nothing is sent
+        assert!(dispatcher.deliver(&notification).await.is_ok());
+        let (deliveries, total) = storage
+            .get_deliveries(&notification.tenant, &notification.uuid, 0, 10)
+            .await
+            .unwrap();
+        assert!(deliveries.is_empty());
+        assert_eq!(total, 0);
+    }
+
+    #[tokio::test]
+    async fn migration_moves_notification_and_subscriptions() {
+        let storage: Arc<dyn NotificationStore> = Arc::new(MemoryStore::new());
+        let dispatcher = dispatcher(storage.clone());
+
+        let mut notification = NotificationBuilder::new()
+            .kind(NotificationKind::Cron)
+            .platform(NotificationPlatform::Telegram)
+            .send_to(Recipient::Telegram {
+                chat_id: -42,
+                thread_id: None,
+            })
+            .text("hello".to_string())
+            .build();
+        notification.cron = Some("0 9 * * *".to_string());
+        storage.persist_notification(&notification).await.unwrap();
+        storage
+            .persist_subscription(&subscription("releases", -42))
+            .await
+            .unwrap();
+        storage
+            .persist_subscription(&subscription("releases", 7))
+            .await
+            .unwrap();
+
+        dispatcher.migrate(&mut notification, -100042).await;
+
+        let moved = Recipient::Telegram {
+            chat_id: -100042,
+            thread_id: None,
+        };
+        assert_eq!(notification.send_to, moved);
+        let stored = storage
+            .get_notification(&notification.tenant, &notification.uuid)
+            .await
+            .unwrap();
+        assert_eq!(stored.send_to, moved);
+
+        let mut chats: Vec<i64> = storage
+            .get_all_subscriptions(&notification.tenant)
+            .await
+            .unwrap()
+            .iter()
+            .map(|s| s.chat_id)
+            .collect();
+        chats.sort();
+        assert_eq!(chats, vec![-100042, 7]);
+    }
+}
@@ -1,25 +1,177 @@
 use axum::{
-    Json,
-    extract::{Path, State},
+    Extension, Json,
+    extract::{Multipart, Path, Query, State},
     http::StatusCode,
 };
+use serde_json::Value;
 use uuid::Uuid;
 
 use crate::{
     AppState,
-    notifications::{Notification, NotificationBuilder, NotificationKind, NotificationPlatform},
-    utils::{ResponseFabric, rfc3339_to_local},
+    attachments::{self, Attachment},
+    auth::{ApiKey, ApiKeyInfo, Scope},
+    bot::{ButtonPress, Subscription},
+    dispatcher::{
+        DeadLetter, Delivered, DeliveryAction, DeliveryActionKind, DeliveryOutcome, DeliveryRecord,
+    },
+    events, formatting,
+    notifications::{
+        AttachmentRef, AttachmentSource, Button, ButtonAction, LinkPreview, MessageFormat,
+        Notification, NotificationBuilder, NotificationKind, NotificationPlatform,
+        default_timezone,
+    },
+    notificators::{SendError, TelegramNotificator},
+    recipients::{Recipient, RecipientPayload},
+    scheduler::parse_stored_timestamp,
+    scheduler::{JobInfo, parse_cron},
+    storage,
+    templates::{self, Template},
+    tenants::{self, DEFAULT_TENANT, Tenant, TenantInfo, validate_tenant_id},
+    utils::{ResponseFabric, parse_timestamp_in_tz, parse_timezone},
 };
 
-const ALLOWED_PLATFORMS: [&str; 1] = ["telegram"];
+const ALLOWED_PLATFORMS: [&str; 3] = ["telegram", "email", "webhook"];
+const DEFAULT_PAGE_SIZE: usize = 20;
+const MAX_PAGE_SIZE: usize = 100;
+// Telegram limits inline keyboard to 100 buttons
+const MAX_BUTTONS: usize = 100;
+const MAX_SNOOZE_MINUTES: u32 = 7 * 24 * 60;
+const BUTTON_URL_SCHEMES: [&str; 3] = ["http", "https", "tg"];
 
 #[derive(serde::Deserialize)]
 pub struct RegisterNotificationMetadata {
-    pub text: String,
+    // Either text or template_id must be set
+    pub text: Option<String>,
+    pub template_id: Option<String>,
+    // Object with template variables
+    pub template_context: Option<Value>,
+    // "plain" (default), "markdownv2" or "html". Telegram only
+    pub format: Option<String>,
+    #[serde(default)]
     pub daily_send_timestamps: Vec<String>,
+    #[serde(default)]
     pub is_daily: bool,
+    // Standard 5 or 6 (with seconds) field cron expression.
+    // Makes notification recurring by it instead of daily timestamps
+    pub cron: Option<String>,
+    // Single send at this time. RFC3339, or local time in notification zone
+    pub send_at: Option<String>,
+    // IANA time zone name. Defaults to the server zone
+    pub timezone: Option<String>,
     pub platform: String,
-    pub send_to: String,
+    pub send_to: RecipientPayload,
+    pub subject: Option<String>,
+    // Rows of inline keyboard buttons. Telegram only
+    #[serde(default)]
+    pub buttons: Vec<Vec<Button>>,
+    // Photos or documents, sent with text as caption. Telegram only
+    #[serde(default)]
+    pub attachments: Vec<AttachmentRef>,
+    // Telegram send options
+    #[serde(default)]
+    pub disable_notification: bool,
+    #[serde(default)]
+    pub protect_content: bool,
+    pub link_preview: Option<LinkPreview>,
+    pub reply_to_message_id: Option<i32>,
+}
+
+// All fields are optional, only passed ones are changed
+#[derive(serde::Deserialize)]
+pub struct UpdateNotificationMetadata {
+    // Setting text detaches template and vice versa
+    pub text: Option<String>,
+    pub template_id: Option<String>,
+    // Fields below with double Option are cleared by explicit null.
+    // Missing field keeps the current value
+    #[serde(default, deserialize_with = "nullable")]
+    pub template_context: Option<Option<Value>>,
+    pub format: Option<String>,
+    pub daily_send_timestamps: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub cron: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub send_at: Option<Option<String>>,
+    pub timezone: Option<String>,
+    pub send_to: Option<RecipientPayload>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub subject: Option<Option<String>>,
+    // Replaces all buttons, empty array or null removes them
+    #[serde(default, deserialize_with = "nullable")]
+    pub buttons: Option<Option<Vec<Vec<Button>>>>,
+    // Replaces all attachments, empty array removes them
+    pub attachments: Option<Vec<AttachmentRef>>,
+    pub disable_notification: Option<bool>,
+    pub protect_content: Option<bool>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub link_preview: Option<Option<LinkPreview>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub reply_to_message_id: Option<Option<i32>>,
+}
+
+// Present field, null or not, becomes Some. Missing one stays None with serde default
+fn nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: serde::Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    return <Option<T> as serde::Deserialize>::deserialize(deserializer).map(Some);
+}
+
+#[derive(serde::Deserialize)]
+pub struct EditDelivery {
+    pub text: String,
+    // Format of the notification, if not set
+    pub format: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PageQuery {
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ListNotificationsQuery {
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+    pub platform: Option<String>,
+    pub kind: Option<String>,
+    pub recipient: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ListSubscriptionsQuery {
+    pub topic: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    // Restrict key to these platforms / recipients. Any, if not set
+    pub platforms: Option<Vec<String>>,
+    pub recipients: Option<Vec<String>>,
+    // Only admins of the default tenant may create keys for other tenants.
+    // Tenant of the caller, if not set
+    pub tenant: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct TemplatePayload {
+    pub name: String,
+    pub body: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct UpsertTenantRequest {
+    // Own telegram bot of the tenant. Server bot is used, if not set
+    pub telegram_bot_token: Option<String>,
+    // Events about notifications are posted here, if set
+    pub events_url: Option<String>,
+    // Replaces webhook secret of the tenant with a new one
+    #[serde(default)]
+    pub rotate_webhook_secret: bool,
 }
 
 #[derive(serde::Serialize)]
@@ -34,32 +186,451 @@ pub struct NotificationResponse {
     pub notification: Notification,
 }
 
+#[derive(serde::Serialize)]
+pub struct DeadLetterListResponse {
+    pub message: String,
+    pub dead_letters: Vec<DeadLetter>,
+}
+
+#[derive(serde::Serialize)]
+pub struct DeliveryListResponse {
+    pub message: String,
+    pub deliveries: Vec<DeliveryRecord>,
+    pub page: usize,
+    pub per_page: usize,
+    pub total: usize,
+}
+
+#[derive(serde::Serialize)]
+pub struct DeliveryResponse {
+    pub message: String,
+    pub delivery: Option<DeliveryRecord>,
+}
+
+#[derive(serde::Serialize)]
+pub struct ButtonPressListResponse {
+    pub message: String,
+    pub presses: Vec<ButtonPress>,
+}
+
+#[derive(serde::Serialize)]
+pub struct SubscriptionListResponse {
+    pub message: String,
+    pub subscriptions: Vec<Subscription>,
+}
+
+#[derive(serde::Serialize)]
+pub struct ApiKeyResponse {
+    pub message: String,
+    // Raw key, returned only once on creation
+    pub key: String,
+    pub api_key: Option<ApiKeyInfo>,
+}
+
+#[derive(serde::Serialize)]
+pub struct ApiKeyListResponse {
+    pub message: String,
+    pub api_keys: Vec<ApiKeyInfo>,
+}
+
+#[derive(serde::Serialize)]
+pub struct TemplateResponse {
+    pub message: String,
+    pub template: Option<Template>,
+}
+
+#[derive(serde::Serialize)]
+pub struct TemplateListResponse {
+    pub message: String,
+    pub templates: Vec<Template>,
+}
+
+#[derive(serde::Serialize)]
+pub struct AttachmentResponse {
+    pub message: String,
+    pub attachment: Option<Attachment>,
+}
+
+#[derive(serde::Serialize)]
+pub struct AttachmentListResponse {
+    pub message: String,
+    pub attachments: Vec<Attachment>,
+}
+
+#[derive(serde::Serialize)]
+pub struct TenantResponse {
+    pub message: String,
+    pub tenant: Option<TenantInfo>,
+    // Set only when generated, it isn't shown again
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhook_secret: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct TenantListResponse {
+    pub message: String,
+    pub tenants: Vec<TenantInfo>,
+}
+
+#[derive(serde::Serialize)]
+pub struct JobListResponse {
+    pub message: String,
+    pub jobs: Vec<JobInfo>,
+}
+
+#[derive(serde::Serialize)]
+pub struct TelegramQueueResponse {
+    pub message: String,
+    // Telegram sends waiting for rate limits of the bot
+    pub queued: usize,
+}
+
+#[derive(serde::Serialize)]
+pub struct NotificationListResponse {
+    pub message: String,
+    pub notifications: Vec<Notification>,
+    pub page: usize,
+    pub per_page: usize,
+    pub total: usize,
+}
+
 fn parse_platform_from_request(input: String) -> Result<NotificationPlatform, String> {
     let normalized = input.to_lowercase();
-    let is_valid = ALLOWED_PLATFORMS.contains(&input.as_str());
+    let is_valid = ALLOWED_PLATFORMS.contains(&normalized.as_str());
     if !is_valid {
-        return Err("Incorrect platform. Supported are \"telegram\" & \"email\"".to_string());
+        return Err(
+            "Incorrect platform. Supported are \"telegram\", \"email\" & \"webhook\"".to_string(),
+        );
     }
 
     return match normalized.as_str() {
         "telegram" => Ok(NotificationPlatform::Telegram),
         "email" => Ok(NotificationPlatform::Email),
+        "webhook" => Ok(NotificationPlatform::Webhook),
         _ => Err("Unsupported platform.".to_string()),
     };
 }
 
+fn parse_kind_from_request(input: &str) -> Result<NotificationKind, String> {
+    return match input.to_lowercase().as_str() {
+        "daily" => Ok(NotificationKind::Daily),
+        "instant" => Ok(NotificationKind::Instant),
+        "cron" => Ok(NotificationKind::Cron),
+        "scheduled" => Ok(NotificationKind::Scheduled),
+        _ => Err(
+            "Incorrect kind. Supported are \"daily\", \"instant\", \"cron\" & \"scheduled\""
+                .to_string(),
+        ),
+    };
+}
+
+fn parse_format_from_request(input: &str) -> Result<MessageFormat, String> {
+    return match input.to_lowercase().as_str() {
+        "plain" => Ok(MessageFormat::Plain),
+        "markdownv2" => Ok(MessageFormat::MarkdownV2),
+        "html" => Ok(MessageFormat::Html),
+        _ => {
+            Err("Incorrect format. Supported are \"plain\", \"markdownv2\" & \"html\"".to_string())
+        }
+    };
+}
+
+// Page number starting from 1 and page size, clamped to sane values
+fn parse_page(page: Option<usize>, per_page: Option<usize>) -> (usize, usize) {
+    return (
+        page.unwrap_or(1).max(1),
+        per_page
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE),
+    );
+}
+
+// Replaces daily timestamps of notification with parsed ones
+fn set_daily_timestamps(
+    notification: &mut Notification,
+    timestamps: Vec<String>,
+) -> Result<(), String> {
+    let tz = notification.tz()?;
+    notification.daily_send_timestamps.clear();
+
+    for payload_ts in timestamps {
+        let timestamp = match parse_timestamp_in_tz(&payload_ts, &tz) {
+            Ok(t) => t,
+            Err(_) => {
+                return Err(format!(
+                    "Incorrect date string: \"{}\". Expected format is RFC3339: 2025-04-18T12:00:00Z, or local time in notification zone: 2025-04-18T12:00:00",
+                    &payload_ts
+                ));
+            }
+        };
+
+        if let Err(e) = notification.add_daily_timestamp(timestamp) {
+            tracing::info!("Error adding daily timestamp: {}", e);
+            return Err(format!("Error adding daily timestamp: {}", e));
+        }
+    }
+
+    return Ok(());
+}
+
+// Sets single send time of scheduled notification and arms it again
+fn set_send_at(notification: &mut Notification, send_at: &str) -> Result<(), String> {
+    let tz = notification.tz()?;
+    let timestamp = match parse_timestamp_in_tz(send_at, &tz) {
+        Ok(t) => t,
+        Err(_) => {
+            return Err(format!(
+                "Incorrect date string: \"{}\". Expected format is RFC3339: 2025-04-18T12:00:00Z, or local time in notification zone: 2025-04-18T12:00:00",
+                send_at
+            ));
+        }
+    };
+
+    if timestamp <= chrono::Utc::now() {
+        return Err(format!("send_at \"{}\" is in the past", send_at));
+    }
+
+    notification.send_at = Some(timestamp.to_rfc3339());
+    notification.completed = false;
+    return Ok(());
+}
+
+fn check_buttons(notification: &Notification) -> Result<(), String> {
+    if notification.buttons.is_empty() {
+        return Ok(());
+    }
+
+    if notification.platform != NotificationPlatform::Telegram {
+        return Err("buttons are supported only by telegram".to_string());
+    }
+
+    if notification.buttons.iter().any(|row| row.is_empty()) {
+        return Err("button rows can't be empty".to_string());
+    }
+
+    if notification.buttons.iter().flatten().count() > MAX_BUTTONS {
+        return Err(format!("there can't be more than {} buttons", MAX_BUTTONS));
+    }
+
+    for button in notification.buttons.iter().flatten() {
+        match button {
+            Button::Url { text, url } => {
+                if text.trim().is_empty() {
+                    return Err("button text can't be empty".to_string());
+                }
+
+                match reqwest::Url::parse(url) {
+                    Ok(u) if BUTTON_URL_SCHEMES.contains(&u.scheme()) => (),
+                    _ => {
+                        return Err(format!(
+                            "invalid button url \"{}\". Expected http, https or tg url",
+                            url
+                        ));
+                    }
+                }
+            }
+            Button::Callback {
+                text,
+                action,
+                snooze_minutes,
+            } => {
+                if text.trim().is_empty() {
+                    return Err("button text can't be empty".to_string());
+                }
+
+                match (action, snooze_minutes) {
+                    (ButtonAction::Ack, None) => (),
+                    (ButtonAction::Ack, Some(_)) => {
+                        return Err("snooze_minutes can only be set for snooze buttons".to_string());
+                    }
+                    (ButtonAction::Snooze, Some(minutes))
+                        if (1..=MAX_SNOOZE_MINUTES).contains(minutes) =>
+                    {
+                        // Snooze copies the stored notification, instant ones aren't stored
+                        if !notification.kind.is_scheduled() {
+                            return Err("snooze buttons can't be used with instant notifications"
+                                .to_string());
+                        }
+                    }
+                    (ButtonAction::Snooze, _) => {
+                        return Err(format!(
+                            "snooze buttons require snooze_minutes between 1 and {}",
+                            MAX_SNOOZE_MINUTES
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+// Uploaded attachments must exist and suit their kind. Albums can't mix
+// photos with documents. Url and file id sources are checked by telegram on send
+async fn check_attachments(state: &AppState, notification: &Notification) -> Result<(), String> {
+    let refs = &notification.attachments;
+    if refs.is_empty() {
+        return Ok(());
+    }
+
+    if notification.platform != NotificationPlatform::Telegram {
+        return Err("attachments are supported only by telegram".to_string());
+    }
+
+    if refs.len() > attachments::MAX_ATTACHMENTS {
+        return Err(format!(
+            "there can't be more than {} attachments",
+            attachments::MAX_ATTACHMENTS
+        ));
+    }
+
+    if refs.len() > 1 {
+        if refs.iter().any(|a| a.kind != refs[0].kind) {
+            return Err("photos and documents can't be mixed in one album".to_string());
+        }
+
+        // Telegram doesn't attach keyboards to albums
+        if !notification.buttons.is_empty() {
+            return Err("buttons can't be used with several attachments".to_string());
+        }
+    }
+
+    for attachment in refs {
+        match &attachment.source {
+            AttachmentSource::Upload { attachment_id } => {
+                let stored = match state
+                    .storage
+                    .get_attachment(&notification.tenant, attachment_id)
+                    .await
+                {
+                    Ok(Some(a)) => a,
+                    Ok(None) => return Err(format!("Attachment {} not found", attachment_id)),
+                    Err(e) => {
+                        tracing::error!("failed to get attachment: {}", e);
+                        return Err("Failed to get attachment".to_string());
+                    }
+                };
+
+                attachments::check_kind(&stored, &attachment.kind)?;
+            }
+            AttachmentSource::Url { url } => match reqwest::Url::parse(url) {
+                Ok(u) if u.scheme() == "http" || u.scheme() == "https" => (),
+                _ => {
+                    return Err(format!(
+                        "invalid attachment url \"{}\". Expected http or https url",
+                        url
+                    ));
+                }
+            },
+            AttachmentSource::FileId { file_id } => {
+                if file_id.trim().is_empty() {
+                    return Err("file_id can't be empty".to_string());
+                }
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+fn check_send_options(notification: &Notification) -> Result<(), String> {
+    let is_set = notification.disable_notification
+        || notification.protect_content
+        || notification.link_preview.is_some()
+        || notification.reply_to_message_id.is_some();
+
+    if is_set && notification.platform != NotificationPlatform::Telegram {
+        return Err("disable_notification, protect_content, link_preview and \
+            reply_to_message_id are supported only by telegram"
+            .to_string());
+    }
+
+    if let Some(id) = notification.reply_to_message_id
+        && id <= 0
+    {
+        return Err("reply_to_message_id must be positive".to_string());
+    }
+
+    if let Some(link_preview) = &notification.link_preview {
+        if link_preview.prefer_small_media && link_preview.prefer_large_media {
+            return Err("link preview can't prefer both small and large media".to_string());
+        }
+
+        if let Some(url) = &link_preview.url {
+            match reqwest::Url::parse(url) {
+                Ok(u) if u.scheme() == "http" || u.scheme() == "https" => (),
+                _ => {
+                    return Err(format!(
+                        "invalid link preview url \"{}\". Expected http or https url",
+                        url
+                    ));
+                }
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+// Checks text markup, rendering template if notification has one.
+// Missing template, bad context, unknown variables or broken markup
+// are reported now instead of on send
+async fn check_text(state: &AppState, notification: &Notification) -> Result<(), String> {
+    if notification.format != MessageFormat::Plain
+        && notification.platform != NotificationPlatform::Telegram
+    {
+        return Err("format is supported only by telegram".to_string());
+    }
+
+    let template_id = match &notification.template_id {
+        Some(id) => id,
+        None => return formatting::validate(&notification.format, &notification.text),
+    };
+
+    let template = match state
+        .storage
+        .get_template(&notification.tenant, template_id)
+        .await
+    {
+        Ok(Some(t)) => t,
+        Ok(None) => return Err(format!("Template {} not found", template_id)),
+        Err(e) => {
+            tracing::error!("failed to get template: {}", e);
+            return Err("Failed to get template".to_string());
+        }
+    };
+
+    return templates::render(&template.body, notification).map(|_| ());
+}
+
 #[axum::debug_handler]
 pub async fn get_notification_metadata(
     Path(notification_key): Path<String>,
     State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
 ) -> (StatusCode, Json<NotificationResponse>) {
-    if let Err(e) = Uuid::try_parse(&notification_key.as_str()) {
+    if let Err(e) = caller.require(Scope::Read) {
+        return ResponseFabric::forbidden::<NotificationResponse>(&e);
+    }
+
+    if let Err(e) = Uuid::try_parse(notification_key.as_str()) {
         tracing::error!("failed to parse uuid: {}", e);
         return ResponseFabric::bad_request::<NotificationResponse>("Invalid notification key");
     };
 
-    let ntf = match state.storage.get_notification(&notification_key.as_str()) {
-        Ok(n) => n,
+    let ntf = match state
+        .storage
+        .get_notification(&caller.tenant, notification_key.as_str())
+        .await
+    {
+        // Notifications out of key restrictions are reported as missing
+        Ok(n) if caller.allows_notification(&n) => n,
+        Ok(_) => {
+            return ResponseFabric::not_found::<NotificationResponse>(
+                "Notification metadata not found",
+            );
+        }
         Err(e) => {
             tracing::error!("failed to get notification by key: {}", e);
             return ResponseFabric::not_found::<NotificationResponse>(
@@ -79,14 +650,65 @@ pub async fn get_notification_metadata(
 #[axum::debug_handler]
 pub async fn register_notification_metadata(
     State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
     Json(payload): Json<RegisterNotificationMetadata>,
 ) -> (StatusCode, Json<MessageResponse>) {
-    let kind = match payload.is_daily {
-        true => NotificationKind::Daily,
-        false => NotificationKind::Instant,
+    let text = match (payload.text, &payload.template_id) {
+        (Some(text), None) => text,
+        (None, Some(_)) => String::new(),
+        (Some(_), Some(_)) => {
+            return ResponseFabric::bad_request::<MessageResponse>(
+                "Only one of text and template_id can be set",
+            );
+        }
+        (None, None) => {
+            return ResponseFabric::bad_request::<MessageResponse>(
+                "Either text or template_id is required",
+            );
+        }
+    };
+
+    if payload.template_context.is_some() && payload.template_id.is_none() {
+        return ResponseFabric::bad_request::<MessageResponse>(
+            "template_context requires template_id",
+        );
+    }
+
+    let kind = match (payload.is_daily, &payload.cron, &payload.send_at) {
+        (false, None, None) => NotificationKind::Instant,
+        (true, None, None) => NotificationKind::Daily,
+        (false, Some(_), None) => NotificationKind::Cron,
+        (false, None, Some(_)) => NotificationKind::Scheduled,
+        _ => {
+            return ResponseFabric::bad_request::<MessageResponse>(
+                "Only one of is_daily, cron and send_at can be set",
+            );
+        }
+    };
+
+    if let Some(expr) = &payload.cron
+        && let Err(e) = parse_cron(expr)
+    {
+        return ResponseFabric::bad_request::<MessageResponse>(&e);
+    }
+
+    let timezone = match payload.timezone {
+        Some(tz) => match parse_timezone(tz.trim()) {
+            Ok(tz) => tz.name().to_string(),
+            Err(e) => return ResponseFabric::bad_request::<MessageResponse>(&e),
+        },
+        None => default_timezone(),
+    };
+
+    let format = match &payload.format {
+        Some(f) => match parse_format_from_request(f) {
+            Ok(f) => f,
+            Err(e) => return ResponseFabric::bad_request::<MessageResponse>(&e),
+        },
+        None => MessageFormat::Plain,
     };
 
-    if payload.is_daily && payload.daily_send_timestamps.len() == 0 {
+    if payload.is_daily && payload.daily_send_timestamps.is_empty() {
         return ResponseFabric::bad_request::<MessageResponse>(
             "is_daily is set true, but daily_send_timestamps size is 0",
         );
@@ -99,54 +721,96 @@ pub async fn register_notification_metadata(
         }
     };
 
-    let send_to = payload.send_to.parse::<i64>().unwrap();
+    let send_to = match Recipient::from_payload(payload.send_to, &platform) {
+        Ok(r) => r,
+        Err(e) => {
+            return ResponseFabric::bad_request::<MessageResponse>(&format!(
+                "Invalid send_to: {}",
+                e
+            ));
+        }
+    };
+
+    let scope = match kind {
+        NotificationKind::Instant => Scope::Send,
+        _ => Scope::Schedule,
+    };
+    if let Err(e) = caller.require(scope) {
+        return ResponseFabric::forbidden::<MessageResponse>(&e);
+    }
+
+    if !caller.allows(&platform, &send_to) {
+        return ResponseFabric::forbidden::<MessageResponse>(
+            "Api key is not allowed to notify this recipient",
+        );
+    }
+
     let mut notification = NotificationBuilder::new()
-        .text(payload.text)
+        .text(text)
+        .format(format)
+        .template_id(payload.template_id)
+        .template_context(payload.template_context)
         .kind(kind)
+        .subject(payload.subject)
         .send_to(send_to)
         .platform(platform)
+        .cron(payload.cron)
+        .timezone(timezone)
+        .tenant(caller.tenant.clone())
+        .buttons(payload.buttons)
+        .attachments(payload.attachments)
+        .disable_notification(payload.disable_notification)
+        .protect_content(payload.protect_content)
+        .link_preview(payload.link_preview)
+        .reply_to_message_id(payload.reply_to_message_id)
         .build();
 
     // add daily timestamps to notification if it's kind set to daily
-    if payload.is_daily {
-        for payload_ts in payload.daily_send_timestamps {
-            let timestamp_utc = match rfc3339_to_local(&payload_ts) {
-                Ok(t_utc) => t_utc,
-                Err(_) => {
-                    return ResponseFabric::bad_request::<MessageResponse>(&format!(
-                        "Incorrect date string: \"{}\". Expected format is RFC3339: 2025-04-18T12:00:00Z",
-                        &payload_ts
-                    ));
-                }
-            };
+    if payload.is_daily
+        && let Err(e) = set_daily_timestamps(&mut notification, payload.daily_send_timestamps)
+    {
+        return ResponseFabric::bad_request::<MessageResponse>(&e);
+    }
 
-            match notification.add_daily_timestamp(timestamp_utc) {
-                Ok(_) => (),
-                Err(e) => {
-                    tracing::info!("Error adding daily timestamp: {}", e);
-                    return ResponseFabric::bad_request::<MessageResponse>(&format!(
-                        "Error adding daily timestamp: {}",
-                        e
-                    ));
-                }
-            }
-        }
+    if let Some(send_at) = &payload.send_at
+        && let Err(e) = set_send_at(&mut notification, send_at)
+    {
+        return ResponseFabric::bad_request::<MessageResponse>(&e);
+    }
+
+    if let Err(e) = check_buttons(&notification) {
+        return ResponseFabric::bad_request::<MessageResponse>(&e);
+    }
+
+    if let Err(e) = check_attachments(&state, &notification).await {
+        return ResponseFabric::bad_request::<MessageResponse>(&e);
+    }
+
+    if let Err(e) = check_send_options(&notification) {
+        return ResponseFabric::bad_request::<MessageResponse>(&e);
+    }
+
+    if let Err(e) = check_text(&state, &notification).await {
+        return ResponseFabric::bad_request::<MessageResponse>(&e);
     }
 
     match notification.kind {
         NotificationKind::Instant => {
-            if let Err(e) = notification.send_instant(state.telegram).await {
-                return ResponseFabric::internal_server_error::<MessageResponse>(&format!(
+            return match state.dispatcher.deliver_instant(&notification).await {
+                Ok(Delivered::Sent) => ResponseFabric::ok_with_id("Sent!", notification.uuid),
+                Ok(Delivered::Retrying) => ResponseFabric::accepted_with_id(
+                    "Sending failed, retrying in background",
+                    notification.uuid,
+                ),
+                Err(e) => ResponseFabric::internal_server_error::<MessageResponse>(&format!(
                     "Failed to send notification: {}",
                     e
-                ));
-            }
-
-            return ResponseFabric::ok_with_id("Sent!", notification.uuid);
+                )),
+            };
         }
 
-        NotificationKind::Daily => {
-            if let Err(e) = state.storage.persist_notification(&notification) {
+        NotificationKind::Daily | NotificationKind::Cron | NotificationKind::Scheduled => {
+            if let Err(e) = state.storage.persist_notification(&notification).await {
                 tracing::error!("failed to persist notification: {}", e);
                 return ResponseFabric::internal_server_error::<MessageResponse>(
                     "Failed to save notification metadata",
@@ -167,3 +831,1790 @@ pub async fn register_notification_metadata(
         }
     }
 }
+
+#[axum::debug_handler]
+pub async fn list_notifications(
+    Query(query): Query<ListNotificationsQuery>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<NotificationListResponse>) {
+    if let Err(e) = caller.require(Scope::Read) {
+        return ResponseFabric::forbidden::<NotificationListResponse>(&e);
+    }
+
+    let platform = match query.platform {
+        Some(p) => match parse_platform_from_request(p) {
+            Ok(p) => Some(p),
+            Err(e) => return ResponseFabric::bad_request::<NotificationListResponse>(&e),
+        },
+        None => None,
+    };
+
+    let kind = match query.kind {
+        Some(k) => match parse_kind_from_request(&k) {
+            Ok(k) => Some(k),
+            Err(e) => return ResponseFabric::bad_request::<NotificationListResponse>(&e),
+        },
+        None => None,
+    };
+
+    let (page, per_page) = parse_page(query.page, query.per_page);
+
+    let mut notifications = match state.storage.get_all_notifications(&caller.tenant).await {
+        Ok(n) => n,
+        Err(e) => {
+            tracing::error!("failed to list notifications: {}", e);
+            return ResponseFabric::internal_server_error::<NotificationListResponse>(
+                "Failed to list notifications",
+            );
+        }
+    };
+
+    notifications.retain(|n| {
+        caller.allows_notification(n)
+            && platform.as_ref().is_none_or(|p| &n.platform == p)
+            && kind.as_ref().is_none_or(|k| &n.kind == k)
+            && query
+                .recipient
+                .as_ref()
+                .is_none_or(|r| n.send_to.matches(r))
+    });
+
+    // Storage returns notifications in arbitrary order,
+    // so sort them to keep pagination stable
+    notifications.sort_by(|a, b| {
+        a.created_at
+            .cmp(&b.created_at)
+            .then_with(|| a.uuid.cmp(&b.uuid))
+    });
+
+    let total = notifications.len();
+    let page_items = notifications
+        .into_iter()
+        .skip((page - 1) * per_page)
+        .take(per_page)
+        .collect();
+
+    return (
+        StatusCode::OK,
+        Json(NotificationListResponse {
+            message: "Found".to_string(),
+            notifications: page_items,
+            page,
+            per_page,
+            total,
+        }),
+    );
+}
+
+#[axum::debug_handler]
+pub async fn update_notification_metadata(
+    Path(notification_key): Path<String>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+    Json(payload): Json<UpdateNotificationMetadata>,
+) -> (StatusCode, Json<NotificationResponse>) {
+    if let Err(e) = caller.require(Scope::Schedule) {
+        return ResponseFabric::forbidden::<NotificationResponse>(&e);
+    }
+
+    if Uuid::try_parse(notification_key.as_str()).is_err() {
+        return ResponseFabric::bad_request::<NotificationResponse>("Invalid notification key");
+    };
+
+    let mut notification = match state
+        .storage
+        .get_notification(&caller.tenant, notification_key.as_str())
+        .await
+    {
+        Ok(n) if caller.allows_notification(&n) => n,
+        Ok(_) => {
+            return ResponseFabric::not_found::<NotificationResponse>(
+                "Notification metadata not found",
+            );
+        }
+        Err(e) => {
+            tracing::error!("failed to get notification by key: {}", e);
+            return ResponseFabric::not_found::<NotificationResponse>(
+                "Notification metadata not found",
+            );
+        }
+    };
+
+    if payload.text.is_some() && payload.template_id.is_some() {
+        return ResponseFabric::bad_request::<NotificationResponse>(
+            "Only one of text and template_id can be set",
+        );
+    }
+
+    if let Some(text) = payload.text {
+        notification.text = text;
+        notification.template_id = None;
+        notification.template_context = None;
+    }
+
+    if let Some(template_id) = payload.template_id {
+        notification.text = String::new();
+        notification.template_id = Some(template_id);
+    }
+
+    if let Some(context) = payload.template_context {
+        if context.is_some() && notification.template_id.is_none() {
+            return ResponseFabric::bad_request::<NotificationResponse>(
+                "template_context requires template_id",
+            );
+        }
+
+        notification.template_context = context;
+    }
+
+    if let Some(format) = payload.format {
+        notification.format = match parse_format_from_request(&format) {
+            Ok(f) => f,
+            Err(e) => return ResponseFabric::bad_request::<NotificationResponse>(&e),
+        };
+    }
+
+    if let Some(subject) = payload.subject {
+        notification.subject = subject;
+    }
+
+    if let Some(buttons) = payload.buttons {
+        notification.buttons = buttons.unwrap_or_default();
+    }
+
+    if let Some(attachments) = payload.attachments {
+        notification.attachments = attachments;
+    }
+
+    if let Some(disable) = payload.disable_notification {
+        notification.disable_notification = disable;
+    }
+
+    if let Some(protect) = payload.protect_content {
+        notification.protect_content = protect;
+    }
+
+    if let Some(link_preview) = payload.link_preview {
+        notification.link_preview = link_preview;
+    }
+
+    if let Some(message_id) = payload.reply_to_message_id {
+        notification.reply_to_message_id = message_id;
+    }
+
+    if let Some(send_to) = payload.send_to {
+        notification.send_to = match Recipient::from_payload(send_to, &notification.platform) {
+            Ok(r) => r,
+            Err(e) => {
+                return ResponseFabric::bad_request::<NotificationResponse>(&format!(
+                    "Invalid send_to: {}",
+                    e
+                ));
+            }
+        };
+
+        if !caller.allows_notification(&notification) {
+            return ResponseFabric::forbidden::<NotificationResponse>(
+                "Api key is not allowed to notify this recipient",
+            );
+        }
+    }
+
+    if let Some(timezone) = payload.timezone {
+        let tz = match parse_timezone(timezone.trim()) {
+            Ok(tz) => tz,
+            Err(e) => return ResponseFabric::bad_request::<NotificationResponse>(&e),
+        };
+
+        // keep wall clock time of existing daily timestamps in the new zone
+        let mut local_times = Vec::new();
+        for ts in &notification.daily_send_timestamps {
+            match parse_stored_timestamp(ts) {
+                Ok(dt) => {
+                    local_times.push(dt.naive_local().format("%Y-%m-%dT%H:%M:%S").to_string())
+                }
+                Err(e) => return ResponseFabric::internal_server_error::<NotificationResponse>(&e),
+            }
+        }
+
+        notification.timezone = tz.name().to_string();
+        if let Err(e) = set_daily_timestamps(&mut notification, local_times) {
+            return ResponseFabric::bad_request::<NotificationResponse>(&e);
+        }
+    }
+
+    if let Some(timestamps) = payload.daily_send_timestamps {
+        if notification.kind == NotificationKind::Daily && timestamps.is_empty() {
+            return ResponseFabric::bad_request::<NotificationResponse>(
+                "daily_send_timestamps can't be empty for daily notification",
+            );
+        }
+
+        if let Err(e) = set_daily_timestamps(&mut notification, timestamps) {
+            return ResponseFabric::bad_request::<NotificationResponse>(&e);
+        }
+    }
+
+    // Schedule of the notification kind can be changed, but not removed
+    if let Some(None) = payload.cron {
+        if notification.kind == NotificationKind::Cron {
+            return ResponseFabric::bad_request::<NotificationResponse>(
+                "cron can't be removed from cron notification",
+            );
+        }
+
+        notification.cron = None;
+    }
+
+    if let Some(Some(expr)) = payload.cron {
+        if notification.kind != NotificationKind::Cron {
+            return ResponseFabric::bad_request::<NotificationResponse>(
+                "cron can only be changed for cron notifications",
+            );
+        }
+
+        if let Err(e) = parse_cron(&expr) {
+            return ResponseFabric::bad_request::<NotificationResponse>(&e);
+        }
+
+        notification.cron = Some(expr);
+    }
+
+    if let Some(None) = payload.send_at {
+        if notification.kind == NotificationKind::Scheduled {
+            return ResponseFabric::bad_request::<NotificationResponse>(
+                "send_at can't be removed from scheduled notification",
+            );
+        }
+
+        notification.send_at = None;
+    }
+
+    if let Some(Some(send_at)) = payload.send_at {
+        if notification.kind != NotificationKind::Scheduled {
+            return ResponseFabric::bad_request::<NotificationResponse>(
+                "send_at can only be changed for scheduled notifications",
+            );
+        }
+
+        if let Err(e) = set_send_at(&mut notification, &send_at) {
+            return ResponseFabric::bad_request::<NotificationResponse>(&e);
+        }
+    }
+
+    if let Err(e) = check_buttons(&notification) {
+        return ResponseFabric::bad_request::<NotificationResponse>(&e);
+    }
+
+    if let Err(e) = check_attachments(&state, &notification).await {
+        return ResponseFabric::bad_request::<NotificationResponse>(&e);
+    }
+
+    if let Err(e) = check_send_options(&notification) {
+        return ResponseFabric::bad_request::<NotificationResponse>(&e);
+    }
+
+    if let Err(e) = check_text(&state, &notification).await {
+        return ResponseFabric::bad_request::<NotificationResponse>(&e);
+    }
+
+    if let Err(e) = state.storage.persist_notification(&notification).await {
+        tracing::error!("failed to persist notification: {}", e);
+        return ResponseFabric::internal_server_error::<NotificationResponse>(
+            "Failed to save notification metadata",
+        );
+    }
+
+    // reschedule, so changes are applied to the next send
+    if notification.kind.is_scheduled()
+        && let Err(e) = state.scheduler.reschedule(&notification)
+    {
+        tracing::error!("Failed to reschedule notification: {}", e);
+        return ResponseFabric::internal_server_error::<NotificationResponse>(
+            "Failed to reschedule notification",
+        );
+    }
+
+    let response = NotificationResponse {
+        message: "Updated".to_string(),
+        notification,
+    };
+
+    return ResponseFabric::ok_with_existing("Updated", response);
+}
+
+#[axum::debug_handler]
+pub async fn delete_notification_metadata(
+    Path(notification_key): Path<String>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<MessageResponse>) {
+    if let Err(e) = caller.require(Scope::Schedule) {
+        return ResponseFabric::forbidden::<MessageResponse>(&e);
+    }
+
+    if Uuid::try_parse(notification_key.as_str()).is_err() {
+        return ResponseFabric::bad_request::<MessageResponse>("Invalid notification key");
+    };
+
+    match state
+        .storage
+        .exists(&caller.tenant, notification_key.as_str())
+        .await
+    {
+        Ok(true) => (),
+        Ok(false) => {
+            return ResponseFabric::not_found::<MessageResponse>("Notification metadata not found");
+        }
+        Err(e) => {
+            tracing::error!("failed to check notification existence: {}", e);
+            return ResponseFabric::internal_server_error::<MessageResponse>(
+                "Failed to delete notification",
+            );
+        }
+    }
+
+    if !caller.has_unrestricted_access() {
+        match state
+            .storage
+            .get_notification(&caller.tenant, &notification_key)
+            .await
+        {
+            Ok(n) if caller.allows_notification(&n) => (),
+            _ => {
+                return ResponseFabric::not_found::<MessageResponse>(
+                    "Notification metadata not found",
+                );
+            }
+        }
+    }
+
+    if let Err(e) = state.scheduler.cancel(&notification_key) {
+        tracing::error!("failed to unschedule notification: {}", e);
+        return ResponseFabric::internal_server_error::<MessageResponse>(
+            "Failed to delete notification",
+        );
+    }
+
+    if let Err(e) = state
+        .storage
+        .delete_notification(&caller.tenant, &notification_key)
+        .await
+    {
+        tracing::error!("failed to delete notification: {}", e);
+        return ResponseFabric::internal_server_error::<MessageResponse>(
+            "Failed to delete notification",
+        );
+    }
+
+    // History isn't needed once the notification is gone
+    storage::delete_history(state.storage.as_ref(), &caller.tenant, &notification_key).await;
+
+    return ResponseFabric::ok_with_id("Deleted", notification_key);
+}
+
+#[axum::debug_handler]
+pub async fn pause_notification(
+    Path(notification_key): Path<String>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<MessageResponse>) {
+    return set_paused(&state, &caller, notification_key, true).await;
+}
+
+#[axum::debug_handler]
+pub async fn resume_notification(
+    Path(notification_key): Path<String>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<MessageResponse>) {
+    return set_paused(&state, &caller, notification_key, false).await;
+}
+
+async fn set_paused(
+    state: &AppState,
+    caller: &ApiKey,
+    notification_key: String,
+    paused: bool,
+) -> (StatusCode, Json<MessageResponse>) {
+    if let Err(e) = caller.require(Scope::Schedule) {
+        return ResponseFabric::forbidden::<MessageResponse>(&e);
+    }
+
+    if Uuid::try_parse(notification_key.as_str()).is_err() {
+        return ResponseFabric::bad_request::<MessageResponse>("Invalid notification key");
+    };
+
+    let mut notification = match state
+        .storage
+        .get_notification(&caller.tenant, notification_key.as_str())
+        .await
+    {
+        Ok(n) if caller.allows_notification(&n) => n,
+        Ok(_) => {
+            return ResponseFabric::not_found::<MessageResponse>("Notification metadata not found");
+        }
+        Err(e) => {
+            tracing::error!("failed to get notification by key: {}", e);
+            return ResponseFabric::not_found::<MessageResponse>("Notification metadata not found");
+        }
+    };
+
+    notification.set_paused(paused);
+    if let Err(e) = state.storage.persist_notification(&notification).await {
+        tracing::error!("failed to persist notification: {}", e);
+        return ResponseFabric::internal_server_error::<MessageResponse>(
+            "Failed to save notification metadata",
+        );
+    }
+
+    let result = match paused {
+        true => state.scheduler.pause(&notification_key),
+        false => state.scheduler.resume(&notification_key),
+    };
+
+    // Notification may be missing in scheduler if it failed
+    // to schedule on startup, so register it again
+    let result = match result {
+        Ok(false) => state.scheduler.add_notification(&notification),
+        Ok(true) => Ok(()),
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = result {
+        tracing::error!("failed to update scheduler job: {}", e);
+        return ResponseFabric::internal_server_error::<MessageResponse>(
+            "Failed to update scheduler job",
+        );
+    }
+
+    let message = match paused {
+        true => "Paused",
+        false => "Resumed",
+    };
+
+    return ResponseFabric::ok_with_id(message, notification_key);
+}
+
+#[axum::debug_handler]
+pub async fn list_scheduler_jobs(
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<JobListResponse>) {
+    if let Err(e) = caller.require(Scope::Read) {
+        return ResponseFabric::forbidden::<JobListResponse>(&e);
+    }
+
+    return match state
+        .scheduler
+        .jobs(|n| n.tenant == caller.tenant && caller.allows_notification(n))
+    {
+        Ok(jobs) => (
+            StatusCode::OK,
+            Json(JobListResponse {
+                message: "Found".to_string(),
+                jobs,
+            }),
+        ),
+        Err(e) => {
+            tracing::error!("failed to list scheduler jobs: {}", e);
+            ResponseFabric::internal_server_error::<JobListResponse>("Failed to list jobs")
+        }
+    };
+}
+
+// Queue of the bot the caller's tenant sends with. Server bot
+// is shared by all tenants without their own one
+#[axum::debug_handler]
+pub async fn get_telegram_queue(
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<TelegramQueueResponse>) {
+    if let Err(e) = caller.require(Scope::Read) {
+        return ResponseFabric::forbidden::<TelegramQueueResponse>(&e);
+    }
+
+    return (
+        StatusCode::OK,
+        Json(TelegramQueueResponse {
+            message: "Found".to_string(),
+            queued: state
+                .dispatcher
+                .notificators
+                .telegram
+                .queued(&caller.tenant),
+        }),
+    );
+}
+
+#[axum::debug_handler]
+pub async fn list_dead_letters(
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<DeadLetterListResponse>) {
+    if let Err(e) = caller.require(Scope::Read) {
+        return ResponseFabric::forbidden::<DeadLetterListResponse>(&e);
+    }
+
+    let mut dead_letters = match state.storage.get_all_dead_letters(&caller.tenant).await {
+        Ok(d) => d,
+        Err(e) => {
+            tracing::error!("failed to list dead letters: {}", e);
+            return ResponseFabric::internal_server_error::<DeadLetterListResponse>(
+                "Failed to list dead letters",
+            );
+        }
+    };
+
+    dead_letters.retain(|d| caller.allows_notification(&d.notification));
+    dead_letters.sort_by(|a, b| a.failed_at.cmp(&b.failed_at));
+
+    return (
+        StatusCode::OK,
+        Json(DeadLetterListResponse {
+            message: "Found".to_string(),
+            dead_letters,
+        }),
+    );
+}
+
+#[axum::debug_handler]
+pub async fn replay_dead_letter(
+    Path(dead_letter_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<MessageResponse>) {
+    if let Err(e) = caller.require(Scope::Send) {
+        return ResponseFabric::forbidden::<MessageResponse>(&e);
+    }
+
+    let dead_letter = match state
+        .storage
+        .get_dead_letter(&caller.tenant, &dead_letter_id)
+        .await
+    {
+        Ok(Some(d)) if caller.allows_notification(&d.notification) => d,
+        Ok(Some(_)) => {
+            return ResponseFabric::not_found::<MessageResponse>("Dead letter not found");
+        }
+        Ok(None) => return ResponseFabric::not_found::<MessageResponse>("Dead letter not found"),
+        Err(e) => {
+            tracing::error!("failed to get dead letter: {}", e);
+            return ResponseFabric::internal_server_error::<MessageResponse>(
+                "Failed to get dead letter",
+            );
+        }
+    };
+
+    // Instant notifications aren't stored, saved ones must still exist
+    if dead_letter.notification.kind.is_scheduled() {
+        match state
+            .storage
+            .exists(&caller.tenant, &dead_letter.notification.uuid)
+            .await
+        {
+            Ok(true) => (),
+            Ok(false) => {
+                return ResponseFabric::not_found::<MessageResponse>(
+                    "Notification of the dead letter was deleted",
+                );
+            }
+            Err(e) => {
+                tracing::error!("failed to check notification existence: {}", e);
+                return ResponseFabric::internal_server_error::<MessageResponse>(
+                    "Failed to get notification",
+                );
+            }
+        }
+    }
+
+    let notification_id = dead_letter.notification.uuid.clone();
+    if let Err(e) = state.dispatcher.replay(dead_letter).await {
+        return ResponseFabric::internal_server_error::<MessageResponse>(&format!(
+            "Failed to send notification: {}",
+            e
+        ));
+    }
+
+    return ResponseFabric::ok_with_id("Sent!", notification_id);
+}
+
+#[axum::debug_handler]
+pub async fn delete_dead_letter(
+    Path(dead_letter_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<MessageResponse>) {
+    if let Err(e) = caller.require(Scope::Send) {
+        return ResponseFabric::forbidden::<MessageResponse>(&e);
+    }
+
+    if !caller.has_unrestricted_access() {
+        match state
+            .storage
+            .get_dead_letter(&caller.tenant, &dead_letter_id)
+            .await
+        {
+            Ok(Some(d)) if caller.allows_notification(&d.notification) => (),
+            _ => return ResponseFabric::not_found::<MessageResponse>("Dead letter not found"),
+        }
+    }
+
+    return match state
+        .storage
+        .delete_dead_letter(&caller.tenant, &dead_letter_id)
+        .await
+    {
+        Ok(true) => ResponseFabric::ok_with_id("Deleted", dead_letter_id),
+        Ok(false) => ResponseFabric::not_found::<MessageResponse>("Dead letter not found"),
+        Err(e) => {
+            tracing::error!("failed to delete dead letter: {}", e);
+            ResponseFabric::internal_server_error::<MessageResponse>("Failed to delete dead letter")
+        }
+    };
+}
+
+#[axum::debug_handler]
+pub async fn list_notification_deliveries(
+    Path(notification_key): Path<String>,
+    Query(query): Query<PageQuery>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<DeliveryListResponse>) {
+    if let Err(e) = caller.require(Scope::Read) {
+        return ResponseFabric::forbidden::<DeliveryListResponse>(&e);
+    }
+
+    if Uuid::try_parse(notification_key.as_str()).is_err() {
+        return ResponseFabric::bad_request::<DeliveryListResponse>("Invalid notification key");
+    };
+
+    let (page, per_page) = parse_page(query.page, query.per_page);
+    let (deliveries, total) = match state
+        .storage
+        .get_deliveries(
+            &caller.tenant,
+            &notification_key,
+            (page - 1) * per_page,
+            per_page,
+        )
+        .await
+    {
+        Ok(d) => d,
+        Err(e) => {
+            tracing::error!("failed to get deliveries: {}", e);
+            return ResponseFabric::internal_server_error::<DeliveryListResponse>(
+                "Failed to get deliveries",
+            );
+        }
+    };
+
+    // Instant notifications are not saved, but their deliveries are,
+    // so notification is unknown only if it has no deliveries either
+    if total == 0
+        && !state
+            .storage
+            .exists(&caller.tenant, &notification_key)
+            .await
+            .unwrap_or(false)
+    {
+        return ResponseFabric::not_found::<DeliveryListResponse>("Notification not found");
+    }
+
+    if !caller.has_unrestricted_access()
+        && !can_read_deliveries(&state, &caller, &notification_key).await
+    {
+        return ResponseFabric::not_found::<DeliveryListResponse>("Notification not found");
+    }
+
+    return (
+        StatusCode::OK,
+        Json(DeliveryListResponse {
+            message: "Found".to_string(),
+            deliveries,
+            page,
+            per_page,
+            total,
+        }),
+    );
+}
+
+#[axum::debug_handler]
+pub async fn get_delivery(
+    Path(delivery_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<DeliveryResponse>) {
+    if let Err(e) = caller.require(Scope::Read) {
+        return ResponseFabric::forbidden::<DeliveryResponse>(&e);
+    }
+
+    return match find_delivery(&state, &caller, &delivery_id).await {
+        Ok(Some(delivery)) => (
+            StatusCode::OK,
+            Json(DeliveryResponse {
+                message: "Found".to_string(),
+                delivery: Some(delivery),
+            }),
+        ),
+        Ok(None) => ResponseFabric::not_found::<DeliveryResponse>("Delivery not found"),
+        Err(e) => {
+            tracing::error!("failed to get delivery: {}", e);
+            ResponseFabric::internal_server_error::<DeliveryResponse>("Failed to get delivery")
+        }
+    };
+}
+
+// Replaces text of the messages sent by the delivery, or caption of it's
+// attachments if text was sent in it
+#[axum::debug_handler]
+pub async fn edit_delivery(
+    Path(delivery_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+    Json(payload): Json<EditDelivery>,
+) -> (StatusCode, Json<DeliveryResponse>) {
+    if let Err(e) = caller.require(Scope::Send) {
+        return ResponseFabric::forbidden::<DeliveryResponse>(&e);
+    }
+
+    let delivery = match find_delivery(&state, &caller, &delivery_id).await {
+        Ok(Some(d)) => d,
+        Ok(None) => return ResponseFabric::not_found::<DeliveryResponse>("Delivery not found"),
+        Err(e) => {
+            tracing::error!("failed to get delivery: {}", e);
+            return ResponseFabric::internal_server_error::<DeliveryResponse>(
+                "Failed to get delivery",
+            );
+        }
+    };
+    if let Some(response) = check_changeable(&delivery, "edited") {
+        return response;
+    }
+
+    // Format and link preview options are taken from the notification
+    // while it's stored. Instant notifications aren't
+    let mut notification = match state
+        .storage
+        .get_notification(&caller.tenant, &delivery.notification_id)
+        .await
+    {
+        Ok(n) => n,
+        Err(_) => NotificationBuilder::new()
+            .tenant(caller.tenant.clone())
+            .platform(NotificationPlatform::Telegram)
+            .send_to(delivery.recipient.clone())
+            .build(),
+    };
+    // Messages keep the buttons they were sent with
+    notification.uuid = delivery.notification_id.clone();
+    notification.buttons = delivery.buttons.clone();
+
+    if let Some(format) = &payload.format {
+        notification.format = match parse_format_from_request(format) {
+            Ok(f) => f,
+            Err(e) => return ResponseFabric::bad_request::<DeliveryResponse>(&e),
+        };
+    }
+
+    if payload.text.trim().is_empty() {
+        return ResponseFabric::bad_request::<DeliveryResponse>("Text can't be empty");
+    }
+    if let Err(e) = formatting::validate(&notification.format, &payload.text) {
+        return ResponseFabric::bad_request::<DeliveryResponse>(&e);
+    }
+    notification.text = payload.text;
+
+    if let Err(e) = TelegramNotificator::check_edit(&delivery, &notification) {
+        return ResponseFabric::bad_request::<DeliveryResponse>(&e);
+    }
+
+    let result = state
+        .dispatcher
+        .notificators
+        .telegram
+        .edit(&delivery, &notification)
+        .await;
+
+    return record_delivery_action(
+        &state,
+        delivery,
+        DeliveryActionKind::Edit,
+        Some(notification.text),
+        result,
+    )
+    .await;
+}
+
+#[axum::debug_handler]
+pub async fn delete_delivery(
+    Path(delivery_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<DeliveryResponse>) {
+    if let Err(e) = caller.require(Scope::Send) {
+        return ResponseFabric::forbidden::<DeliveryResponse>(&e);
+    }
+
+    let delivery = match find_delivery(&state, &caller, &delivery_id).await {
+        Ok(Some(d)) => d,
+        Ok(None) => return ResponseFabric::not_found::<DeliveryResponse>("Delivery not found"),
+        Err(e) => {
+            tracing::error!("failed to get delivery: {}", e);
+            return ResponseFabric::internal_server_error::<DeliveryResponse>(
+                "Failed to get delivery",
+            );
+        }
+    };
+    if let Some(response) = check_changeable(&delivery, "deleted") {
+        return response;
+    }
+
+    let result = state
+        .dispatcher
+        .notificators
+        .telegram
+        .delete(&delivery)
+        .await;
+
+    return record_delivery_action(&state, delivery, DeliveryActionKind::Delete, None, result)
+        .await;
+}
+
+// Deliveries to recipients restricted keys can't reach are reported as missing
+async fn find_delivery(
+    state: &AppState,
+    caller: &ApiKey,
+    delivery_id: &str,
+) -> Result<Option<DeliveryRecord>, String> {
+    let delivery = state
+        .storage
+        .get_delivery(&caller.tenant, delivery_id)
+        .await?;
+    return Ok(delivery.filter(|d| caller.allows(&d.platform, &d.recipient)));
+}
+
+// Response to return if messages of the delivery can't be changed.
+// Only telegram ones can be changed once sent
+fn check_changeable(
+    delivery: &DeliveryRecord,
+    action: &str,
+) -> Option<(StatusCode, Json<DeliveryResponse>)> {
+    if delivery.platform != NotificationPlatform::Telegram {
+        return Some(ResponseFabric::bad_request::<DeliveryResponse>(&format!(
+            "Only telegram messages can be {}",
+            action
+        )));
+    }
+
+    // Failed deliveries may have sent some parts of split text
+    if delivery.provider_message_ids.is_empty() {
+        return Some(ResponseFabric::conflict::<DeliveryResponse>(
+            "Delivery has no sent messages",
+        ));
+    }
+
+    if delivery.is_deleted() {
+        return Some(ResponseFabric::conflict::<DeliveryResponse>(
+            "Messages of the delivery are already deleted",
+        ));
+    }
+
+    return None;
+}
+
+// Outcome is saved along with the delivery, failed attempts included
+async fn record_delivery_action(
+    state: &AppState,
+    mut delivery: DeliveryRecord,
+    kind: DeliveryActionKind,
+    text: Option<String>,
+    result: Result<(), SendError>,
+) -> (StatusCode, Json<DeliveryResponse>) {
+    let (outcome, error) = match &result {
+        Ok(_) => (DeliveryOutcome::Sent, None),
+        Err(e) => (DeliveryOutcome::Failed, Some(e.to_string())),
+    };
+    delivery.actions.push(DeliveryAction {
+        kind,
+        outcome,
+        error,
+        text,
+        performed_at: chrono::Utc::now().to_rfc3339(),
+    });
+
+    if let Err(e) = state.storage.update_delivery(&delivery).await {
+        tracing::error!("failed to save delivery {}: {}", delivery.id, e);
+    }
+
+    return match result {
+        Ok(_) => (
+            StatusCode::OK,
+            Json(DeliveryResponse {
+                message: "Done".to_string(),
+                delivery: Some(delivery),
+            }),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(DeliveryResponse {
+                message: format!("Failed to change messages: {}", e),
+                delivery: Some(delivery),
+            }),
+        ),
+    };
+}
+
+#[axum::debug_handler]
+pub async fn list_button_presses(
+    Path(notification_key): Path<String>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<ButtonPressListResponse>) {
+    if let Err(e) = caller.require(Scope::Read) {
+        return ResponseFabric::forbidden::<ButtonPressListResponse>(&e);
+    }
+
+    if Uuid::try_parse(notification_key.as_str()).is_err() {
+        return ResponseFabric::bad_request::<ButtonPressListResponse>("Invalid notification key");
+    };
+
+    let presses = match state
+        .storage
+        .get_button_presses(&caller.tenant, &notification_key)
+        .await
+    {
+        Ok(p) => p,
+        Err(e) => {
+            tracing::error!("failed to get button presses: {}", e);
+            return ResponseFabric::internal_server_error::<ButtonPressListResponse>(
+                "Failed to get button presses",
+            );
+        }
+    };
+
+    // Same as deliveries: notification or its deliveries must exist
+    // and be allowed by the key
+    if !can_read_deliveries(&state, &caller, &notification_key).await {
+        return ResponseFabric::not_found::<ButtonPressListResponse>("Notification not found");
+    }
+
+    return (
+        StatusCode::OK,
+        Json(ButtonPressListResponse {
+            message: "Found".to_string(),
+            presses,
+        }),
+    );
+}
+
+// Checks key restrictions against the notification. Instant notifications
+// are not saved, so the recipient of their latest delivery is checked instead
+async fn can_read_deliveries(state: &AppState, caller: &ApiKey, notification_key: &str) -> bool {
+    if let Ok(n) = state
+        .storage
+        .get_notification(&caller.tenant, notification_key)
+        .await
+    {
+        return caller.allows_notification(&n);
+    }
+
+    return match state
+        .storage
+        .get_deliveries(&caller.tenant, notification_key, 0, 1)
+        .await
+    {
+        Ok((deliveries, _)) => deliveries
+            .first()
+            .is_some_and(|d| caller.allows(&d.platform, &d.recipient)),
+        Err(_) => false,
+    };
+}
+
+#[axum::debug_handler]
+pub async fn create_api_key(
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+    Json(payload): Json<CreateApiKeyRequest>,
+) -> (StatusCode, Json<ApiKeyResponse>) {
+    if let Err(e) = caller.require(Scope::Admin) {
+        return ResponseFabric::forbidden::<ApiKeyResponse>(&e);
+    }
+
+    if payload.name.trim().is_empty() {
+        return ResponseFabric::bad_request::<ApiKeyResponse>("name can't be empty");
+    }
+
+    if payload.scopes.is_empty() {
+        return ResponseFabric::bad_request::<ApiKeyResponse>("at least one scope is required");
+    }
+
+    let platforms = match payload.platforms {
+        Some(platforms) => {
+            let mut parsed = Vec::new();
+            for p in platforms {
+                match parse_platform_from_request(p) {
+                    Ok(p) => parsed.push(p),
+                    Err(e) => return ResponseFabric::bad_request::<ApiKeyResponse>(&e),
+                }
+            }
+            Some(parsed)
+        }
+        None => None,
+    };
+
+    if let Err(e) = caller.can_grant(
+        &payload.scopes,
+        platforms.as_ref(),
+        payload.recipients.as_ref(),
+    ) {
+        return ResponseFabric::forbidden::<ApiKeyResponse>(&e);
+    }
+
+    let tenant = match payload.tenant {
+        Some(tenant) if tenant != caller.tenant => {
+            if !caller.is_root() {
+                return ResponseFabric::forbidden::<ApiKeyResponse>(
+                    "Only admins of the default tenant can create keys for other tenants",
+                );
+            }
+
+            match tenant_exists(&state, &tenant).await {
+                Ok(true) => tenant,
+                Ok(false) => {
+                    return ResponseFabric::bad_request::<ApiKeyResponse>(&format!(
+                        "Tenant \"{}\" not found",
+                        tenant
+                    ));
+                }
+                Err(e) => {
+                    tracing::error!("failed to get tenant: {}", e);
+                    return ResponseFabric::internal_server_error::<ApiKeyResponse>(
+                        "Failed to get tenant",
+                    );
+                }
+            }
+        }
+        _ => caller.tenant.clone(),
+    };
+
+    let (api_key, raw) = ApiKey::generate(
+        payload.name.trim().to_string(),
+        payload.scopes,
+        platforms,
+        payload.recipients,
+        tenant,
+    );
+
+    if let Err(e) = state.storage.persist_api_key(&api_key).await {
+        tracing::error!("failed to persist api key: {}", e);
+        return ResponseFabric::internal_server_error::<ApiKeyResponse>("Failed to save api key");
+    }
+
+    tracing::info!("api key {} ({}) created", api_key.id, api_key.name);
+
+    return (
+        StatusCode::OK,
+        Json(ApiKeyResponse {
+            message: "Created. Save the key, it won't be shown again".to_string(),
+            key: raw,
+            api_key: Some(ApiKeyInfo::from(&api_key)),
+        }),
+    );
+}
+
+#[axum::debug_handler]
+pub async fn list_api_keys(
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<ApiKeyListResponse>) {
+    if let Err(e) = caller.require(Scope::Admin) {
+        return ResponseFabric::forbidden::<ApiKeyListResponse>(&e);
+    }
+
+    let mut api_keys = match state.storage.get_all_api_keys().await {
+        Ok(k) => k,
+        Err(e) => {
+            tracing::error!("failed to list api keys: {}", e);
+            return ResponseFabric::internal_server_error::<ApiKeyListResponse>(
+                "Failed to list api keys",
+            );
+        }
+    };
+
+    if !caller.is_root() {
+        api_keys.retain(|k| k.tenant == caller.tenant);
+    }
+    api_keys.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+
+    return (
+        StatusCode::OK,
+        Json(ApiKeyListResponse {
+            message: "Found".to_string(),
+            api_keys: api_keys.iter().map(ApiKeyInfo::from).collect(),
+        }),
+    );
+}
+
+#[axum::debug_handler]
+pub async fn revoke_api_key(
+    Path(key_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<MessageResponse>) {
+    if let Err(e) = caller.require(Scope::Admin) {
+        return ResponseFabric::forbidden::<MessageResponse>(&e);
+    }
+
+    // Keys of other tenants are reported as missing
+    if !caller.is_root() {
+        let keys = match state.storage.get_all_api_keys().await {
+            Ok(k) => k,
+            Err(e) => {
+                tracing::error!("failed to list api keys: {}", e);
+                return ResponseFabric::internal_server_error::<MessageResponse>(
+                    "Failed to revoke api key",
+                );
+            }
+        };
+
+        if !keys
+            .iter()
+            .any(|k| k.id == key_id && k.tenant == caller.tenant)
+        {
+            return ResponseFabric::not_found::<MessageResponse>("Api key not found");
+        }
+    }
+
+    return match state.storage.delete_api_key(&key_id).await {
+        Ok(true) => {
+            tracing::info!("api key {} revoked", key_id);
+            ResponseFabric::ok_with_id("Revoked", key_id)
+        }
+        Ok(false) => ResponseFabric::not_found::<MessageResponse>("Api key not found"),
+        Err(e) => {
+            tracing::error!("failed to revoke api key: {}", e);
+            ResponseFabric::internal_server_error::<MessageResponse>("Failed to revoke api key")
+        }
+    };
+}
+
+// Default tenant always exists, others only once created
+async fn tenant_exists(state: &AppState, tenant: &str) -> Result<bool, String> {
+    if tenant == DEFAULT_TENANT {
+        return Ok(true);
+    }
+
+    return Ok(state.storage.get_tenant(tenant).await?.is_some());
+}
+
+#[axum::debug_handler]
+pub async fn list_tenants(
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<TenantListResponse>) {
+    if !caller.is_root() {
+        return ResponseFabric::forbidden::<TenantListResponse>(
+            "Only admins of the default tenant can manage tenants",
+        );
+    }
+
+    let mut tenants = match state.storage.get_all_tenants().await {
+        Ok(t) => t,
+        Err(e) => {
+            tracing::error!("failed to list tenants: {}", e);
+            return ResponseFabric::internal_server_error::<TenantListResponse>(
+                "Failed to list tenants",
+            );
+        }
+    };
+
+    tenants.sort_by(|a, b| a.id.cmp(&b.id));
+
+    return (
+        StatusCode::OK,
+        Json(TenantListResponse {
+            message: "Found".to_string(),
+            tenants: tenants.iter().map(TenantInfo::from).collect(),
+        }),
+    );
+}
+
+// Creates tenant or replaces its bot token
+#[axum::debug_handler]
+pub async fn upsert_tenant(
+    Path(tenant_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+    Json(payload): Json<UpsertTenantRequest>,
+) -> (StatusCode, Json<TenantResponse>) {
+    if !caller.is_root() {
+        return ResponseFabric::forbidden::<TenantResponse>(
+            "Only admins of the default tenant can manage tenants",
+        );
+    }
+
+    if let Err(e) = validate_tenant_id(&tenant_id) {
+        return ResponseFabric::bad_request::<TenantResponse>(&e);
+    }
+
+    if tenant_id == DEFAULT_TENANT {
+        return ResponseFabric::bad_request::<TenantResponse>(
+            "Default tenant is configured with env and can't be changed",
+        );
+    }
+
+    let telegram_bot_token = payload
+        .telegram_bot_token
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty());
+    if let Some(token) = &telegram_bot_token
+        && state
+            .dispatcher
+            .notificators
+            .telegram
+            .token_taken(&tenant_id, token)
+    {
+        return ResponseFabric::bad_request::<TenantResponse>(
+            "telegram_bot_token is already used by other tenant or the server",
+        );
+    }
+
+    let events_url = payload
+        .events_url
+        .map(|u| u.trim().to_string())
+        .filter(|u| !u.is_empty());
+    if let Some(url) = &events_url
+        && let Err(e) = events::validate_events_url(url)
+    {
+        return ResponseFabric::bad_request::<TenantResponse>(&e);
+    }
+
+    let (created_at, webhook_secret) = match state.storage.get_tenant(&tenant_id).await {
+        Ok(Some(existing)) => (existing.created_at, existing.webhook_secret),
+        Ok(None) => (chrono::Utc::now().to_rfc3339(), None),
+        Err(e) => {
+            tracing::error!("failed to get tenant: {}", e);
+            return ResponseFabric::internal_server_error::<TenantResponse>("Failed to get tenant");
+        }
+    };
+
+    // Tenants saved before secrets were introduced get one too
+    let generated_secret = match webhook_secret.is_none() || payload.rotate_webhook_secret {
+        true => Some(tenants::generate_webhook_secret()),
+        false => None,
+    };
+
+    let tenant = Tenant {
+        id: tenant_id,
+        telegram_bot_token,
+        events_url,
+        webhook_secret: generated_secret.clone().or(webhook_secret),
+        created_at,
+    };
+
+    if let Err(e) = state.storage.persist_tenant(&tenant).await {
+        tracing::error!("failed to persist tenant: {}", e);
+        return ResponseFabric::internal_server_error::<TenantResponse>("Failed to save tenant");
+    }
+
+    state
+        .dispatcher
+        .notificators
+        .telegram
+        .set_tenant_token(&tenant.id, tenant.telegram_bot_token.clone());
+    state
+        .bot_updates
+        .watch(Some(tenant.id.clone()), tenant.telegram_bot_token.clone());
+
+    tracing::info!("tenant {} saved", tenant.id);
+
+    return (
+        StatusCode::OK,
+        Json(TenantResponse {
+            message: "Saved".to_string(),
+            tenant: Some(TenantInfo::from(&tenant)),
+            webhook_secret: generated_secret,
+        }),
+    );
+}
+
+fn validate_template_payload(payload: &TemplatePayload) -> Result<(), String> {
+    if payload.name.trim().is_empty() {
+        return Err("name can't be empty".to_string());
+    }
+
+    return templates::validate_body(&payload.body);
+}
+
+#[axum::debug_handler]
+pub async fn create_template(
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+    Json(payload): Json<TemplatePayload>,
+) -> (StatusCode, Json<TemplateResponse>) {
+    if let Err(e) = caller.require(Scope::Schedule) {
+        return ResponseFabric::forbidden::<TemplateResponse>(&e);
+    }
+
+    if let Err(e) = validate_template_payload(&payload) {
+        return ResponseFabric::bad_request::<TemplateResponse>(&e);
+    }
+
+    let template = Template {
+        id: Uuid::new_v4().to_string(),
+        tenant: caller.tenant.clone(),
+        name: payload.name.trim().to_string(),
+        body: payload.body,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        updated_at: None,
+    };
+
+    if let Err(e) = state.storage.persist_template(&template).await {
+        tracing::error!("failed to persist template: {}", e);
+        return ResponseFabric::internal_server_error::<TemplateResponse>(
+            "Failed to save template",
+        );
+    }
+
+    return (
+        StatusCode::OK,
+        Json(TemplateResponse {
+            message: "Created".to_string(),
+            template: Some(template),
+        }),
+    );
+}
+
+#[axum::debug_handler]
+pub async fn list_templates(
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<TemplateListResponse>) {
+    if let Err(e) = caller.require(Scope::Read) {
+        return ResponseFabric::forbidden::<TemplateListResponse>(&e);
+    }
+
+    let mut templates = match state.storage.get_all_templates(&caller.tenant).await {
+        Ok(t) => t,
+        Err(e) => {
+            tracing::error!("failed to list templates: {}", e);
+            return ResponseFabric::internal_server_error::<TemplateListResponse>(
+                "Failed to list templates",
+            );
+        }
+    };
+
+    templates.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+
+    return (
+        StatusCode::OK,
+        Json(TemplateListResponse {
+            message: "Found".to_string(),
+            templates,
+        }),
+    );
+}
+
+#[axum::debug_handler]
+pub async fn get_template(
+    Path(template_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<TemplateResponse>) {
+    if let Err(e) = caller.require(Scope::Read) {
+        return ResponseFabric::forbidden::<TemplateResponse>(&e);
+    }
+
+    return match state
+        .storage
+        .get_template(&caller.tenant, &template_id)
+        .await
+    {
+        Ok(Some(template)) => (
+            StatusCode::OK,
+            Json(TemplateResponse {
+                message: "Found".to_string(),
+                template: Some(template),
+            }),
+        ),
+        Ok(None) => ResponseFabric::not_found::<TemplateResponse>("Template not found"),
+        Err(e) => {
+            tracing::error!("failed to get template: {}", e);
+            ResponseFabric::internal_server_error::<TemplateResponse>("Failed to get template")
+        }
+    };
+}
+
+// Replaces name and body. Notifications pick the new body up on the next send
+#[axum::debug_handler]
+pub async fn update_template(
+    Path(template_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+    Json(payload): Json<TemplatePayload>,
+) -> (StatusCode, Json<TemplateResponse>) {
+    if let Err(e) = caller.require(Scope::Schedule) {
+        return ResponseFabric::forbidden::<TemplateResponse>(&e);
+    }
+
+    if let Err(e) = validate_template_payload(&payload) {
+        return ResponseFabric::bad_request::<TemplateResponse>(&e);
+    }
+
+    let mut template = match state
+        .storage
+        .get_template(&caller.tenant, &template_id)
+        .await
+    {
+        Ok(Some(t)) => t,
+        Ok(None) => return ResponseFabric::not_found::<TemplateResponse>("Template not found"),
+        Err(e) => {
+            tracing::error!("failed to get template: {}", e);
+            return ResponseFabric::internal_server_error::<TemplateResponse>(
+                "Failed to get template",
+            );
+        }
+    };
+
+    template.name = payload.name.trim().to_string();
+    template.body = payload.body;
+    template.updated_at = Some(chrono::Utc::now().to_rfc3339());
+
+    if let Err(e) = state.storage.persist_template(&template).await {
+        tracing::error!("failed to persist template: {}", e);
+        return ResponseFabric::internal_server_error::<TemplateResponse>(
+            "Failed to save template",
+        );
+    }
+
+    return (
+        StatusCode::OK,
+        Json(TemplateResponse {
+            message: "Updated".to_string(),
+            template: Some(template),
+        }),
+    );
+}
+
+// Templates used by saved notifications can't be deleted
+#[axum::debug_handler]
+pub async fn delete_template(
+    Path(template_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<MessageResponse>) {
+    if let Err(e) = caller.require(Scope::Schedule) {
+        return ResponseFabric::forbidden::<MessageResponse>(&e);
+    }
+
+    let notifications = match state.storage.get_all_notifications(&caller.tenant).await {
+        Ok(n) => n,
+        Err(e) => {
+            tracing::error!("failed to list notifications: {}", e);
+            return ResponseFabric::internal_server_error::<MessageResponse>(
+                "Failed to delete template",
+            );
+        }
+    };
+
+    let users = notifications
+        .iter()
+        .filter(|n| n.template_id.as_deref() == Some(template_id.as_str()))
+        .count();
+    if users > 0 {
+        return ResponseFabric::conflict::<MessageResponse>(&format!(
+            "Template is used by {} notifications",
+            users
+        ));
+    }
+
+    return match state
+        .storage
+        .delete_template(&caller.tenant, &template_id)
+        .await
+    {
+        Ok(true) => ResponseFabric::ok_with_id("Deleted", template_id),
+        Ok(false) => ResponseFabric::not_found::<MessageResponse>("Template not found"),
+        Err(e) => {
+            tracing::error!("failed to delete template: {}", e);
+            ResponseFabric::internal_server_error::<MessageResponse>("Failed to delete template")
+        }
+    };
+}
+
+// Multipart form with the file in "file" field
+#[axum::debug_handler]
+pub async fn upload_attachment(
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+    mut multipart: Multipart,
+) -> (StatusCode, Json<AttachmentResponse>) {
+    if let Err(e) = caller.require(Scope::Schedule) {
+        return ResponseFabric::forbidden::<AttachmentResponse>(&e);
+    }
+
+    let field = loop {
+        match multipart.next_field().await {
+            Ok(Some(f)) if f.name() == Some("file") => break f,
+            Ok(Some(_)) => continue,
+            Ok(None) => {
+                return ResponseFabric::bad_request::<AttachmentResponse>(
+                    "Multipart field \"file\" is required",
+                );
+            }
+            Err(e) => {
+                return ResponseFabric::bad_request::<AttachmentResponse>(&format!(
+                    "Invalid multipart body: {}",
+                    e
+                ));
+            }
+        }
+    };
+
+    let file_name = field.file_name().unwrap_or("").trim().to_string();
+    // Clients often send generic type, so it's guessed by extension then
+    let content_type = match field.content_type() {
+        Some(t) if t != "application/octet-stream" => t.to_string(),
+        _ => attachments::guess_content_type(&file_name),
+    };
+
+    let data = match field.bytes().await {
+        Ok(d) => d,
+        Err(e) => {
+            return ResponseFabric::bad_request::<AttachmentResponse>(&format!(
+                "Failed to read file: {}",
+                e
+            ));
+        }
+    };
+
+    if let Err(e) = attachments::validate_upload(&file_name, &data) {
+        return ResponseFabric::bad_request::<AttachmentResponse>(&e);
+    }
+
+    let attachment = Attachment {
+        id: Uuid::new_v4().to_string(),
+        tenant: caller.tenant.clone(),
+        file_name,
+        content_type,
+        size: data.len(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+
+    if let Err(e) = state.storage.persist_attachment(&attachment, &data).await {
+        tracing::error!("failed to persist attachment: {}", e);
+        return ResponseFabric::internal_server_error::<AttachmentResponse>(
+            "Failed to save attachment",
+        );
+    }
+
+    return (
+        StatusCode::OK,
+        Json(AttachmentResponse {
+            message: "Uploaded".to_string(),
+            attachment: Some(attachment),
+        }),
+    );
+}
+
+#[axum::debug_handler]
+pub async fn list_attachments(
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<AttachmentListResponse>) {
+    if let Err(e) = caller.require(Scope::Read) {
+        return ResponseFabric::forbidden::<AttachmentListResponse>(&e);
+    }
+
+    let mut attachments = match state.storage.get_all_attachments(&caller.tenant).await {
+        Ok(a) => a,
+        Err(e) => {
+            tracing::error!("failed to list attachments: {}", e);
+            return ResponseFabric::internal_server_error::<AttachmentListResponse>(
+                "Failed to list attachments",
+            );
+        }
+    };
+
+    attachments.sort_by(|a, b| {
+        a.created_at
+            .cmp(&b.created_at)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+
+    return (
+        StatusCode::OK,
+        Json(AttachmentListResponse {
+            message: "Found".to_string(),
+            attachments,
+        }),
+    );
+}
+
+#[axum::debug_handler]
+pub async fn get_attachment(
+    Path(attachment_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<AttachmentResponse>) {
+    if let Err(e) = caller.require(Scope::Read) {
+        return ResponseFabric::forbidden::<AttachmentResponse>(&e);
+    }
+
+    return match state
+        .storage
+        .get_attachment(&caller.tenant, &attachment_id)
+        .await
+    {
+        Ok(Some(attachment)) => (
+            StatusCode::OK,
+            Json(AttachmentResponse {
+                message: "Found".to_string(),
+                attachment: Some(attachment),
+            }),
+        ),
+        Ok(None) => ResponseFabric::not_found::<AttachmentResponse>("Attachment not found"),
+        Err(e) => {
+            tracing::error!("failed to get attachment: {}", e);
+            ResponseFabric::internal_server_error::<AttachmentResponse>("Failed to get attachment")
+        }
+    };
+}
+
+// Attachments used by saved notifications can't be deleted
+#[axum::debug_handler]
+pub async fn delete_attachment(
+    Path(attachment_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<MessageResponse>) {
+    if let Err(e) = caller.require(Scope::Schedule) {
+        return ResponseFabric::forbidden::<MessageResponse>(&e);
+    }
+
+    let notifications = match state.storage.get_all_notifications(&caller.tenant).await {
+        Ok(n) => n,
+        Err(e) => {
+            tracing::error!("failed to list notifications: {}", e);
+            return ResponseFabric::internal_server_error::<MessageResponse>(
+                "Failed to delete attachment",
+            );
+        }
+    };
+
+    let users = notifications
+        .iter()
+        .filter(|n| {
+            n.attachments.iter().any(|a| {
+                matches!(&a.source, AttachmentSource::Upload { attachment_id: id } if *id == attachment_id)
+            })
+        })
+        .count();
+    if users > 0 {
+        return ResponseFabric::conflict::<MessageResponse>(&format!(
+            "Attachment is used by {} notifications",
+            users
+        ));
+    }
+
+    return match state
+        .storage
+        .delete_attachment(&caller.tenant, &attachment_id)
+        .await
+    {
+        Ok(true) => ResponseFabric::ok_with_id("Deleted", attachment_id),
+        Ok(false) => ResponseFabric::not_found::<MessageResponse>("Attachment not found"),
+        Err(e) => {
+            tracing::error!("failed to delete attachment: {}", e);
+            ResponseFabric::internal_server_error::<MessageResponse>("Failed to delete attachment")
+        }
+    };
+}
+
+#[axum::debug_handler]
+pub async fn list_subscriptions(
+    Query(query): Query<ListSubscriptionsQuery>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<SubscriptionListResponse>) {
+    if let Err(e) = caller.require(Scope::Read) {
+        return ResponseFabric::forbidden::<SubscriptionListResponse>(&e);
+    }
+
+    let mut subscriptions = match state.storage.get_all_subscriptions(&caller.tenant).await {
+        Ok(s) => s,
+        Err(e) => {
+            tracing::error!("failed to list subscriptions: {}", e);
+            return ResponseFabric::internal_server_error::<SubscriptionListResponse>(
+                "Failed to list subscriptions",
+            );
+        }
+    };
+
+    let topic = query.topic.map(|t| t.trim().to_lowercase());
+    subscriptions.retain(|s| {
+        topic.as_ref().is_none_or(|t| s.topic == *t)
+            && caller.allows(&NotificationPlatform::Telegram, &s.recipient())
+    });
+    subscriptions.sort_by(|a, b| {
+        a.created_at
+            .cmp(&b.created_at)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+
+    return (
+        StatusCode::OK,
+        Json(SubscriptionListResponse {
+            message: "Found".to_string(),
+            subscriptions,
+        }),
+    );
+}
+
+#[axum::debug_handler]
+pub async fn delete_subscription(
+    Path(subscription_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(caller): Extension<ApiKey>,
+) -> (StatusCode, Json<MessageResponse>) {
+    if let Err(e) = caller.require(Scope::Schedule) {
+        return ResponseFabric::forbidden::<MessageResponse>(&e);
+    }
+
+    if !caller.has_unrestricted_access() {
+        match state.storage.get_all_subscriptions(&caller.tenant).await {
+            Ok(subscriptions) => {
+                let allowed = subscriptions.iter().any(|s| {
+                    s.id == subscription_id
+                        && caller.allows(&NotificationPlatform::Telegram, &s.recipient())
+                });
+                if !allowed {
+                    return ResponseFabric::not_found::<MessageResponse>("Subscription not found");
+                }
+            }
+            Err(e) => {
+                tracing::error!("failed to list subscriptions: {}", e);
+                return ResponseFabric::internal_server_error::<MessageResponse>(
+                    "Failed to delete subscription",
+                );
+            }
+        }
+    }
+
+    return match state
+        .storage
+        .delete_subscription(&caller.tenant, &subscription_id)
+        .await
+    {
+        Ok(true) => ResponseFabric::ok_with_id("Deleted", subscription_id),
+        Ok(false) => ResponseFabric::not_found::<MessageResponse>("Subscription not found"),
+        Err(e) => {
+            tracing::error!("failed to delete subscription: {}", e);
+            ResponseFabric::internal_server_error::<MessageResponse>(
+                "Failed to delete subscription",
+            )
+        }
+    };
+}
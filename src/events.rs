@@ -0,0 +1,129 @@
+use serde::Serialize;
+use std::{env, sync::Arc};
+
+use crate::{
+    notifications::NotificationKind,
+    notificators::{WebhookNotificator, webhook},
+    recipients::Recipient,
+    storage::NotificationStore,
+    tenants::DEFAULT_TENANT,
+};
+
+// Something the owning system should know about, posted to events url of the tenant
+#[derive(Debug, Serialize, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    // Send failed permanently, e.g. the user blocked the bot
+    RecipientUnreachable {
+        notification_id: String,
+        kind: NotificationKind,
+        recipient: Recipient,
+        reason: String,
+        // Stored notifications are deactivated, instant ones just fail
+        deactivated: bool,
+    },
+}
+
+#[derive(Serialize)]
+struct EventEnvelope<'a> {
+    tenant: &'a str,
+    #[serde(flatten)]
+    event: &'a Event,
+    timestamp: i64, // Unix seconds, same as in timestamp header
+}
+
+// Posts events signed with webhook secret of the tenant, like webhook notifications.
+// Delivery is best effort: single try in background, failures are logged
+pub struct Events {
+    // None when WEBHOOK_SECRET is not set, events are disabled then
+    webhook: Option<Arc<WebhookNotificator>>,
+    // Url of the default tenant, other tenants set their own
+    default_url: Option<String>,
+    storage: Arc<dyn NotificationStore>,
+}
+
+impl Events {
+    pub fn new(
+        webhook: Option<Arc<WebhookNotificator>>,
+        storage: Arc<dyn NotificationStore>,
+    ) -> Result<Self, String> {
+        let default_url = match env::var("EVENTS_URL") {
+            Ok(url) if !url.trim().is_empty() => {
+                validate_events_url(url.trim())?;
+                Some(url.trim().to_string())
+            }
+            _ => None,
+        };
+
+        if default_url.is_some() && webhook.is_none() {
+            tracing::warn!("EVENTS_URL is set, but WEBHOOK_SECRET is not. Events are disabled");
+        }
+
+        return Ok(Events {
+            webhook,
+            default_url,
+            storage,
+        });
+    }
+
+    pub fn emit(self: &Arc<Self>, tenant: &str, event: Event) {
+        if self.webhook.is_none() {
+            return;
+        }
+
+        let events = self.clone();
+        let tenant = tenant.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = events.post(&tenant, &event).await {
+                tracing::error!("failed to post event of tenant {}: {}", tenant, e);
+            }
+        });
+    }
+
+    async fn post(&self, tenant: &str, event: &Event) -> Result<(), String> {
+        let webhook = match &self.webhook {
+            Some(w) => w,
+            None => return Ok(()),
+        };
+
+        let url = match tenant {
+            DEFAULT_TENANT => self.default_url.clone(),
+            _ => self
+                .storage
+                .get_tenant(tenant)
+                .await?
+                .and_then(|t| t.events_url),
+        };
+
+        let url = match url {
+            Some(u) => u,
+            None => return Ok(()),
+        };
+
+        let timestamp = chrono::Utc::now().timestamp();
+        let body = serde_json::to_vec(&EventEnvelope {
+            tenant,
+            event,
+            timestamp,
+        })
+        .map_err(|e| format!("failed to serialize event: {}", e))?;
+
+        return webhook
+            .post(tenant, &url, timestamp, body)
+            .await
+            .map_err(|e| e.to_string());
+    }
+}
+
+pub fn validate_events_url(url: &str) -> Result<(), String> {
+    return match reqwest::Url::parse(url) {
+        Ok(u) if u.scheme() == "http" || u.scheme() == "https" => {
+            webhook::check_target(&u, webhook::private_targets_allowed())
+                .map_err(|e| format!("events url \"{}\" is not allowed: {}", url, e))
+        }
+        _ => Err(format!(
+            "invalid events url \"{}\". Expected http or https url",
+            url
+        )),
+    };
+}
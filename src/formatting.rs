@@ -0,0 +1,903 @@
+use std::collections::HashMap;
+
+use crate::notifications::MessageFormat;
+
+// Characters, which must be escaped with '\' outside of entities
+const MARKDOWN_V2_SPECIAL: &str = "_*[]()~`>#+-=|{}.!\\";
+
+// Tags telegram accepts in HTML parse mode
+const HTML_TAGS: [&str; 16] = [
+    "b",
+    "strong",
+    "i",
+    "em",
+    "u",
+    "ins",
+    "s",
+    "strike",
+    "del",
+    "span",
+    "tg-spoiler",
+    "a",
+    "tg-emoji",
+    "code",
+    "pre",
+    "blockquote",
+];
+const HTML_NAMED_ENTITIES: [&str; 4] = ["lt", "gt", "amp", "quot"];
+// Longest entity telegram may accept, e.g. "&#x1F600;"
+const MAX_HTML_ENTITY_LENGTH: usize = 10;
+
+// Makes text show up literally in the given format
+pub fn escape(format: &MessageFormat, text: &str) -> String {
+    return match format {
+        MessageFormat::Plain => text.to_string(),
+        MessageFormat::MarkdownV2 => {
+            let mut escaped = String::with_capacity(text.len());
+            for c in text.chars() {
+                if MARKDOWN_V2_SPECIAL.contains(c) {
+                    escaped.push('\\');
+                }
+                escaped.push(c);
+            }
+            escaped
+        }
+        MessageFormat::Html => text
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;"),
+    };
+}
+
+// Checks markup the same way telegram does before sending,
+// so broken text is rejected at registration
+pub fn validate(format: &MessageFormat, text: &str) -> Result<(), String> {
+    return match format {
+        MessageFormat::Plain => Ok(()),
+        MessageFormat::MarkdownV2 => validate_markdown_v2(text),
+        MessageFormat::Html => validate_html(text),
+    };
+}
+
+// Number of characters telegram counts against message limits, i.e. length
+// of text with markup removed, in UTF-16 code units. Text must be valid
+pub fn visible_length(format: &MessageFormat, text: &str) -> usize {
+    return tokenize(format, text).iter().map(Token::width).sum();
+}
+
+// Splits text into parts of at most `limit` visible characters, preferably
+// on paragraph, then line, then word boundaries. Entities crossing a split are
+// closed at the end of the part and opened again in the next one, so every
+// part is valid on it's own. Parts without visible text are dropped
+pub fn split(format: &MessageFormat, text: &str, limit: usize) -> Vec<String> {
+    let tokens = tokenize(format, text);
+    let closings: HashMap<usize, &str> = tokens
+        .iter()
+        .filter_map(|t| match t {
+            Token::Close(id, markup) => Some((*id, markup.as_str())),
+            _ => None,
+        })
+        .collect();
+
+    let mut parts = Vec::new();
+    // Entities open at the beginning of the part: id and opening markup
+    let mut open: Vec<(usize, &str)> = Vec::new();
+    let mut start = 0;
+
+    while start < tokens.len() {
+        let end = part_end(&tokens, start, limit);
+        let mut part: String = open.iter().map(|(_, opening)| *opening).collect();
+        let mut blank = true;
+
+        for token in &tokens[start..end] {
+            match token {
+                Token::Text(raw, _) => {
+                    blank = blank && raw.trim().is_empty();
+                    part.push_str(raw);
+                }
+                Token::Markup(raw) => part.push_str(raw),
+                Token::Open(id, raw) => {
+                    part.push_str(raw);
+                    open.push((*id, raw));
+                }
+                Token::Close(id, raw) => {
+                    part.push_str(raw);
+                    open.retain(|(open_id, _)| open_id != id);
+                }
+            }
+        }
+
+        for (id, _) in open.iter().rev() {
+            part.push_str(closings.get(id).copied().unwrap_or(""));
+        }
+
+        if !blank {
+            parts.push(part);
+        }
+        start = end;
+    }
+
+    return parts;
+}
+
+// Piece of formatted text
+enum Token {
+    // Single visible character as written in the text, e.g. "\\." or "&amp;",
+    // along with it's width in UTF-16 code units
+    Text(String, usize),
+    // Markup, which isn't an entity, e.g. quote mark
+    Markup(String),
+    // Opening and closing markup of entity with the given id
+    Open(usize, String),
+    Close(usize, String),
+}
+
+impl Token {
+    fn width(&self) -> usize {
+        return match self {
+            Token::Text(_, width) => *width,
+            _ => 0,
+        };
+    }
+
+    fn text(c: char) -> Token {
+        return Token::Text(c.to_string(), c.len_utf16());
+    }
+}
+
+// End of the part starting at `start`: the rest of tokens if it fits the limit,
+// otherwise the best boundary. Boundaries in the first half of the part are
+// used only if there is nothing better
+fn part_end(tokens: &[Token], start: usize, limit: usize) -> usize {
+    // Token index right after the boundary and visible length before it
+    let mut paragraph: Option<(usize, usize)> = None;
+    let mut line: Option<(usize, usize)> = None;
+    let mut word: Option<(usize, usize)> = None;
+    let mut previous_newline = false;
+    let mut visible = 0;
+
+    for (i, token) in tokens.iter().enumerate().skip(start) {
+        let (raw, width) = match token {
+            Token::Text(raw, width) => (raw.as_str(), *width),
+            _ => continue,
+        };
+
+        if visible + width > limit {
+            let boundaries = [paragraph, line, word];
+            let end = boundaries
+                .iter()
+                .flatten()
+                .find(|(_, before)| *before >= limit / 2)
+                .or(boundaries.iter().flatten().next())
+                .map(|(end, _)| *end);
+
+            // Single character wider than the limit still makes a part
+            return end.unwrap_or(i.max(start + 1));
+        }
+
+        visible += width;
+        match raw {
+            "\n" if previous_newline => {
+                paragraph = Some((i + 1, visible));
+                line = Some((i + 1, visible));
+            }
+            "\n" => line = Some((i + 1, visible)),
+            " " => word = Some((i + 1, visible)),
+            _ => (),
+        }
+        previous_newline = raw == "\n";
+    }
+
+    return tokens.len();
+}
+
+fn tokenize(format: &MessageFormat, text: &str) -> Vec<Token> {
+    return match format {
+        MessageFormat::Plain => text.chars().map(Token::text).collect(),
+        MessageFormat::MarkdownV2 => markdown_v2_tokens(text),
+        MessageFormat::Html => html_tokens(text),
+    };
+}
+
+fn markdown_v2_tokens(text: &str) -> Vec<Token> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut open: Vec<(usize, &'static str)> = Vec::new();
+    let mut next_id = 0;
+    // Quote lasts till the end of line
+    let mut quote: Option<usize> = None;
+    let mut line_start = true;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let at_line_start = line_start;
+        line_start = c == '\n';
+
+        match c {
+            '\n' => {
+                if let Some(id) = quote.take() {
+                    tokens.push(Token::Close(id, String::new()));
+                }
+                tokens.push(Token::text(c));
+            }
+            '\\' => {
+                tokens.push(escaped(&chars, i));
+                i += 2;
+                continue;
+            }
+            '`' => {
+                let delimiter = if chars[i..].starts_with(&['`', '`', '`']) {
+                    "```"
+                } else {
+                    "`"
+                };
+                let end = skip_code(&chars, i, delimiter).unwrap_or(chars.len());
+                i = code_tokens(&chars, i, end, delimiter, next_id, &mut tokens);
+                next_id += 1;
+                continue;
+            }
+            '*' => toggle_token(&mut tokens, &mut open, &mut next_id, "*"),
+            '~' => toggle_token(&mut tokens, &mut open, &mut next_id, "~"),
+            '_' if chars.get(i + 1) == Some(&'_') => {
+                toggle_token(&mut tokens, &mut open, &mut next_id, "__");
+                i += 1;
+            }
+            '_' => toggle_token(&mut tokens, &mut open, &mut next_id, "_"),
+            '|' if chars.get(i + 1) == Some(&'|') => {
+                toggle_token(&mut tokens, &mut open, &mut next_id, "||");
+                i += 1;
+            }
+            '[' => {
+                tokens.push(Token::Open(next_id, "[".to_string()));
+                open.push((next_id, "["));
+                next_id += 1;
+            }
+            ']' if open.last().is_some_and(|(_, e)| *e == "[") => {
+                let end = skip_link_url(&chars, i + 1).unwrap_or(i + 1);
+                if let Some((id, _)) = open.pop() {
+                    tokens.push(Token::Close(id, chars[i..end].iter().collect()));
+                }
+                i = end;
+                continue;
+            }
+            '>' if at_line_start => {
+                tokens.push(Token::Open(next_id, ">".to_string()));
+                quote = Some(next_id);
+                next_id += 1;
+            }
+            _ => tokens.push(Token::text(c)),
+        }
+
+        i += 1;
+    }
+
+    if let Some(id) = quote {
+        tokens.push(Token::Close(id, String::new()));
+    }
+
+    return tokens;
+}
+
+// Escaped character, it's backslash has no width
+fn escaped(chars: &[char], i: usize) -> Token {
+    return match chars.get(i + 1) {
+        Some(c) => Token::Text(format!("\\{}", c), c.len_utf16()),
+        None => Token::Markup("\\".to_string()),
+    };
+}
+
+// Code / pre block between `start` and `end` as a single entity.
+// Language line of pre block is a part of it's opening markup.
+// Returns position right after the block
+fn code_tokens(
+    chars: &[char],
+    start: usize,
+    end: usize,
+    delimiter: &str,
+    id: usize,
+    tokens: &mut Vec<Token>,
+) -> usize {
+    let mut content_start = start + delimiter.len();
+    let content_end = match chars[..end].ends_with(&delimiter.chars().collect::<Vec<char>>()) {
+        true if end >= content_start + delimiter.len() => end - delimiter.len(),
+        _ => end,
+    };
+
+    if delimiter == "```"
+        && let Some(newline) = chars[content_start..content_end]
+            .iter()
+            .position(|c| *c == '\n')
+    {
+        content_start += newline + 1;
+    }
+
+    tokens.push(Token::Open(
+        id,
+        chars[start..content_start].iter().collect(),
+    ));
+
+    let mut i = content_start;
+    while i < content_end {
+        if chars[i] == '\\' {
+            tokens.push(escaped(chars, i));
+            i += 2;
+            continue;
+        }
+        tokens.push(Token::text(chars[i]));
+        i += 1;
+    }
+
+    tokens.push(Token::Close(id, chars[content_end..end].iter().collect()));
+    return end;
+}
+
+fn toggle_token(
+    tokens: &mut Vec<Token>,
+    open: &mut Vec<(usize, &'static str)>,
+    next_id: &mut usize,
+    entity: &'static str,
+) {
+    if let Some((id, _)) = open.last().filter(|(_, e)| *e == entity) {
+        tokens.push(Token::Close(*id, entity.to_string()));
+        open.pop();
+        return;
+    }
+
+    tokens.push(Token::Open(*next_id, entity.to_string()));
+    open.push((*next_id, entity));
+    *next_id += 1;
+}
+
+fn html_tokens(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut open: Vec<(usize, String)> = Vec::new();
+    let mut next_id = 0;
+    let mut i = 0;
+
+    while let Some(c) = text[i..].chars().next() {
+        match c {
+            '<' if text[i..].contains('>') => {
+                let end = i + text[i..].find('>').unwrap_or(0);
+                let tag = &text[i..=end];
+                let name = tag
+                    .trim_start_matches(['<', '/'])
+                    .trim_end_matches('>')
+                    .split(|c: char| c.is_whitespace())
+                    .next()
+                    .unwrap_or("")
+                    .to_lowercase();
+
+                if tag.starts_with("</") {
+                    match open.iter().rposition(|(_, n)| *n == name) {
+                        Some(position) => {
+                            let (id, _) = open.remove(position);
+                            tokens.push(Token::Close(id, tag.to_string()));
+                        }
+                        None => tokens.push(Token::Markup(tag.to_string())),
+                    }
+                } else {
+                    tokens.push(Token::Open(next_id, tag.to_string()));
+                    open.push((next_id, name));
+                    next_id += 1;
+                }
+
+                i = end + 1;
+                continue;
+            }
+            '&' => {
+                let end = text[i..]
+                    .find(';')
+                    .filter(|end| *end <= MAX_HTML_ENTITY_LENGTH)
+                    .map(|end| i + end);
+
+                if let Some(end) = end
+                    && is_html_entity(&text[i + 1..end])
+                {
+                    tokens.push(Token::Text(
+                        text[i..=end].to_string(),
+                        html_entity_width(&text[i + 1..end]),
+                    ));
+                    i = end + 1;
+                    continue;
+                }
+
+                tokens.push(Token::text(c));
+            }
+            _ => tokens.push(Token::text(c)),
+        }
+
+        i += c.len_utf8();
+    }
+
+    return tokens;
+}
+
+// Numeric entities may stand for characters outside of the BMP
+fn html_entity_width(entity: &str) -> usize {
+    let code = match entity.strip_prefix('#') {
+        Some(code) => match code.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => code.parse::<u32>().ok(),
+        },
+        None => None,
+    };
+
+    return code
+        .and_then(char::from_u32)
+        .map(|c| c.len_utf16())
+        .unwrap_or(1);
+}
+
+fn validate_markdown_v2(text: &str) -> Result<(), String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut open: Vec<&'static str> = Vec::new();
+    let mut line_start = true;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let at_line_start = line_start;
+        line_start = c == '\n';
+
+        match c {
+            '\\' => {
+                match chars.get(i + 1) {
+                    Some(next) if (1..=126).contains(&(*next as u32)) => (),
+                    _ => {
+                        return Err(format!(
+                            "'\\' at position {} must be followed by an ASCII character",
+                            i
+                        ));
+                    }
+                }
+                i += 2;
+                continue;
+            }
+            '`' => {
+                let delimiter = if chars[i..].starts_with(&['`', '`', '`']) {
+                    "```"
+                } else {
+                    "`"
+                };
+                i = skip_code(&chars, i, delimiter)?;
+                continue;
+            }
+            '*' => toggle_entity(&mut open, "*", i)?,
+            '~' => toggle_entity(&mut open, "~", i)?,
+            '_' if chars.get(i + 1) == Some(&'_') => {
+                toggle_entity(&mut open, "__", i)?;
+                i += 1;
+            }
+            '_' => toggle_entity(&mut open, "_", i)?,
+            '|' if chars.get(i + 1) == Some(&'|') => {
+                toggle_entity(&mut open, "||", i)?;
+                i += 1;
+            }
+            '[' => {
+                if open.contains(&"[") {
+                    return Err(format!("nested link at position {}", i));
+                }
+                open.push("[");
+            }
+            ']' if open.last() == Some(&"[") => {
+                open.pop();
+                i = skip_link_url(&chars, i + 1)?;
+                continue;
+            }
+            // Quote
+            '>' if at_line_start => (),
+            c if MARKDOWN_V2_SPECIAL.contains(c) => {
+                return Err(format!(
+                    "character '{}' at position {} must be escaped with '\\'",
+                    c, i
+                ));
+            }
+            _ => (),
+        }
+
+        i += 1;
+    }
+
+    return match open.last() {
+        Some(entity) => Err(format!("entity \"{}\" is not closed", entity)),
+        None => Ok(()),
+    };
+}
+
+// Closes entity if it's the innermost open one, opens it otherwise
+fn toggle_entity(
+    open: &mut Vec<&'static str>,
+    entity: &'static str,
+    position: usize,
+) -> Result<(), String> {
+    if open.last() == Some(&entity) {
+        open.pop();
+        return Ok(());
+    }
+
+    if open.contains(&entity) {
+        return Err(format!(
+            "entity \"{}\" at position {} overlaps with another one",
+            entity, position
+        ));
+    }
+
+    open.push(entity);
+    return Ok(());
+}
+
+// Returns position right after the closing delimiter of code / pre block
+fn skip_code(chars: &[char], start: usize, delimiter: &str) -> Result<usize, String> {
+    let delimiter: Vec<char> = delimiter.chars().collect();
+    let mut i = start + delimiter.len();
+
+    while i < chars.len() {
+        if chars[i] == '\\' {
+            i += 2;
+            continue;
+        }
+
+        if chars[i..].starts_with(&delimiter) {
+            return Ok(i + delimiter.len());
+        }
+
+        i += 1;
+    }
+
+    return Err(format!("code block at position {} is not closed", start));
+}
+
+// Link text must be followed by "(url)". Returns position right after it
+fn skip_link_url(chars: &[char], start: usize) -> Result<usize, String> {
+    if chars.get(start) != Some(&'(') {
+        return Err(format!(
+            "link at position {} must be followed by (url)",
+            start
+        ));
+    }
+
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            ')' => return Ok(i + 1),
+            _ => i += 1,
+        }
+    }
+
+    return Err(format!("link url at position {} is not closed", start));
+}
+
+fn validate_html(text: &str) -> Result<(), String> {
+    let mut open: Vec<String> = Vec::new();
+    let mut i = 0;
+
+    while let Some(c) = text[i..].chars().next() {
+        match c {
+            '<' => {
+                let end = match text[i..].find('>') {
+                    Some(end) => i + end,
+                    None => {
+                        return Err(format!("'<' at position {} must be escaped as &lt;", i));
+                    }
+                };
+
+                check_html_tag(&text[i + 1..end], &mut open)?;
+                i = end + 1;
+                continue;
+            }
+            '&' => {
+                let end = text[i..]
+                    .find(';')
+                    .filter(|end| *end <= MAX_HTML_ENTITY_LENGTH)
+                    .map(|end| i + end);
+
+                match end {
+                    Some(end) if is_html_entity(&text[i + 1..end]) => {
+                        i = end + 1;
+                        continue;
+                    }
+                    _ => {
+                        return Err(format!("'&' at position {} must be escaped as &amp;", i));
+                    }
+                }
+            }
+            '>' => {
+                return Err(format!("'>' at position {} must be escaped as &gt;", i));
+            }
+            _ => (),
+        }
+
+        i += c.len_utf8();
+    }
+
+    return match open.last() {
+        Some(tag) => Err(format!("tag <{}> is not closed", tag)),
+        None => Ok(()),
+    };
+}
+
+// Tag is the text between '<' and '>'
+fn check_html_tag(tag: &str, open: &mut Vec<String>) -> Result<(), String> {
+    let (closing, tag) = match tag.strip_prefix('/') {
+        Some(t) => (true, t),
+        None => (false, tag),
+    };
+
+    let name = tag
+        .split(|c: char| c.is_whitespace())
+        .next()
+        .unwrap_or("")
+        .to_lowercase();
+    if !HTML_TAGS.contains(&name.as_str()) {
+        return Err(format!("tag <{}> is not supported by telegram", name));
+    }
+
+    if !closing {
+        open.push(name);
+        return Ok(());
+    }
+
+    return match open.pop() {
+        Some(opened) if opened == name => Ok(()),
+        _ => Err(format!("unexpected closing tag </{}>", name)),
+    };
+}
+
+fn is_html_entity(entity: &str) -> bool {
+    if HTML_NAMED_ENTITIES.contains(&entity) {
+        return true;
+    }
+
+    return match entity.strip_prefix('#') {
+        Some(code) => match code.strip_prefix(['x', 'X']) {
+            Some(hex) => !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()),
+            None => !code.is_empty() && code.chars().all(|c| c.is_ascii_digit()),
+        },
+        None => false,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MD: MessageFormat = MessageFormat::MarkdownV2;
+    const HTML: MessageFormat = MessageFormat::Html;
+
+    #[test]
+    fn escape_plain_keeps_text() {
+        assert_eq!(escape(&MessageFormat::Plain, "a_b <c>"), "a_b <c>");
+    }
+
+    #[test]
+    fn escape_markdown_v2_special_characters() {
+        let escaped = escape(&MD, "1.5 * (x_y) = [z]! #tag\\");
+        assert_eq!(escaped, "1\\.5 \\* \\(x\\_y\\) \\= \\[z\\]\\! \\#tag\\\\");
+        assert_eq!(validate(&MD, &escaped), Ok(()));
+        assert_eq!(
+            visible_length(&MD, &escaped),
+            "1.5 * (x_y) = [z]! #tag\\".len()
+        );
+    }
+
+    #[test]
+    fn escape_html_entities() {
+        let escaped = escape(&HTML, "<a href=\"x\">&amp;</a>");
+        assert_eq!(escaped, "&lt;a href=&quot;x&quot;&gt;&amp;amp;&lt;/a&gt;");
+        assert_eq!(validate(&HTML, &escaped), Ok(()));
+    }
+
+    #[test]
+    fn validate_plain_accepts_anything() {
+        assert_eq!(validate(&MessageFormat::Plain, "*_<&"), Ok(()));
+    }
+
+    #[test]
+    fn validate_markdown_v2_accepts_valid_markup() {
+        for text in [
+            "*bold* _italic_ __underline__ ~strike~ ||spoiler||",
+            "*bold _italic bold_ bold*",
+            "[link](https://example.com/a_(b\\))",
+            "`inline *code*` and ```rust\nfn main() {}\n```",
+            ">quote\n>second line\nplain",
+            "escaped \\* \\_ \\` \\\\",
+            "emoji 🙂 and кириллица",
+        ] {
+            assert_eq!(validate(&MD, text), Ok(()), "{}", text);
+        }
+    }
+
+    #[test]
+    fn validate_markdown_v2_rejects_broken_markup() {
+        for text in [
+            "1.5",
+            "*not closed",
+            "*bold _overlap* italic_",
+            "[nested [link](a)](b)",
+            "[no url]",
+            "[url](not closed",
+            "`code",
+            "```\npre",
+            "trailing \\",
+            "a > b",
+        ] {
+            assert!(validate(&MD, text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn validate_html_accepts_valid_markup() {
+        for text in [
+            "<b>bold</b> <i>italic</i> <u>u</u> <s>s</s>",
+            "<b>bold <i>both</i></b>",
+            "<a href=\"https://example.com\">link</a>",
+            "<pre><code class=\"language-rust\">a &lt; b</code></pre>",
+            "<tg-spoiler>hidden</tg-spoiler> <blockquote>q</blockquote>",
+            "&lt;&gt;&amp;&quot;&#128512;&#x1F600;",
+            "<B>upper case</B>",
+        ] {
+            assert_eq!(validate(&HTML, text), Ok(()), "{}", text);
+        }
+    }
+
+    #[test]
+    fn validate_html_rejects_broken_markup() {
+        for text in [
+            "<b>not closed",
+            "<b><i>overlap</b></i>",
+            "</b>",
+            "<div>unsupported</div>",
+            "a < b",
+            "a > b",
+            "fish & chips",
+            "&unknown;",
+            "&#xZZ;",
+        ] {
+            assert!(validate(&HTML, text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn visible_length_plain_counts_utf16() {
+        assert_eq!(visible_length(&MessageFormat::Plain, "abc"), 3);
+        assert_eq!(visible_length(&MessageFormat::Plain, "é"), 1);
+        // Outside of the BMP: surrogate pair
+        assert_eq!(visible_length(&MessageFormat::Plain, "🙂"), 2);
+    }
+
+    #[test]
+    fn visible_length_markdown_v2_skips_markup() {
+        assert_eq!(visible_length(&MD, "*bold* _it_"), 7);
+        assert_eq!(visible_length(&MD, "\\.\\!"), 2);
+        assert_eq!(visible_length(&MD, "[link](https://example.com)"), 4);
+        assert_eq!(visible_length(&MD, "`a\\`b`"), 3);
+        assert_eq!(visible_length(&MD, "```rust\ncode```"), 4);
+        assert_eq!(visible_length(&MD, ">quote"), 5);
+        assert_eq!(visible_length(&MD, "*🙂*"), 2);
+    }
+
+    #[test]
+    fn visible_length_html_skips_tags_and_counts_entities() {
+        assert_eq!(visible_length(&HTML, "<b>bold</b>"), 4);
+        assert_eq!(visible_length(&HTML, "<a href=\"https://x.y\">a</a>"), 1);
+        assert_eq!(visible_length(&HTML, "&lt;&amp;&gt;"), 3);
+        assert_eq!(visible_length(&HTML, "&#128512;"), 2);
+        assert_eq!(visible_length(&HTML, "&#x41;"), 1);
+    }
+
+    // Every part must be valid on it's own and fit the limit
+    fn split_checked(format: &MessageFormat, text: &str, limit: usize) -> Vec<String> {
+        let parts = split(format, text, limit);
+        for part in &parts {
+            assert_eq!(validate(format, part), Ok(()), "{:?}", part);
+            assert!(visible_length(format, part) <= limit, "{:?}", part);
+        }
+        return parts;
+    }
+
+    #[test]
+    fn split_keeps_short_text_whole() {
+        assert_eq!(split_checked(&MD, "*short*", 10), vec!["*short*"]);
+        assert!(split(&MessageFormat::Plain, "  \n ", 10).is_empty());
+    }
+
+    #[test]
+    fn split_prefers_paragraph_then_line_then_word() {
+        let plain = MessageFormat::Plain;
+
+        let parts = split_checked(&plain, "aaaa bbbb\ncccc\n\ndddd eeee", 20);
+        assert_eq!(parts, vec!["aaaa bbbb\ncccc\n\n", "dddd eeee"]);
+
+        let parts = split_checked(&plain, "aaaa bbbb\ncccc dddd eeee", 18);
+        assert_eq!(parts, vec!["aaaa bbbb\n", "cccc dddd eeee"]);
+
+        let parts = split_checked(&plain, "aaaa bbbb cccc dddd", 12);
+        assert_eq!(parts, vec!["aaaa bbbb ", "cccc dddd"]);
+    }
+
+    #[test]
+    fn split_ignores_boundaries_in_the_first_half() {
+        let parts = split_checked(&MessageFormat::Plain, "a\n\nbbbb cccc dddd", 12);
+        assert_eq!(parts, vec!["a\n\nbbbb ", "cccc dddd"]);
+    }
+
+    #[test]
+    fn split_cuts_words_longer_than_the_limit() {
+        let parts = split_checked(&MessageFormat::Plain, "abcdefghij", 4);
+        assert_eq!(parts, vec!["abcd", "efgh", "ij"]);
+    }
+
+    #[test]
+    fn split_reopens_markdown_v2_entities() {
+        let text = "*bold _italic words here_ and more bold words*";
+        let parts = split_checked(&MD, text, 16);
+        assert!(parts.len() > 1);
+        for part in &parts[1..] {
+            assert!(part.starts_with('*'), "{:?}", part);
+        }
+        let visible: usize = parts.iter().map(|p| visible_length(&MD, p)).sum();
+        assert_eq!(visible, visible_length(&MD, text));
+    }
+
+    #[test]
+    fn split_reopens_html_entities() {
+        let text = "<b>bold <a href=\"https://example.com\">link text here</a> tail words</b>";
+        let parts = split_checked(&HTML, text, 12);
+        assert!(parts.len() > 1);
+        for part in &parts[1..] {
+            assert!(part.starts_with("<b>"), "{:?}", part);
+        }
+        assert!(
+            parts
+                .iter()
+                .any(|p| p.contains("<a href=\"https://example.com\">"))
+        );
+    }
+
+    #[test]
+    fn split_reopens_code_block_with_language() {
+        let code: String = (0..10).map(|i| format!("line {}\n", i)).collect();
+        let text = format!("```rust\n{}```", code);
+        let parts = split_checked(&MD, &text, 20);
+        assert!(parts.len() > 1);
+        for part in &parts {
+            assert!(part.starts_with("```rust\n"), "{:?}", part);
+            assert!(part.ends_with("```"), "{:?}", part);
+        }
+
+        let parts = split_checked(&HTML, &format!("<pre>{}</pre>", code), 20);
+        assert!(parts.len() > 1);
+        for part in &parts {
+            assert!(
+                part.starts_with("<pre>") && part.ends_with("</pre>"),
+                "{:?}",
+                part
+            );
+        }
+    }
+
+    #[test]
+    fn split_keeps_escapes_and_entities_whole() {
+        let parts = split_checked(&MD, "a\\.b\\.c\\.d", 2);
+        assert_eq!(parts, vec!["a\\.", "b\\.", "c\\.", "d"]);
+
+        let parts = split_checked(&HTML, "&lt;&gt;&amp;", 2);
+        assert_eq!(parts, vec!["&lt;&gt;", "&amp;"]);
+    }
+
+    #[test]
+    fn split_puts_character_wider_than_the_limit_into_own_part() {
+        // Surrogate pair takes 2 units, so it can't fit the limit of 1
+        let parts = split(&MessageFormat::Plain, "a🙂b", 1);
+        assert_eq!(parts, vec!["a", "🙂", "b"]);
+
+        let parts = split(&MD, "*🙂🙂*", 1);
+        assert_eq!(parts, vec!["*🙂*", "*🙂*"]);
+        for part in &parts {
+            assert_eq!(validate(&MD, part), Ok(()));
+        }
+    }
+}
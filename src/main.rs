@@ -1,29 +1,48 @@
-use crate::storage::Storage;
+use crate::storage::NotificationStore;
 use axum::{
     Router,
-    routing::{get, post},
+    extract::DefaultBodyLimit,
+    middleware,
+    routing::{delete, get, patch, post, put},
 };
+use bot::BotUpdates;
+use dispatcher::{Dispatcher, RetryPolicy};
 use dotenv::dotenv;
-use notificators::TelegramNotificator;
+use events::Events;
+use notificators::{
+    EmailNotificator, Notificators, TelegramNotificator, WebhookNotificator, email::SmtpConfig,
+};
 use scheduler::Scheduler;
 use std::{env, sync::Arc};
 use tower_http::trace::{self, TraceLayer};
 use tracing::Level;
 
+mod attachments;
+mod auth;
+mod bot;
+mod dispatcher;
 mod endpoints;
+mod events;
+mod formatting;
 mod notifications;
 mod notificators;
+mod recipients;
 mod scheduler;
 mod storage;
+mod templates;
+mod tenants;
 mod utils;
 
 const DEFAULT_PORT: i16 = 3692;
 
 #[derive(Clone)]
 pub struct AppState {
-    telegram: Arc<TelegramNotificator>,
-    storage: Arc<Storage>,
+    dispatcher: Arc<Dispatcher>,
+    storage: Arc<dyn NotificationStore>,
     scheduler: Arc<Scheduler>,
+    bot_updates: Arc<BotUpdates>,
+    // Hash of ADMIN_API_KEY env, if set
+    admin_key_hash: Option<String>,
 }
 
 enum AppMode {
@@ -65,52 +84,218 @@ async fn main() {
     };
 
     let app_mode = get_app_mode();
-    let storage = Storage::new(&app_mode);
+    let storage = match storage::from_env(&app_mode).await {
+        Ok(s) => s,
+        Err(e) => panic!("failed to set up storage: {}", e),
+    };
 
-    let telegram_notificator = Arc::new(TelegramNotificator::new(tg_token));
-    let scheduler = Scheduler::new(telegram_notificator.clone());
+    let email_notificator = match SmtpConfig::from_env() {
+        Some(Ok(config)) => match EmailNotificator::new(config) {
+            Ok(n) => Some(Arc::new(n)),
+            Err(e) => panic!("failed to set up email notificator: {}", e),
+        },
+        Some(Err(e)) => panic!("invalid SMTP configuration: {}", e),
+        None => {
+            tracing::info!("SMTP_HOST env not set. Email notifications are disabled");
+            None
+        }
+    };
+
+    let webhook_notificator = match WebhookNotificator::from_env(storage.clone()) {
+        Some(Ok(n)) => Some(Arc::new(n)),
+        Some(Err(e)) => panic!("failed to set up webhook notificator: {}", e),
+        None => {
+            tracing::info!("WEBHOOK_SECRET env not set. Webhook notifications are disabled");
+            None
+        }
+    };
+
+    let events = match Events::new(webhook_notificator.clone(), storage.clone()) {
+        Ok(e) => Arc::new(e),
+        Err(e) => panic!("invalid events configuration: {}", e),
+    };
+
+    let notificators = Arc::new(Notificators {
+        telegram: Arc::new(TelegramNotificator::new(tg_token.clone(), storage.clone())),
+        email: email_notificator,
+        webhook: webhook_notificator,
+    });
+    let retry_policy = match RetryPolicy::from_env() {
+        Ok(p) => p,
+        Err(e) => panic!("invalid retry policy configuration: {}", e),
+    };
+
+    let dispatcher = Arc::new(Dispatcher::new(
+        notificators,
+        storage.clone(),
+        retry_policy,
+        events,
+    ));
+    let scheduler = Arc::new(Scheduler::new(dispatcher.clone(), storage.clone()));
+    let bot_updates = Arc::new(BotUpdates::new(storage.clone(), scheduler.clone()));
+
+    let admin_key_hash = match env::var("ADMIN_API_KEY") {
+        Ok(key) if !key.trim().is_empty() => Some(auth::hash_key(key.trim())),
+        _ => {
+            tracing::warn!(
+                "ADMIN_API_KEY env not set. Only api keys saved in storage are accepted"
+            );
+            None
+        }
+    };
 
     let state = AppState {
-        telegram: telegram_notificator,
-        storage: Arc::new(storage),
-        scheduler: Arc::new(scheduler),
+        dispatcher,
+        storage,
+        scheduler,
+        bot_updates,
+        admin_key_hash,
     };
 
-    // schedule already registered notifications
-    match state.storage.get_all_notifications() {
-        Ok(notifications) => {
-            tracing::info!("loaded {} notifications from storage", notifications.len());
-            for notification in notifications {
-                if let Err(e) = state.scheduler.add_notification(&notification) {
-                    tracing::error!(
-                        "failed to register loaded notification with key {}: {}",
-                        &notification.uuid,
-                        e
-                    );
-                };
+    state.bot_updates.watch(None, Some(tg_token));
+
+    let mut tenant_ids = vec![tenants::default_tenant()];
+    match state.storage.get_all_tenants().await {
+        Ok(tenants) => {
+            for tenant in tenants {
+                state
+                    .dispatcher
+                    .notificators
+                    .telegram
+                    .set_tenant_token(&tenant.id, tenant.telegram_bot_token.clone());
+                state
+                    .bot_updates
+                    .watch(Some(tenant.id.clone()), tenant.telegram_bot_token.clone());
+                tenant_ids.push(tenant.id);
             }
         }
         Err(e) => {
-            tracing::error!("Failed to load saved notifications: {}", e);
+            tracing::error!("Failed to load tenants: {}", e);
         }
     };
 
+    // schedule already registered notifications of every tenant
+    for tenant in &tenant_ids {
+        match state.storage.get_all_notifications(tenant).await {
+            Ok(notifications) => {
+                tracing::info!(
+                    "loaded {} notifications of tenant {} from storage",
+                    notifications.len(),
+                    tenant
+                );
+                for notification in notifications {
+                    if let Err(e) = state.scheduler.add_notification(&notification) {
+                        tracing::error!(
+                            "failed to register loaded notification with key {}: {}",
+                            &notification.uuid,
+                            e
+                        );
+                    };
+                }
+            }
+            Err(e) => {
+                tracing::error!(
+                    "Failed to load saved notifications of tenant {}: {}",
+                    tenant,
+                    e
+                );
+            }
+        };
+    }
+
     let router = Router::new()
-        .route("/hc", get(|| async { "Alive!" }))
         .route(
             "/notifications",
-            post(endpoints::register_notification_metadata),
+            get(endpoints::list_notifications).post(endpoints::register_notification_metadata),
+        )
+        .route(
+            "/notifications/:notification_key",
+            patch(endpoints::update_notification_metadata)
+                .delete(endpoints::delete_notification_metadata),
+        )
+        .route(
+            "/notifications/:notification_key/pause",
+            post(endpoints::pause_notification),
+        )
+        .route(
+            "/notifications/:notification_key/resume",
+            post(endpoints::resume_notification),
+        )
+        .route(
+            "/notifications/:notification_key/deliveries",
+            get(endpoints::list_notification_deliveries),
+        )
+        .route(
+            "/notifications/:notification_key/presses",
+            get(endpoints::list_button_presses),
+        )
+        .route(
+            "/templates",
+            get(endpoints::list_templates).post(endpoints::create_template),
+        )
+        .route(
+            "/templates/:template_id",
+            get(endpoints::get_template)
+                .put(endpoints::update_template)
+                .delete(endpoints::delete_template),
+        )
+        .route(
+            "/attachments",
+            get(endpoints::list_attachments)
+                .post(endpoints::upload_attachment)
+                .layer(DefaultBodyLimit::max(attachments::MAX_UPLOAD_BODY_SIZE)),
+        )
+        .route(
+            "/attachments/:attachment_id",
+            get(endpoints::get_attachment).delete(endpoints::delete_attachment),
+        )
+        .route(
+            "/deliveries/:delivery_id",
+            get(endpoints::get_delivery).delete(endpoints::delete_delivery),
+        )
+        .route(
+            "/deliveries/:delivery_id/edit",
+            post(endpoints::edit_delivery),
+        )
+        .route("/subscriptions", get(endpoints::list_subscriptions))
+        .route(
+            "/subscriptions/:subscription_id",
+            delete(endpoints::delete_subscription),
+        )
+        .route("/scheduler/jobs", get(endpoints::list_scheduler_jobs))
+        .route("/telegram/queue", get(endpoints::get_telegram_queue))
+        .route("/dead-letters", get(endpoints::list_dead_letters))
+        .route(
+            "/dead-letters/:dead_letter_id",
+            delete(endpoints::delete_dead_letter),
+        )
+        .route(
+            "/dead-letters/:dead_letter_id/replay",
+            post(endpoints::replay_dead_letter),
         )
         .route(
             "/find/:notification_key",
             get(endpoints::get_notification_metadata),
         )
+        .route(
+            "/admin/keys",
+            get(endpoints::list_api_keys).post(endpoints::create_api_key),
+        )
+        .route("/admin/keys/:key_id", delete(endpoints::revoke_api_key))
+        .route("/admin/tenants", get(endpoints::list_tenants))
+        .route("/admin/tenants/:tenant_id", put(endpoints::upsert_tenant))
+        // Applies to routes above only, healthcheck stays public
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_api_key,
+        ))
+        .route("/hc", get(|| async { "Alive!" }))
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
                 .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
         )
-        .with_state(state);
+        .with_state(state.clone());
 
     let port = match env::var("PORT") {
         Ok(v) => v,
@@ -123,5 +308,43 @@ async fn main() {
     let addr = format!("0.0.0.0:{}", port);
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
 
-    axum::serve(listener, router).await.unwrap();
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .unwrap();
+
+    state.bot_updates.shutdown().await;
+    tracing::info!("stopped");
+}
+
+// Ctrl+C or SIGTERM, which docker sends on stop
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("failed to listen for ctrl+c: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    tracing::info!("shutting down");
 }
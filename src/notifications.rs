@@ -1,21 +1,117 @@
-use std::sync::Arc;
-
-use chrono::{DateTime, Local};
+use chrono::{DateTime, TimeZone};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use uuid::Uuid;
 
-use crate::{TelegramNotificator, notificators::Notificator, notificators::telegram};
+use crate::{
+    recipients::{self, Recipient},
+    tenants::default_tenant,
+    utils::parse_timezone,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum NotificationKind {
     Daily,
     Instant,
+    Cron,
+    Scheduled,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum NotificationPlatform {
     Telegram,
     Email,
+    Webhook,
+}
+
+// Markup of notification text. Only telegram applies it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub enum MessageFormat {
+    #[default]
+    Plain,
+    MarkdownV2,
+    Html,
+}
+
+// Button of telegram inline keyboard
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Button {
+    Url {
+        text: String,
+        url: String,
+    },
+    // Pressing it sends callback query to the bot
+    Callback {
+        text: String,
+        action: ButtonAction,
+        // Used by snooze buttons only
+        #[serde(default)]
+        snooze_minutes: Option<u32>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ButtonAction {
+    // Confirms notification was seen
+    Ack,
+    // Sends notification again after snooze_minutes
+    Snooze,
+}
+
+impl ButtonAction {
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            ButtonAction::Ack => "ack",
+            ButtonAction::Snooze => "snooze",
+        };
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AttachmentKind {
+    Photo,
+    Document,
+}
+
+// Where the attached file comes from
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum AttachmentSource {
+    // File uploaded to /attachments
+    Upload { attachment_id: String },
+    // Downloaded by telegram itself
+    Url { url: String },
+    // File already stored on telegram servers
+    FileId { file_id: String },
+}
+
+// File sent along with the notification. Telegram only
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AttachmentRef {
+    #[serde(rename = "type")]
+    pub kind: AttachmentKind,
+    #[serde(flatten)]
+    pub source: AttachmentSource,
+}
+
+// How telegram shows preview of a link in text
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct LinkPreview {
+    #[serde(default)]
+    pub disabled: bool,
+    // Previewed instead of the first link in text
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub prefer_small_media: bool,
+    #[serde(default)]
+    pub prefer_large_media: bool,
+    #[serde(default)]
+    pub show_above_text: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -23,14 +119,49 @@ pub struct Notification {
     // Base ID
     pub uuid: String,
 
-    // Preformatted message text
+    // Owner of the notification. Records saved before
+    // tenants were introduced belong to the default one
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
+
+    // Preformatted message text. Replaced with the rendered
+    // template on send, if template_id is set
     pub text: String,
 
+    // Markup of text. Template variables are escaped for it
+    #[serde(default)]
+    pub format: MessageFormat,
+
+    // Template of the same tenant and variables it's rendered with
+    #[serde(default)]
+    pub template_id: Option<String>,
+    #[serde(default)]
+    pub template_context: Option<Value>,
+
     // Array of stringified dates
     // Max size = 2
     // Used if kind == NotificationKind::Daily
     pub daily_send_timestamps: Vec<String>,
 
+    // Cron expression, 5 or 6 (with seconds) fields
+    // Used if kind == NotificationKind::Cron
+    #[serde(default)]
+    pub cron: Option<String>,
+
+    // Single send time, RFC3339 in notification zone
+    // Used if kind == NotificationKind::Scheduled
+    #[serde(default)]
+    pub send_at: Option<String>,
+
+    // Set when scheduled notification has fired
+    #[serde(default)]
+    pub completed: bool,
+
+    // IANA time zone, daily timestamps and cron expression are evaluated in.
+    // Records saved without it use the server zone
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+
     // Daily notifications sends every day
     // on timestamps, specified in self.daily_send_timestamps
     pub kind: NotificationKind,
@@ -38,56 +169,139 @@ pub struct Notification {
     // Pretty much speaks for itself
     pub platform: NotificationPlatform,
 
-    // Data, needed to send a message to certain person
-    // for now there ContactData only for Telegram
-    // Gonna implement one for Email too
-    pub send_to: telegram::ContactData,
+    // Data, needed to send a message to certain person.
+    // Always matches self.platform
+    #[serde(deserialize_with = "recipients::deserialize_with_legacy")]
+    pub send_to: Recipient,
+
+    // Email subject. Ignored by other platforms
+    #[serde(default)]
+    pub subject: Option<String>,
+
+    // Rows of inline keyboard buttons. Telegram only
+    #[serde(default)]
+    pub buttons: Vec<Vec<Button>>,
+
+    // Photos or documents, text becomes their caption.
+    // Several attachments are sent as an album
+    #[serde(default)]
+    pub attachments: Vec<AttachmentRef>,
+
+    // Telegram send options, ignored by other platforms.
+    // Silent message, delivered without sound
+    #[serde(default)]
+    pub disable_notification: bool,
+    // Message can't be forwarded or saved
+    #[serde(default)]
+    pub protect_content: bool,
+    // Telegram default preview, if not set
+    #[serde(default)]
+    pub link_preview: Option<LinkPreview>,
+    // Message in the recipient chat the notification replies to.
+    // Sent as a regular message, if that one was deleted
+    #[serde(default)]
+    pub reply_to_message_id: Option<i32>,
+
+    // Paused notifications are kept in storage and scheduler,
+    // but not sent until resumed
+    #[serde(default)]
+    pub paused: bool,
+
+    // Set along with paused when recipient became unreachable,
+    // e.g. blocked the bot. Cleared on resume
+    #[serde(default)]
+    pub deactivated_reason: Option<String>,
+    #[serde(default)]
+    pub deactivated_at: Option<String>, // Stringified UTC date
+
+    // Number of successful sends, available to templates
+    #[serde(default)]
+    pub send_count: u64,
 
     pub last_sent: Option<String>, // Stringified UTC date
     pub created_at: String,        // Stringified UTC date
 }
 
 pub const JSON_NOTIFICATION_KEY: &str = "$";
+const FALLBACK_TIMEZONE: &str = "UTC";
 const MAX_DAILY_TIMESTAMPS: usize = 2;
 
+// IANA name of the server zone
+pub fn default_timezone() -> String {
+    return match iana_time_zone::get_timezone() {
+        Ok(name) if name.parse::<Tz>().is_ok() => name,
+        _ => FALLBACK_TIMEZONE.to_string(),
+    };
+}
+
+impl NotificationKind {
+    // Everything except instant notifications is persisted
+    // and driven by the scheduler
+    pub fn is_scheduled(&self) -> bool {
+        return *self != NotificationKind::Instant;
+    }
+}
+
 impl Notification {
     pub fn default() -> Self {
         let uuid = Uuid::new_v4();
 
         return Notification {
             uuid: uuid.to_string(),
+            tenant: default_tenant(),
             kind: NotificationKind::Instant,
             platform: NotificationPlatform::Telegram,
-            send_to: telegram::ContactData { user_id: 0 },
+            send_to: Recipient::Telegram {
+                chat_id: 0,
+                thread_id: None,
+            },
+            subject: None,
+            buttons: Vec::new(),
+            attachments: Vec::new(),
+            disable_notification: false,
+            protect_content: false,
+            link_preview: None,
+            reply_to_message_id: None,
             created_at: chrono::Local::now().to_string(),
             text: "Default notification".to_string(),
+            format: MessageFormat::Plain,
+            template_id: None,
+            template_context: None,
             daily_send_timestamps: Vec::new(),
+            cron: None,
+            send_at: None,
+            completed: false,
+            timezone: default_timezone(),
+            paused: false,
+            deactivated_reason: None,
+            deactivated_at: None,
+            send_count: 0,
             last_sent: None,
         };
     }
 
-    pub fn add_daily_timestamp(&mut self, timestamp: DateTime<Local>) -> Result<(), String> {
+    pub fn add_daily_timestamp<T: TimeZone>(&mut self, timestamp: DateTime<T>) -> Result<(), String>
+    where
+        T::Offset: std::fmt::Display,
+    {
         if self.daily_send_timestamps.len() >= MAX_DAILY_TIMESTAMPS {
             return Err("You can only send 2 notification a day".to_string());
         }
 
-        self.daily_send_timestamps.push(timestamp.to_string());
+        self.daily_send_timestamps.push(timestamp.to_rfc3339());
         Ok(())
     }
 
-    pub async fn send_instant(&self, bot: Arc<TelegramNotificator>) -> Result<(), String> {
-        let bot = bot.clone();
-        match self.platform {
-            NotificationPlatform::Telegram => match bot.send(self).await {
-                Ok(_) => Ok(()),
-                Err(e) => {
-                    tracing::error!("{}", e);
-                    Err(e.to_string())
-                }
-            },
-            NotificationPlatform::Email => {
-                return Err("Email notifications are not implemented yet.".to_string());
-            }
+    pub fn tz(&self) -> Result<Tz, String> {
+        return parse_timezone(&self.timezone);
+    }
+
+    // Resuming also reactivates deactivated notification
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        if !paused {
+            self.deactivated_reason = None;
+            self.deactivated_at = None;
         }
     }
 }
@@ -115,8 +329,43 @@ impl NotificationBuilder {
         return self;
     }
 
-    pub fn send_to(mut self, send_to: i64) -> NotificationBuilder {
-        self.notification.send_to = telegram::ContactData { user_id: send_to };
+    pub fn format(mut self, format: MessageFormat) -> NotificationBuilder {
+        self.notification.format = format;
+        return self;
+    }
+
+    pub fn template_id(mut self, template_id: Option<String>) -> NotificationBuilder {
+        self.notification.template_id = template_id;
+        return self;
+    }
+
+    pub fn template_context(mut self, context: Option<Value>) -> NotificationBuilder {
+        self.notification.template_context = context;
+        return self;
+    }
+
+    pub fn send_to(mut self, send_to: Recipient) -> NotificationBuilder {
+        self.notification.send_to = send_to;
+        return self;
+    }
+
+    pub fn subject(mut self, subject: Option<String>) -> NotificationBuilder {
+        self.notification.subject = subject;
+        return self;
+    }
+
+    pub fn timezone(mut self, timezone: String) -> NotificationBuilder {
+        self.notification.timezone = timezone;
+        return self;
+    }
+
+    pub fn cron(mut self, cron: Option<String>) -> NotificationBuilder {
+        self.notification.cron = cron;
+        return self;
+    }
+
+    pub fn tenant(mut self, tenant: String) -> NotificationBuilder {
+        self.notification.tenant = tenant;
         return self;
     }
 
@@ -125,6 +374,36 @@ impl NotificationBuilder {
         return self;
     }
 
+    pub fn buttons(mut self, buttons: Vec<Vec<Button>>) -> NotificationBuilder {
+        self.notification.buttons = buttons;
+        return self;
+    }
+
+    pub fn attachments(mut self, attachments: Vec<AttachmentRef>) -> NotificationBuilder {
+        self.notification.attachments = attachments;
+        return self;
+    }
+
+    pub fn disable_notification(mut self, disable: bool) -> NotificationBuilder {
+        self.notification.disable_notification = disable;
+        return self;
+    }
+
+    pub fn protect_content(mut self, protect: bool) -> NotificationBuilder {
+        self.notification.protect_content = protect;
+        return self;
+    }
+
+    pub fn link_preview(mut self, link_preview: Option<LinkPreview>) -> NotificationBuilder {
+        self.notification.link_preview = link_preview;
+        return self;
+    }
+
+    pub fn reply_to_message_id(mut self, message_id: Option<i32>) -> NotificationBuilder {
+        self.notification.reply_to_message_id = message_id;
+        return self;
+    }
+
     pub fn build(self) -> Notification {
         return self.notification;
     }
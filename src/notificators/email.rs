@@ -0,0 +1,334 @@
+use crate::{
+    notifications::Notification,
+    notificators::{Notificator, SendError, SendReceipt},
+    recipients::Recipient,
+};
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::{Mailbox, MultiPart},
+    transport::smtp::authentication::Credentials,
+};
+use std::env;
+use uuid::Uuid;
+
+const DEFAULT_SUBJECT: &str = "Notification";
+
+// How the connection to the SMTP server is secured
+#[derive(Debug, Clone, PartialEq)]
+pub enum SmtpSecurity {
+    // Plain connection, upgraded with STARTTLS (usually port 587)
+    StartTls,
+    // TLS from the first byte (usually port 465)
+    Tls,
+    // No encryption at all. Only for local SMTP sinks like mailpit
+    None,
+}
+
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: Option<u16>,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+}
+
+impl SmtpConfig {
+    // Returns None if SMTP_HOST is not set, which means
+    // email notifications are disabled
+    pub fn from_env() -> Option<Result<Self, String>> {
+        return Self::from_vars(|name| env::var(name).ok());
+    }
+
+    // Same as from_env, with variables looked up by `var`
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Option<Result<Self, String>> {
+        let host = var("SMTP_HOST")?;
+
+        let port = match var("SMTP_PORT") {
+            Some(p) => match p.trim().parse::<u16>() {
+                Ok(p) => Some(p),
+                Err(_) => return Some(Err(format!("invalid SMTP_PORT: \"{}\"", p))),
+            },
+            None => None,
+        };
+
+        let security = match var("SMTP_SECURITY") {
+            Some(s) => match s.trim().to_lowercase().as_str() {
+                "starttls" => SmtpSecurity::StartTls,
+                "tls" => SmtpSecurity::Tls,
+                "none" => SmtpSecurity::None,
+                _ => {
+                    return Some(Err(format!(
+                        "invalid SMTP_SECURITY: \"{}\". Expected \"starttls\", \"tls\" or \"none\"",
+                        s
+                    )));
+                }
+            },
+            None => SmtpSecurity::StartTls,
+        };
+
+        let from = match var("SMTP_FROM") {
+            Some(f) => f,
+            None => return Some(Err("SMTP_HOST is set, but SMTP_FROM is not".to_string())),
+        };
+
+        return Some(Ok(SmtpConfig {
+            host,
+            port,
+            security,
+            username: var("SMTP_USERNAME"),
+            password: var("SMTP_PASSWORD"),
+            from,
+        }));
+    }
+}
+
+pub struct EmailNotificator {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl EmailNotificator {
+    pub fn new(config: SmtpConfig) -> Result<Self, String> {
+        let from = config
+            .from
+            .parse::<Mailbox>()
+            .map_err(|e| format!("invalid SMTP_FROM address: {}", e))?;
+
+        let mut builder = match config.security {
+            SmtpSecurity::StartTls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+                    .map_err(|e| format!("failed to configure STARTTLS relay: {}", e))?
+            }
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)
+                .map_err(|e| format!("failed to configure TLS relay: {}", e))?,
+            SmtpSecurity::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host)
+            }
+        };
+
+        if let Some(port) = config.port {
+            builder = builder.port(port);
+        }
+
+        if let (Some(username), Some(password)) = (config.username, config.password) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        return Ok(Self {
+            transport: builder.build(),
+            from,
+        });
+    }
+
+    fn build_message(
+        &self,
+        message_id: String,
+        to: Mailbox,
+        subject: &str,
+        text: &str,
+    ) -> Result<Message, String> {
+        return Message::builder()
+            .message_id(Some(message_id))
+            .from(self.from.clone())
+            .to(to)
+            .subject(subject)
+            .multipart(MultiPart::alternative_plain_html(
+                text.to_string(),
+                text_to_html(text),
+            ))
+            .map_err(|e| format!("failed to build email message: {}", e));
+    }
+}
+
+impl Notificator for EmailNotificator {
+    async fn send(
+        &self,
+        notification: &Notification,
+        _attempt: u32,
+    ) -> Result<SendReceipt, SendError> {
+        let (address, name) = match &notification.send_to {
+            Recipient::Email { address, name } => (address, name),
+            other => return Err(format!("{} is not an email recipient", other).into()),
+        };
+
+        let parsed = address
+            .parse()
+            .map_err(|e| format!("invalid email address \"{}\": {}", address, e))?;
+        let to = Mailbox::new(name.clone(), parsed);
+
+        let subject = notification.subject.as_deref().unwrap_or(DEFAULT_SUBJECT);
+        // Own Message-ID, so it can be recorded as provider message id
+        let message_id = format!("<{}@{}>", Uuid::new_v4(), self.from.email.domain());
+        let message = self.build_message(message_id.clone(), to, subject, &notification.text)?;
+
+        // Rejections with 5xx codes won't change on retry
+        self.transport.send(message).await.map_err(|e| {
+            match e.is_permanent() || e.is_client() {
+                true => SendError::from(e.to_string()),
+                false => SendError::retryable(e.to_string()),
+            }
+        })?;
+
+        Ok(SendReceipt {
+            message_ids: vec![message_id],
+        })
+    }
+}
+
+// Minimal HTML alternative for plain-text notifications:
+// escapes markup and keeps line breaks
+fn text_to_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            '\n' => escaped.push_str("<br>\n"),
+            _ => escaped.push(c),
+        }
+    }
+
+    return format!(
+        "<!DOCTYPE html><html><body><p>{}</p></body></html>",
+        escaped
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::{NotificationBuilder, NotificationPlatform};
+    use std::collections::HashMap;
+
+    fn config(vars: &[(&str, &str)]) -> Option<Result<SmtpConfig, String>> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        return SmtpConfig::from_vars(|name| vars.get(name).cloned());
+    }
+
+    #[test]
+    fn disabled_without_host() {
+        assert!(config(&[("SMTP_FROM", "a@b.c")]).is_none());
+    }
+
+    #[test]
+    fn defaults_to_starttls_on_default_port() {
+        let config = config(&[("SMTP_HOST", "smtp.example.com"), ("SMTP_FROM", "a@b.c")])
+            .unwrap()
+            .unwrap();
+        assert_eq!(config.host, "smtp.example.com");
+        assert_eq!(config.port, None);
+        assert_eq!(config.security, SmtpSecurity::StartTls);
+        assert_eq!(config.username, None);
+        assert_eq!(config.password, None);
+    }
+
+    #[test]
+    fn parses_security_modes_and_port() {
+        for (value, security) in [
+            ("starttls", SmtpSecurity::StartTls),
+            ("TLS", SmtpSecurity::Tls),
+            (" none ", SmtpSecurity::None),
+        ] {
+            let config = config(&[
+                ("SMTP_HOST", "localhost"),
+                ("SMTP_PORT", "1025"),
+                ("SMTP_SECURITY", value),
+                ("SMTP_FROM", "a@b.c"),
+            ])
+            .unwrap()
+            .unwrap();
+            assert_eq!(config.security, security);
+            assert_eq!(config.port, Some(1025));
+        }
+    }
+
+    #[test]
+    fn keeps_credentials() {
+        let config = config(&[
+            ("SMTP_HOST", "localhost"),
+            ("SMTP_FROM", "a@b.c"),
+            ("SMTP_USERNAME", "user"),
+            ("SMTP_PASSWORD", "pass"),
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(config.username.as_deref(), Some("user"));
+        assert_eq!(config.password.as_deref(), Some("pass"));
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let missing_from = config(&[("SMTP_HOST", "localhost")]).unwrap();
+        assert!(missing_from.err().unwrap().contains("SMTP_FROM"));
+
+        let port = config(&[
+            ("SMTP_HOST", "localhost"),
+            ("SMTP_PORT", "70000"),
+            ("SMTP_FROM", "a@b.c"),
+        ]);
+        assert!(port.unwrap().err().unwrap().contains("SMTP_PORT"));
+
+        let security = config(&[
+            ("SMTP_HOST", "localhost"),
+            ("SMTP_SECURITY", "ssl"),
+            ("SMTP_FROM", "a@b.c"),
+        ]);
+        assert!(security.unwrap().err().unwrap().contains("SMTP_SECURITY"));
+    }
+
+    #[test]
+    fn rejects_invalid_from_address() {
+        let config = config(&[("SMTP_HOST", "localhost"), ("SMTP_FROM", "not an address")]);
+        assert!(EmailNotificator::new(config.unwrap().unwrap()).is_err());
+    }
+
+    // Needs mailpit from docker-compose: `docker compose up -d mailpit`,
+    // then `cargo test -- --ignored mailpit`
+    #[tokio::test]
+    #[ignore = "needs mailpit SMTP sink on localhost"]
+    async fn mailpit_receives_sent_email() {
+        let notificator = EmailNotificator::new(SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port: Some(1025),
+            security: SmtpSecurity::None,
+            username: None,
+            password: None,
+            from: "Notificator <notificator@example.com>".to_string(),
+        })
+        .unwrap();
+
+        let subject = format!("test {}", Uuid::new_v4());
+        let notification = NotificationBuilder::new()
+            .platform(NotificationPlatform::Email)
+            .send_to(Recipient::Email {
+                address: "user@example.com".to_string(),
+                name: None,
+            })
+            .subject(Some(subject.clone()))
+            .text("Hello <world>".to_string())
+            .build();
+
+        let receipt = notificator.send(&notification, 1).await.unwrap();
+        assert_eq!(receipt.message_ids.len(), 1);
+
+        let messages: serde_json::Value = reqwest::get("http://127.0.0.1:8025/api/v1/messages")
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let received = messages["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|m| m["Subject"] == subject.as_str());
+        assert!(received, "message \"{}\" not found in mailpit", subject);
+    }
+}
@@ -1,8 +1,104 @@
-use crate::notifications::Notification;
+use std::{fmt, sync::Arc, time::Duration};
 
+use crate::notifications::{Notification, NotificationPlatform};
+
+pub mod email;
+pub mod rate_limiter;
 pub mod telegram;
+pub mod webhook;
+pub use email::EmailNotificator;
 pub use telegram::TelegramNotificator;
+pub use webhook::WebhookNotificator;
+
+#[derive(Debug, Clone)]
+pub struct SendError {
+    pub message: String,
+    // Set when platform asked to wait before the next try
+    pub retry_after: Option<Duration>,
+    // Recipient can't be reached anymore, e.g. blocked the bot.
+    // Such sends deactivate the notification
+    pub permanent: bool,
+    // Only network errors, rate limits and platform failures can go away
+    // on retry. Bad requests and missing configuration can't
+    pub retryable: bool,
+    // Messages sent before the failure, e.g. first parts of split text
+    pub message_ids: Vec<String>,
+    // New id of telegram group, which was upgraded to supergroup.
+    // The message should be sent there instead
+    pub migrated_to: Option<i64>,
+}
+
+impl SendError {
+    pub fn retryable(message: String) -> Self {
+        return SendError {
+            retryable: true,
+            ..SendError::from(message)
+        };
+    }
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<String> for SendError {
+    fn from(message: String) -> Self {
+        SendError {
+            message,
+            retry_after: None,
+            permanent: false,
+            retryable: false,
+            message_ids: Vec::new(),
+            migrated_to: None,
+        }
+    }
+}
+
+// What platform returned for a successful send
+#[derive(Debug, Clone, Default)]
+pub struct SendReceipt {
+    // Ids of sent messages on the platform side, e.g. telegram message_id.
+    // Empty if platform doesn't have them
+    pub message_ids: Vec<String>,
+}
 
 pub trait Notificator {
-    async fn send(&self, notification: &Notification) -> Result<(), String>;
+    // attempt starts from 1 and grows with every retry of the same delivery
+    async fn send(
+        &self,
+        notification: &Notification,
+        attempt: u32,
+    ) -> Result<SendReceipt, SendError>;
+}
+
+// Set of configured notificators, one per platform.
+// Optional ones are None when not configured via env
+pub struct Notificators {
+    pub telegram: Arc<TelegramNotificator>,
+    pub email: Option<Arc<EmailNotificator>>,
+    pub webhook: Option<Arc<WebhookNotificator>>,
+}
+
+impl Notificators {
+    pub async fn send(
+        &self,
+        notification: &Notification,
+        attempt: u32,
+    ) -> Result<SendReceipt, SendError> {
+        return match notification.platform {
+            NotificationPlatform::Telegram => self.telegram.send(notification, attempt).await,
+            NotificationPlatform::Email => match &self.email {
+                Some(email) => email.send(notification, attempt).await,
+                None => Err("Email notifications are not configured".to_string().into()),
+            },
+            NotificationPlatform::Webhook => match &self.webhook {
+                Some(webhook) => webhook.send(notification, attempt).await,
+                None => Err("Webhook notifications are not configured"
+                    .to_string()
+                    .into()),
+            },
+        };
+    }
 }
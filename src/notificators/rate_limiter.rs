@@ -0,0 +1,272 @@
+use std::{
+    cmp,
+    collections::{HashMap, VecDeque},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+use tokio::time::{Instant, sleep};
+
+// Telegram limits of a single bot
+const GLOBAL_LIMIT: usize = 30;
+const GLOBAL_PERIOD: Duration = Duration::from_secs(1);
+const CHAT_LIMIT: usize = 1;
+const CHAT_PERIOD: Duration = Duration::from_secs(1);
+const GROUP_LIMIT: usize = 20;
+const GROUP_PERIOD: Duration = Duration::from_secs(60);
+
+// Idle chats are forgotten once there are more of them
+const MAX_TRACKED_CHATS: usize = 1000;
+
+// Sends made during the last period
+struct Window {
+    sends: VecDeque<Instant>,
+    limit: usize,
+    period: Duration,
+}
+
+impl Window {
+    fn new(limit: usize, period: Duration) -> Self {
+        return Window {
+            sends: VecDeque::with_capacity(limit),
+            limit,
+            period,
+        };
+    }
+
+    // Time left until the next send fits into the window
+    fn wait(&mut self, now: Instant) -> Duration {
+        while let Some(first) = self.sends.front() {
+            if now.duration_since(*first) < self.period {
+                break;
+            }
+            self.sends.pop_front();
+        }
+
+        if self.sends.len() < self.limit {
+            return Duration::ZERO;
+        }
+
+        return (self.sends[0] + self.period).duration_since(now);
+    }
+
+    fn record(&mut self, now: Instant) {
+        self.sends.push_back(now);
+    }
+
+    fn is_idle(&self, now: Instant) -> bool {
+        return self
+            .sends
+            .back()
+            .is_none_or(|last| now.duration_since(*last) >= self.period);
+    }
+}
+
+struct Chat {
+    per_second: Window,
+    // Groups and channels only
+    per_minute: Option<Window>,
+    // Set when telegram answered with retry_after
+    held_until: Option<Instant>,
+}
+
+impl Chat {
+    fn new(chat_id: i64) -> Self {
+        return Chat {
+            per_second: Window::new(CHAT_LIMIT, CHAT_PERIOD),
+            // Negative ids belong to groups and channels
+            per_minute: (chat_id < 0).then(|| Window::new(GROUP_LIMIT, GROUP_PERIOD)),
+            held_until: None,
+        };
+    }
+
+    fn wait(&mut self, now: Instant) -> Duration {
+        let mut wait = self.per_second.wait(now);
+        if let Some(per_minute) = &mut self.per_minute {
+            wait = cmp::max(wait, per_minute.wait(now));
+        }
+        if let Some(held_until) = self.held_until {
+            wait = cmp::max(wait, held_until.saturating_duration_since(now));
+        }
+        return wait;
+    }
+
+    fn record(&mut self, now: Instant) {
+        self.per_second.record(now);
+        if let Some(per_minute) = &mut self.per_minute {
+            per_minute.record(now);
+        }
+    }
+
+    fn is_idle(&self, now: Instant) -> bool {
+        return self.per_second.is_idle(now)
+            && self.per_minute.as_ref().is_none_or(|w| w.is_idle(now))
+            && self.held_until.is_none_or(|until| until <= now);
+    }
+}
+
+struct State {
+    global: Window,
+    chats: HashMap<i64, Chat>,
+}
+
+// Keeps sends of one bot within telegram limits: 30 messages per second
+// overall, 1 per second in a chat and 20 per minute in a group.
+// Sends over the limit wait in a queue
+pub struct RateLimiter {
+    state: Mutex<State>,
+    // Sends waiting for their turn
+    queued: AtomicUsize,
+}
+
+// Removes send from the queue, even if it was cancelled while waiting
+struct Queued<'a>(&'a AtomicUsize);
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        return RateLimiter {
+            state: Mutex::new(State {
+                global: Window::new(GLOBAL_LIMIT, GLOBAL_PERIOD),
+                chats: HashMap::new(),
+            }),
+            queued: AtomicUsize::new(0),
+        };
+    }
+
+    // Waits until a message can be sent to the chat and takes the slot
+    pub async fn acquire(&self, chat_id: i64) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        let _queued = Queued(&self.queued);
+
+        loop {
+            let wait = self.try_acquire(chat_id);
+            if wait.is_zero() {
+                return;
+            }
+            sleep(wait).await;
+        }
+    }
+
+    // Zero if the slot is taken, otherwise time to wait before the next try
+    fn try_acquire(&self, chat_id: i64) -> Duration {
+        let mut state = match self.state.lock() {
+            Ok(s) => s,
+            Err(e) => e.into_inner(),
+        };
+        let now = Instant::now();
+
+        if state.chats.len() > MAX_TRACKED_CHATS {
+            state.chats.retain(|_, chat| !chat.is_idle(now));
+        }
+
+        let global_wait = state.global.wait(now);
+        let chat = state
+            .chats
+            .entry(chat_id)
+            .or_insert_with(|| Chat::new(chat_id));
+        let wait = cmp::max(global_wait, chat.wait(now));
+        if !wait.is_zero() {
+            return wait;
+        }
+
+        chat.record(now);
+        state.global.record(now);
+        return Duration::ZERO;
+    }
+
+    // Holds sends to the chat, when telegram asked to retry later
+    pub fn hold(&self, chat_id: i64, duration: Duration) {
+        let mut state = match self.state.lock() {
+            Ok(s) => s,
+            Err(e) => e.into_inner(),
+        };
+
+        let until = Instant::now() + duration;
+        let chat = state
+            .chats
+            .entry(chat_id)
+            .or_insert_with(|| Chat::new(chat_id));
+        chat.held_until = Some(chat.held_until.map_or(until, |held| cmp::max(held, until)));
+    }
+
+    pub fn queued(&self) -> usize {
+        return self.queued.load(Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    // Virtual time passed while acquiring
+    async fn acquire_time(limiter: &RateLimiter, chat_id: i64) -> Duration {
+        let start = Instant::now();
+        limiter.acquire(chat_id).await;
+        return start.elapsed();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn one_message_per_second_in_chat() {
+        let limiter = RateLimiter::new();
+        assert_eq!(acquire_time(&limiter, 1).await, Duration::ZERO);
+        assert_eq!(acquire_time(&limiter, 1).await, CHAT_PERIOD);
+        // Other chats aren't held by it
+        assert_eq!(acquire_time(&limiter, 2).await, Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn twenty_messages_per_minute_in_group() {
+        let limiter = RateLimiter::new();
+        let start = Instant::now();
+        for _ in 0..GROUP_LIMIT {
+            limiter.acquire(-100).await;
+        }
+        assert_eq!(start.elapsed(), CHAT_PERIOD * (GROUP_LIMIT as u32 - 1));
+
+        limiter.acquire(-100).await;
+        assert_eq!(start.elapsed(), GROUP_PERIOD);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn thirty_messages_per_second_overall() {
+        let limiter = RateLimiter::new();
+        for chat_id in 0..GLOBAL_LIMIT as i64 {
+            assert_eq!(acquire_time(&limiter, chat_id).await, Duration::ZERO);
+        }
+        assert_eq!(acquire_time(&limiter, 1000).await, GLOBAL_PERIOD);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn held_chat_waits_for_retry_after() {
+        let limiter = RateLimiter::new();
+        limiter.hold(1, Duration::from_secs(5));
+        // Shorter hold doesn't cut the longer one
+        limiter.hold(1, Duration::from_secs(2));
+        assert_eq!(acquire_time(&limiter, 1).await, Duration::from_secs(5));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waiting_sends_are_counted() {
+        let limiter = Arc::new(RateLimiter::new());
+        limiter.acquire(1).await;
+
+        let waiting = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire(1).await })
+        };
+        tokio::task::yield_now().await;
+        assert_eq!(limiter.queued(), 1);
+
+        waiting.await.unwrap();
+        assert_eq!(limiter.queued(), 0);
+    }
+}
@@ -1,32 +1,861 @@
-use crate::{notifications::Notification, notificators::Notificator};
-use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use teloxide::prelude::*;
+use crate::{
+    attachments::MAX_CAPTION_LENGTH,
+    bot,
+    dispatcher::{DeliveryOutcome, DeliveryRecord},
+    formatting,
+    notifications::{
+        AttachmentKind, AttachmentSource, Button, LinkPreview, MessageFormat, Notification,
+    },
+    notificators::{Notificator, SendError, SendReceipt, rate_limiter::RateLimiter},
+    recipients::Recipient,
+    storage::NotificationStore,
+};
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use teloxide::{
+    ApiError, RequestError,
+    prelude::*,
+    requests::{Output, Request},
+    types::{
+        InlineKeyboardButton, InlineKeyboardMarkup, InputFile, InputMedia, InputMediaDocument,
+        InputMediaPhoto, LinkPreviewOptions, MessageId, ParseMode, ReplyParameters, ThreadId,
+    },
+};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct ContactData {
-    pub user_id: i64,
-}
+// Telegram limit of message text, after entities are parsed
+const MAX_MESSAGE_LENGTH: usize = 4096;
+// Requests answered with retry_after are repeated in place that many times,
+// so parts of split text already sent aren't sent again by the dispatcher
+const MAX_RETRY_AFTER_REPEATS: u32 = 3;
+// Longer waits are left to the dispatcher retry policy
+const MAX_RETRY_AFTER_WAIT: Duration = Duration::from_secs(10);
+// Deliveries searched for the previous attempt. Topic subscribers add theirs in between
+const PREVIOUS_ATTEMPT_LOOKUP: usize = 100;
+const SERVER_FAILURES: [&str; 4] = [
+    "Internal Server Error",
+    "Bad Gateway",
+    "Service Unavailable",
+    "Gateway Timeout",
+];
 
 pub struct TelegramNotificator {
-    bot: Arc<Bot>,
+    bot: Arc<LimitedBot>,
+    // Own bots of tenants. Tenants without one use the server bot
+    tenant_bots: RwLock<HashMap<String, Arc<LimitedBot>>>,
+    // Uploaded attachments are read from here
+    storage: Arc<dyn NotificationStore>,
+}
+
+// Bot along with limits of it's sends, telegram counts them per bot
+struct LimitedBot {
+    bot: Bot,
+    limiter: RateLimiter,
+}
+
+impl LimitedBot {
+    fn new(token: String) -> Self {
+        return LimitedBot {
+            bot: Bot::new(token),
+            limiter: RateLimiter::new(),
+        };
+    }
+
+    // Sends request to the chat once the limiter lets it through
+    async fn call<R>(&self, chat_id: ChatId, request: R) -> Result<Output<R>, RequestError>
+    where
+        R: Request<Err = RequestError>,
+    {
+        let mut repeats = 0;
+
+        loop {
+            self.limiter.acquire(chat_id.0).await;
+
+            match request.send_ref().await {
+                Err(RequestError::RetryAfter(seconds)) => {
+                    tracing::warn!(
+                        "telegram asked to wait {:?} before sending to chat {}",
+                        seconds.duration(),
+                        chat_id
+                    );
+                    self.limiter.hold(chat_id.0, seconds.duration());
+
+                    if repeats >= MAX_RETRY_AFTER_REPEATS
+                        || seconds.duration() > MAX_RETRY_AFTER_WAIT
+                    {
+                        return Err(RequestError::RetryAfter(seconds));
+                    }
+                    repeats += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Deref for LimitedBot {
+    type Target = Bot;
+
+    fn deref(&self) -> &Bot {
+        return &self.bot;
+    }
+}
+
+// Chat and forum topic message is sent to
+struct Target {
+    chat_id: ChatId,
+    thread_id: Option<ThreadId>,
 }
 
 impl TelegramNotificator {
-    pub fn new(token: String) -> Self {
-        let bot = Bot::new(token);
-        Self { bot: Arc::new(bot) }
+    pub fn new(token: String, storage: Arc<dyn NotificationStore>) -> Self {
+        Self {
+            bot: Arc::new(LimitedBot::new(token)),
+            tenant_bots: RwLock::new(HashMap::new()),
+            storage,
+        }
+    }
+
+    // Sets bot of the tenant, None switches it back to the server bot
+    pub fn set_tenant_token(&self, tenant: &str, token: Option<String>) {
+        let mut bots = match self.tenant_bots.write() {
+            Ok(b) => b,
+            Err(e) => e.into_inner(),
+        };
+
+        match token {
+            Some(token) => bots.insert(tenant.to_string(), Arc::new(LimitedBot::new(token))),
+            None => bots.remove(tenant),
+        };
+    }
+
+    // Other tenant or the server already sends with the token
+    pub fn token_taken(&self, tenant: &str, token: &str) -> bool {
+        let bots = match self.tenant_bots.read() {
+            Ok(b) => b,
+            Err(e) => e.into_inner(),
+        };
+
+        return self.bot.token() == token
+            || bots
+                .iter()
+                .any(|(t, bot)| t != tenant && bot.token() == token);
+    }
+
+    // Sends waiting for rate limits of the bot, the tenant sends with
+    pub fn queued(&self, tenant: &str) -> usize {
+        return self.bot_for(tenant).limiter.queued();
+    }
+
+    fn bot_for(&self, tenant: &str) -> Arc<LimitedBot> {
+        let bots = match self.tenant_bots.read() {
+            Ok(b) => b,
+            Err(e) => e.into_inner(),
+        };
+
+        return bots.get(tenant).unwrap_or(&self.bot).clone();
+    }
+
+    // Checks new text fits the messages sent by the delivery. Text sent
+    // apart from attachments is split the same way as on send, so it can't
+    // take more messages than were sent. Text in caption of the attachments
+    // must still fit the caption
+    pub fn check_edit(
+        delivery: &DeliveryRecord,
+        notification: &Notification,
+    ) -> Result<(), String> {
+        let (attachment_ids, text_ids) = delivery_layout(delivery);
+
+        if text_ids == 0 && attachment_ids > 0 {
+            let length = formatting::visible_length(&notification.format, &notification.text);
+            if length > MAX_CAPTION_LENGTH {
+                return Err(format!(
+                    "text is in caption of the attachments and can't be longer than {} characters",
+                    MAX_CAPTION_LENGTH
+                ));
+            }
+            return Ok(());
+        }
+
+        let parts = formatting::split(&notification.format, &notification.text, MAX_MESSAGE_LENGTH);
+        if parts.is_empty() {
+            return Err("message text is empty".to_string());
+        }
+        if parts.len() > text_ids {
+            return Err(format!(
+                "text takes {} messages, but only {} were sent",
+                parts.len(),
+                text_ids
+            ));
+        }
+
+        return Ok(());
+    }
+
+    // Replaces text of the sent messages, see check_edit. Text messages left
+    // over are deleted. Buttons are attached the same way as on send
+    pub async fn edit(
+        &self,
+        delivery: &DeliveryRecord,
+        notification: &Notification,
+    ) -> Result<(), SendError> {
+        Self::check_edit(delivery, notification)?;
+        let chat_id = delivery_chat(delivery)?;
+        let message_ids = delivery_message_ids(delivery)?;
+        let bot = self.bot_for(&delivery.tenant);
+        let (attachment_ids, _) = delivery_layout(delivery);
+        let (attachment_ids, text_ids) = message_ids.split_at(attachment_ids);
+
+        // Album shows caption of the first item, only single attachment has buttons
+        if text_ids.is_empty() {
+            let mut request = bot
+                .edit_message_caption(chat_id, attachment_ids[0])
+                .caption(notification.text.clone());
+            if let Some(parse_mode) = parse_mode(&notification.format) {
+                request = request.parse_mode(parse_mode);
+            }
+            if attachment_ids.len() == 1
+                && let Some(buttons) = keyboard(notification)?
+            {
+                request = request.reply_markup(buttons);
+            }
+
+            return match bot.call(chat_id, request).await {
+                Ok(_) | Err(RequestError::Api(ApiError::MessageNotModified)) => Ok(()),
+                Err(e) => Err(to_send_error(e)),
+            };
+        }
+
+        let parts = formatting::split(&notification.format, &notification.text, MAX_MESSAGE_LENGTH);
+        let last = parts.len() - 1;
+
+        for (i, part) in parts.into_iter().enumerate() {
+            let mut request = bot.edit_message_text(chat_id, text_ids[i], part);
+            if let Some(link_preview) = &notification.link_preview {
+                request = request.link_preview_options(link_preview_options(link_preview));
+            }
+            if let Some(parse_mode) = parse_mode(&notification.format) {
+                request = request.parse_mode(parse_mode);
+            }
+            if i == last
+                && let Some(buttons) = keyboard(notification)?
+            {
+                request = request.reply_markup(buttons);
+            }
+
+            match bot.call(chat_id, request).await {
+                Ok(_) | Err(RequestError::Api(ApiError::MessageNotModified)) => {}
+                Err(e) => return Err(to_send_error(e)),
+            };
+        }
+
+        for message_id in &text_ids[last + 1..] {
+            delete_message(&bot, chat_id, *message_id).await?;
+        }
+
+        return Ok(());
+    }
+
+    // Deletes all sent messages. Already deleted ones are skipped
+    pub async fn delete(&self, delivery: &DeliveryRecord) -> Result<(), SendError> {
+        let chat_id = delivery_chat(delivery)?;
+        let message_ids = delivery_message_ids(delivery)?;
+        let bot = self.bot_for(&delivery.tenant);
+
+        for message_id in message_ids {
+            delete_message(&bot, chat_id, message_id).await?;
+        }
+
+        return Ok(());
+    }
+
+    // Text goes into caption of the attachments if it fits. Otherwise it's
+    // sent as a separate message right after them, along with the buttons
+    async fn send_attachments(
+        &self,
+        bot: &LimitedBot,
+        target: &Target,
+        notification: &Notification,
+        mut sent: Vec<String>,
+    ) -> Result<SendReceipt, SendError> {
+        let text_apart = formatting::visible_length(&notification.format, &notification.text)
+            > MAX_CAPTION_LENGTH;
+
+        // Previous attempt got to the text, attachments aren't sent again
+        if text_apart && sent.len() >= notification.attachments.len() {
+            let text_sent = sent.split_off(notification.attachments.len());
+            let mut message_ids = sent;
+            return match send_text(bot, target, notification, false, text_sent).await {
+                Ok(ids) => {
+                    message_ids.extend(ids);
+                    Ok(SendReceipt { message_ids })
+                }
+                Err(mut e) => {
+                    message_ids.append(&mut e.message_ids);
+                    e.message_ids = message_ids;
+                    Err(e)
+                }
+            };
+        }
+
+        let caption = match text_apart || notification.text.is_empty() {
+            true => None,
+            false => Some(notification.text.clone()),
+        };
+        let parse_mode = parse_mode(&notification.format);
+
+        let mut files = Vec::new();
+        for attachment in &notification.attachments {
+            files.push(
+                self.input_file(&notification.tenant, &attachment.source)
+                    .await?,
+            );
+        }
+
+        let mut message_ids = Vec::new();
+
+        if files.len() == 1 {
+            let file = files.remove(0);
+            let buttons = match text_apart {
+                true => None,
+                false => keyboard(notification)?,
+            };
+
+            let message = match notification.attachments[0].kind {
+                AttachmentKind::Photo => {
+                    let mut request = bot
+                        .send_photo(target.chat_id, file)
+                        .disable_notification(notification.disable_notification)
+                        .protect_content(notification.protect_content);
+                    if let Some(caption) = caption {
+                        request = request.caption(caption);
+                    }
+                    if let Some(parse_mode) = parse_mode {
+                        request = request.parse_mode(parse_mode);
+                    }
+                    if let Some(thread_id) = target.thread_id {
+                        request = request.message_thread_id(thread_id);
+                    }
+                    if let Some(reply) = reply_parameters(notification) {
+                        request = request.reply_parameters(reply);
+                    }
+                    if let Some(buttons) = buttons {
+                        request = request.reply_markup(buttons);
+                    }
+                    bot.call(target.chat_id, request).await
+                }
+                AttachmentKind::Document => {
+                    let mut request = bot
+                        .send_document(target.chat_id, file)
+                        .disable_notification(notification.disable_notification)
+                        .protect_content(notification.protect_content);
+                    if let Some(caption) = caption {
+                        request = request.caption(caption);
+                    }
+                    if let Some(parse_mode) = parse_mode {
+                        request = request.parse_mode(parse_mode);
+                    }
+                    if let Some(thread_id) = target.thread_id {
+                        request = request.message_thread_id(thread_id);
+                    }
+                    if let Some(reply) = reply_parameters(notification) {
+                        request = request.reply_parameters(reply);
+                    }
+                    if let Some(buttons) = buttons {
+                        request = request.reply_markup(buttons);
+                    }
+                    bot.call(target.chat_id, request).await
+                }
+            }
+            .map_err(to_send_error)?;
+
+            message_ids.push(message.id.0.to_string());
+        } else {
+            let mut media = Vec::new();
+            for (i, (file, attachment)) in
+                files.into_iter().zip(&notification.attachments).enumerate()
+            {
+                // Album shows caption of the first item
+                let caption = caption.clone().filter(|_| i == 0);
+                media.push(match attachment.kind {
+                    AttachmentKind::Photo => {
+                        let mut item = InputMediaPhoto::new(file);
+                        if let Some(caption) = caption {
+                            item = item.caption(caption);
+                        }
+                        if let Some(parse_mode) = parse_mode {
+                            item = item.parse_mode(parse_mode);
+                        }
+                        InputMedia::Photo(item)
+                    }
+                    AttachmentKind::Document => {
+                        let mut item = InputMediaDocument::new(file);
+                        if let Some(caption) = caption {
+                            item = item.caption(caption);
+                        }
+                        if let Some(parse_mode) = parse_mode {
+                            item = item.parse_mode(parse_mode);
+                        }
+                        InputMedia::Document(item)
+                    }
+                });
+            }
+
+            let mut request = bot
+                .send_media_group(target.chat_id, media)
+                .disable_notification(notification.disable_notification)
+                .protect_content(notification.protect_content);
+            if let Some(thread_id) = target.thread_id {
+                request = request.message_thread_id(thread_id);
+            }
+            if let Some(reply) = reply_parameters(notification) {
+                request = request.reply_parameters(reply);
+            }
+
+            let messages = bot
+                .call(target.chat_id, request)
+                .await
+                .map_err(to_send_error)?;
+            message_ids.extend(messages.iter().map(|m| m.id.0.to_string()));
+        }
+
+        if text_apart {
+            // Attachments already reply to the message
+            match send_text(bot, target, notification, false, Vec::new()).await {
+                Ok(ids) => message_ids.extend(ids),
+                Err(mut e) => {
+                    message_ids.append(&mut e.message_ids);
+                    e.message_ids = message_ids;
+                    return Err(e);
+                }
+            };
+        }
+
+        return Ok(SendReceipt { message_ids });
+    }
+
+    async fn input_file(
+        &self,
+        tenant: &str,
+        source: &AttachmentSource,
+    ) -> Result<InputFile, SendError> {
+        return match source {
+            AttachmentSource::Upload { attachment_id } => {
+                let attachment = match self.storage.get_attachment(tenant, attachment_id).await? {
+                    Some(a) => a,
+                    None => return Err(format!("Attachment {} not found", attachment_id).into()),
+                };
+                let data = match self
+                    .storage
+                    .get_attachment_data(tenant, attachment_id)
+                    .await?
+                {
+                    Some(d) => d,
+                    None => return Err(format!("Attachment {} not found", attachment_id).into()),
+                };
+
+                Ok(InputFile::memory(data).file_name(attachment.file_name))
+            }
+            AttachmentSource::Url { url } => match reqwest::Url::parse(url) {
+                Ok(url) => Ok(InputFile::url(url)),
+                Err(e) => Err(format!("invalid attachment url \"{}\": {}", url, e).into()),
+            },
+            AttachmentSource::FileId { file_id } => Ok(InputFile::file_id(file_id.clone())),
+        };
     }
 }
 
 impl Notificator for TelegramNotificator {
-    async fn send(&self, notification: &Notification) -> Result<(), String> {
-        let chat_id = notification.send_to.user_id;
-        self.bot
-            .send_message(ChatId(chat_id), &notification.text)
+    async fn send(
+        &self,
+        notification: &Notification,
+        attempt: u32,
+    ) -> Result<SendReceipt, SendError> {
+        let (chat_id, thread_id) = match &notification.send_to {
+            Recipient::Telegram { chat_id, thread_id } => (*chat_id, *thread_id),
+            other => return Err(format!("{} is not a telegram recipient", other).into()),
+        };
+
+        let bot = self.bot_for(&notification.tenant);
+        let target = Target {
+            chat_id: ChatId(chat_id),
+            thread_id: thread_id.map(|t| ThreadId(MessageId(t))),
+        };
+
+        let sent = self.sent_before(notification, attempt).await;
+
+        if !notification.attachments.is_empty() {
+            return self
+                .send_attachments(&bot, &target, notification, sent)
+                .await;
+        }
+
+        let message_ids = send_text(&bot, &target, notification, true, sent).await?;
+
+        Ok(SendReceipt { message_ids })
+    }
+}
+
+impl TelegramNotificator {
+    // Messages sent by the previous attempt before it failed
+    async fn sent_before(&self, notification: &Notification, attempt: u32) -> Vec<String> {
+        if attempt <= 1 {
+            return Vec::new();
+        }
+
+        return match self
+            .storage
+            .get_deliveries(
+                &notification.tenant,
+                &notification.uuid,
+                0,
+                PREVIOUS_ATTEMPT_LOOKUP,
+            )
             .await
-            .map_err(|e| e.to_string())?;
+        {
+            Ok((deliveries, _)) => previous_attempt_messages(&deliveries, notification, attempt),
+            Err(e) => {
+                tracing::warn!(
+                    "failed to get deliveries of notification {}, sending all parts again: {}",
+                    notification.uuid,
+                    e
+                );
+                Vec::new()
+            }
+        };
+    }
+}
+
+// Deliveries are newest first. Subscribers of a topic share notification
+// id, so the attempt is told by recipient as well
+fn previous_attempt_messages(
+    deliveries: &[DeliveryRecord],
+    notification: &Notification,
+    attempt: u32,
+) -> Vec<String> {
+    return deliveries
+        .iter()
+        .find(|d| d.attempt + 1 == attempt && d.recipient == notification.send_to)
+        .filter(|d| d.outcome == DeliveryOutcome::Failed)
+        .map(|d| d.provider_message_ids.clone())
+        .unwrap_or_default();
+}
+
+// Text over telegram limit is sent as several messages, buttons are
+// attached to the last one. If one of them fails, error keeps ids of the parts
+// already sent, so they are recorded. Retry passes them as `sent`, and only
+// the rest is sent. Returns ids of all parts
+async fn send_text(
+    bot: &LimitedBot,
+    target: &Target,
+    notification: &Notification,
+    reply: bool,
+    sent: Vec<String>,
+) -> Result<Vec<String>, SendError> {
+    let parts = formatting::split(&notification.format, &notification.text, MAX_MESSAGE_LENGTH);
+    if parts.is_empty() {
+        return Err("message text is empty".to_string().into());
+    }
+
+    let last = parts.len() - 1;
+    let skipped = sent.len();
+    let mut message_ids = sent;
+
+    for (i, part) in parts.into_iter().enumerate().skip(skipped) {
+        let mut request = bot
+            .send_message(target.chat_id, part)
+            .disable_notification(notification.disable_notification)
+            .protect_content(notification.protect_content);
+        if let Some(thread_id) = target.thread_id {
+            request = request.message_thread_id(thread_id);
+        }
+
+        if let Some(link_preview) = &notification.link_preview {
+            request = request.link_preview_options(link_preview_options(link_preview));
+        }
+
+        // Only the first part of split text is the reply
+        if reply
+            && i == 0
+            && let Some(reply) = reply_parameters(notification)
+        {
+            request = request.reply_parameters(reply);
+        }
+
+        if let Some(parse_mode) = parse_mode(&notification.format) {
+            request = request.parse_mode(parse_mode);
+        }
+
+        if i == last
+            && let Some(buttons) = keyboard(notification)?
+        {
+            request = request.reply_markup(buttons);
+        }
+
+        match bot.call(target.chat_id, request).await {
+            Ok(message) => message_ids.push(message.id.0.to_string()),
+            Err(e) => {
+                return Err(SendError {
+                    message_ids,
+                    ..to_send_error(e)
+                });
+            }
+        };
+    }
+
+    return Ok(message_ids);
+}
+
+async fn delete_message(
+    bot: &LimitedBot,
+    chat_id: ChatId,
+    message_id: MessageId,
+) -> Result<(), SendError> {
+    return match bot
+        .call(chat_id, bot.delete_message(chat_id, message_id))
+        .await
+    {
+        Ok(_) | Err(RequestError::Api(ApiError::MessageToDeleteNotFound)) => Ok(()),
+        Err(e) => Err(to_send_error(e)),
+    };
+}
+
+fn delivery_chat(delivery: &DeliveryRecord) -> Result<ChatId, SendError> {
+    return match &delivery.recipient {
+        Recipient::Telegram { chat_id, .. } => Ok(ChatId(*chat_id)),
+        other => Err(format!("{} is not a telegram recipient", other).into()),
+    };
+}
+
+// Number of sent messages with attachments and with text apart from them
+fn delivery_layout(delivery: &DeliveryRecord) -> (usize, usize) {
+    let sent = delivery.provider_message_ids.len();
+    let attachments = delivery.attachment_messages.min(sent);
+    return (attachments, sent - attachments);
+}
+
+fn delivery_message_ids(delivery: &DeliveryRecord) -> Result<Vec<MessageId>, SendError> {
+    let mut ids = Vec::new();
+    for id in &delivery.provider_message_ids {
+        match id.parse::<i32>() {
+            Ok(id) => ids.push(MessageId(id)),
+            Err(_) => return Err(format!("invalid telegram message id {}", id).into()),
+        }
+    }
+
+    if ids.is_empty() {
+        return Err("delivery has no sent messages".to_string().into());
+    }
+
+    return Ok(ids);
+}
+
+fn link_preview_options(link_preview: &LinkPreview) -> LinkPreviewOptions {
+    return LinkPreviewOptions {
+        is_disabled: link_preview.disabled,
+        url: link_preview.url.clone(),
+        prefer_small_media: link_preview.prefer_small_media,
+        prefer_large_media: link_preview.prefer_large_media,
+        show_above_text: link_preview.show_above_text,
+    };
+}
+
+fn reply_parameters(notification: &Notification) -> Option<ReplyParameters> {
+    return notification
+        .reply_to_message_id
+        .map(|id| ReplyParameters::new(MessageId(id)).allow_sending_without_reply());
+}
+
+fn parse_mode(format: &MessageFormat) -> Option<ParseMode> {
+    return match format {
+        MessageFormat::Plain => None,
+        MessageFormat::MarkdownV2 => Some(ParseMode::MarkdownV2),
+        MessageFormat::Html => Some(ParseMode::Html),
+    };
+}
+
+// None if notification has no buttons
+fn keyboard(notification: &Notification) -> Result<Option<InlineKeyboardMarkup>, SendError> {
+    if notification.buttons.is_empty() {
+        return Ok(None);
+    }
+
+    let mut rows = Vec::new();
+
+    for row in &notification.buttons {
+        let mut buttons = Vec::new();
+        for button in row {
+            buttons.push(match button {
+                Button::Url { text, url } => match reqwest::Url::parse(url) {
+                    Ok(url) => InlineKeyboardButton::url(text, url),
+                    Err(e) => return Err(format!("invalid button url \"{}\": {}", url, e).into()),
+                },
+                Button::Callback {
+                    text,
+                    action,
+                    snooze_minutes,
+                } => InlineKeyboardButton::callback(
+                    text,
+                    bot::callback_data(&notification.uuid, action, snooze_minutes.unwrap_or(0)),
+                ),
+            });
+        }
+        rows.push(buttons);
+    }
+
+    return Ok(Some(InlineKeyboardMarkup::new(rows)));
+}
+
+// Errors saying the chat is gone or closed for the bot are permanent.
+// Network errors, flood limits and telegram failures are worth retrying
+fn to_send_error(e: RequestError) -> SendError {
+    let retry_after = match &e {
+        RequestError::RetryAfter(seconds) => Some(seconds.duration()),
+        _ => None,
+    };
+
+    let permanent = matches!(
+        &e,
+        RequestError::Api(
+            ApiError::BotBlocked
+                | ApiError::BotKicked
+                | ApiError::BotKickedFromSupergroup
+                | ApiError::BotKickedFromChannel
+                | ApiError::ChatNotFound
+                | ApiError::UserNotFound
+                | ApiError::UserDeactivated
+                | ApiError::GroupDeactivated
+                | ApiError::CantInitiateConversation
+                | ApiError::CantTalkWithBots
+                | ApiError::NotEnoughRightsToPostMessages
+        )
+    );
+
+    // Other api errors are answers to the request itself, it fails the same way again
+    let retryable = match &e {
+        RequestError::Network(_) | RequestError::RetryAfter(_) => true,
+        RequestError::Api(ApiError::Unknown(description)) => is_server_failure(description),
+        // Failing gateway in front of telegram answers with html page
+        RequestError::InvalidJson { raw, .. } => is_server_failure(raw),
+        _ => false,
+    };
+
+    let (message, migrated_to) = match &e {
+        RequestError::MigrateToChatId(id) => (
+            format!("group was upgraded to supergroup, new chat id is {}", id),
+            Some(id.0),
+        ),
+        _ => (e.to_string(), None),
+    };
+
+    return SendError {
+        message,
+        retry_after,
+        permanent,
+        retryable,
+        message_ids: Vec::new(),
+        migrated_to,
+    };
+}
+
+// Telegram describes 5xx failures with the status text
+fn is_server_failure(text: &str) -> bool {
+    return SERVER_FAILURES.iter().any(|f| text.contains(f));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::{NotificationBuilder, NotificationPlatform};
+    use teloxide::types::Seconds;
+
+    #[test]
+    fn only_network_rate_limit_and_server_failures_are_retried() {
+        let retryable = |e: RequestError| to_send_error(e).retryable;
+
+        assert!(retryable(RequestError::RetryAfter(Seconds::from_seconds(
+            3
+        ))));
+        assert!(retryable(RequestError::Api(ApiError::Unknown(
+            "Internal Server Error".to_string()
+        ))));
+        assert!(retryable(RequestError::Api(ApiError::Unknown(
+            "Bad Gateway".to_string()
+        ))));
+
+        // Unknown errors about the request itself fail the same way again
+        assert!(!retryable(RequestError::Api(ApiError::Unknown(
+            "Bad Request: message thread not found".to_string()
+        ))));
+        assert!(!retryable(RequestError::Api(ApiError::MessageTextIsEmpty)));
+        assert!(!retryable(RequestError::Api(ApiError::BotBlocked)));
+    }
+
+    #[test]
+    fn migrated_group_is_not_unreachable() {
+        let error = to_send_error(RequestError::MigrateToChatId(ChatId(-1001234567890)));
+        assert!(!error.permanent);
+        assert_eq!(error.migrated_to, Some(-1001234567890));
+    }
+
+    fn delivery(
+        chat_id: i64,
+        attempt: u32,
+        outcome: DeliveryOutcome,
+        ids: &[&str],
+    ) -> DeliveryRecord {
+        return DeliveryRecord {
+            id: attempt.to_string(),
+            tenant: "default".to_string(),
+            notification_id: "n".to_string(),
+            attempt,
+            platform: NotificationPlatform::Telegram,
+            recipient: Recipient::Telegram {
+                chat_id,
+                thread_id: None,
+            },
+            outcome,
+            error: None,
+            provider_message_ids: ids.iter().map(|id| id.to_string()).collect(),
+            attempted_at: String::new(),
+            actions: Vec::new(),
+            attachment_messages: 0,
+            buttons: Vec::new(),
+        };
+    }
+
+    #[test]
+    fn retry_skips_parts_sent_before_failure() {
+        let notification = NotificationBuilder::new()
+            .platform(NotificationPlatform::Telegram)
+            .send_to(Recipient::Telegram {
+                chat_id: 42,
+                thread_id: None,
+            })
+            .text("a".repeat(MAX_MESSAGE_LENGTH * 2 + 10))
+            .build();
+        let parts = formatting::split(&notification.format, &notification.text, MAX_MESSAGE_LENGTH);
+        assert_eq!(parts.len(), 3);
+
+        // First attempt failed on the third part, another subscriber
+        // of the topic failed on the second one
+        let deliveries = vec![
+            delivery(7, 1, DeliveryOutcome::Failed, &["99"]),
+            delivery(42, 1, DeliveryOutcome::Failed, &["10", "11"]),
+        ];
+        let sent = previous_attempt_messages(&deliveries, &notification, 2);
+        assert_eq!(sent, vec!["10", "11"]);
+        assert_eq!(parts.len() - sent.len(), 1);
+
+        // First attempt sends everything, and there was no second one
+        assert!(previous_attempt_messages(&deliveries, &notification, 1).is_empty());
+        assert!(previous_attempt_messages(&deliveries, &notification, 3).is_empty());
 
-        Ok(())
+        // Sent record is not a partial failure
+        let deliveries = vec![delivery(42, 1, DeliveryOutcome::Sent, &["10", "11", "12"])];
+        assert!(previous_attempt_messages(&deliveries, &notification, 2).is_empty());
     }
 }
@@ -0,0 +1,453 @@
+use crate::{
+    notifications::{Notification, NotificationKind},
+    notificators::{Notificator, SendError, SendReceipt},
+    recipients::Recipient,
+    storage::NotificationStore,
+    tenants::DEFAULT_TENANT,
+};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use std::{
+    env,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+    time::Duration,
+};
+
+pub const SIGNATURE_HEADER: &str = "X-Notificator-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Notificator-Timestamp";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_REDIRECTS: usize = 10;
+
+// Body of every webhook request
+#[derive(Serialize)]
+pub struct WebhookEnvelope<'a> {
+    pub uuid: &'a str,
+    pub text: &'a str,
+    pub kind: &'a NotificationKind,
+    pub attempt: u32,
+    pub timestamp: i64, // Unix seconds, same as in TIMESTAMP_HEADER
+}
+
+pub struct WebhookNotificator {
+    client: reqwest::Client,
+    // Secret of the default tenant, others have own
+    secret: String,
+    allow_private: bool,
+    storage: Arc<dyn NotificationStore>,
+}
+
+impl WebhookNotificator {
+    // Returns None if WEBHOOK_SECRET is not set, which means
+    // webhook notifications are disabled
+    pub fn from_env(storage: Arc<dyn NotificationStore>) -> Option<Result<Self, String>> {
+        let secret = env::var("WEBHOOK_SECRET").ok()?;
+        return Some(Self::new(secret, private_targets_allowed(), storage));
+    }
+
+    pub fn new(
+        secret: String,
+        allow_private: bool,
+        storage: Arc<dyn NotificationStore>,
+    ) -> Result<Self, String> {
+        if secret.is_empty() {
+            return Err("webhook secret can't be empty".to_string());
+        }
+
+        let mut builder = reqwest::Client::builder().timeout(REQUEST_TIMEOUT);
+        if !allow_private {
+            // Hostnames are checked after resolving, and every redirect
+            // target again, so neither can lead to an internal address
+            builder = builder.dns_resolver(Arc::new(PublicResolver)).redirect(
+                reqwest::redirect::Policy::custom(|attempt| {
+                    if attempt.previous().len() >= MAX_REDIRECTS {
+                        return attempt.error("too many redirects");
+                    }
+                    return match check_target(attempt.url(), false) {
+                        Ok(()) => attempt.follow(),
+                        Err(e) => attempt.error(e),
+                    };
+                }),
+            );
+        }
+        let client = builder
+            .build()
+            .map_err(|e| format!("failed to build http client: {}", e))?;
+
+        return Ok(Self {
+            client,
+            secret,
+            allow_private,
+            storage,
+        });
+    }
+
+    async fn secret_of(&self, tenant: &str) -> Result<String, SendError> {
+        if tenant == DEFAULT_TENANT {
+            return Ok(self.secret.clone());
+        }
+
+        return match self
+            .storage
+            .get_tenant(tenant)
+            .await
+            .map_err(SendError::retryable)?
+        {
+            Some(t) => t.webhook_secret.ok_or_else(|| {
+                SendError::from(format!(
+                    "tenant {} has no webhook secret, save the tenant to generate it",
+                    tenant
+                ))
+            }),
+            None => Err(format!("tenant {} not found", tenant).into()),
+        };
+    }
+
+    // Posts JSON body signed with secret of the tenant. Service events are sent the same way
+    pub async fn post(
+        &self,
+        tenant: &str,
+        url: &str,
+        timestamp: i64,
+        body: Vec<u8>,
+    ) -> Result<(), SendError> {
+        // Urls saved before the check was introduced may still point inside
+        let target = reqwest::Url::parse(url).map_err(|e| format!("invalid webhook url: {}", e))?;
+        check_target(&target, self.allow_private)?;
+
+        let signature = sign(&self.secret_of(tenant).await?, timestamp, &body);
+
+        let response = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, signature)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| {
+                let message = format!("webhook request failed: {}", e);
+                match e.is_builder() {
+                    true => SendError::from(message),
+                    false => SendError::retryable(message),
+                }
+            })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(SendError {
+                message: format!("webhook responded with {}", status),
+                retry_after: parse_retry_after(response.headers()),
+                permanent: false,
+                // Other client errors will be answered the same way
+                retryable: status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                    || status.is_server_error(),
+                message_ids: Vec::new(),
+                migrated_to: None,
+            });
+        }
+
+        return Ok(());
+    }
+}
+
+impl Notificator for WebhookNotificator {
+    async fn send(
+        &self,
+        notification: &Notification,
+        attempt: u32,
+    ) -> Result<SendReceipt, SendError> {
+        let url = match &notification.send_to {
+            Recipient::Webhook { url } => url,
+            other => return Err(format!("{} is not a webhook recipient", other).into()),
+        };
+
+        let timestamp = chrono::Utc::now().timestamp();
+        let envelope = WebhookEnvelope {
+            uuid: &notification.uuid,
+            text: &notification.text,
+            kind: &notification.kind,
+            attempt,
+            timestamp,
+        };
+
+        let body = serde_json::to_vec(&envelope)
+            .map_err(|e| format!("failed to serialize webhook body: {}", e))?;
+        self.post(&notification.tenant, url, timestamp, body)
+            .await?;
+
+        Ok(SendReceipt::default())
+    }
+}
+
+// Signature is HMAC-SHA256 of "<timestamp>.<body>", so a captured
+// body can't be replayed with a fresh timestamp
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    return format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+}
+
+// Sending to loopback, private and link-local networks is allowed
+// only with WEBHOOK_ALLOW_PRIVATE=true, so tenant keys can't reach
+// services next to the server
+pub fn private_targets_allowed() -> bool {
+    return matches!(
+        env::var("WEBHOOK_ALLOW_PRIVATE").map(|v| v.trim().to_lowercase()),
+        Ok(v) if v == "true" || v == "1"
+    );
+}
+
+// Rejects localhost and literal addresses that aren't public.
+// Hostnames are resolved and checked only when sending
+pub fn check_target(url: &reqwest::Url, allow_private: bool) -> Result<(), String> {
+    if allow_private {
+        return Ok(());
+    }
+
+    let host = match url.host_str() {
+        Some(h) => h.trim_start_matches('[').trim_end_matches(']'),
+        None => return Err("url has no host".to_string()),
+    };
+    let host = host.trim_end_matches('.').to_lowercase();
+
+    if host == "localhost" || host.ends_with(".localhost") {
+        return Err(format!("{} is a private address", host));
+    }
+    if let Ok(ip) = host.parse::<IpAddr>()
+        && !is_public(ip)
+    {
+        return Err(format!("{} is a private address", ip));
+    }
+
+    return Ok(());
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    return match ip {
+        IpAddr::V4(v4) => is_public_v4(v4),
+        IpAddr::V6(v6) => match embedded_v4(v6) {
+            Some(v4) => is_public_v4(v4),
+            None => is_public_v6(v6),
+        },
+    };
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    // 0.0.0.0/8 is "this network"
+    let this_network = a == 0;
+    // 100.64.0.0/10 is carrier-grade NAT, private as well
+    let shared = a == 100 && (64..128).contains(&b);
+    // 192.0.0.0/24 is reserved for protocol assignments
+    let protocol = a == 192 && b == 0 && c == 0;
+    // 198.18.0.0/15 is for benchmarking, routed inside networks only
+    let benchmarking = a == 198 && (b == 18 || b == 19);
+    // 240.0.0.0/4 is reserved, including broadcast
+    let reserved = a >= 240;
+    return !(ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || this_network
+        || shared
+        || protocol
+        || benchmarking
+        || reserved);
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    // 2001:db8::/32 is for documentation
+    let documentation = ip.segments()[..2] == [0x2001, 0xdb8];
+    return !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_unique_local()
+        || ip.is_unicast_link_local()
+        || ip.is_multicast()
+        || documentation);
+}
+
+// IPv4 address routed through IPv6 one: mapped ::ffff:a.b.c.d, compatible
+// ::a.b.c.d, NAT64 64:ff9b::a.b.c.d and 6to4 2002:aabb:ccdd::/48.
+// Such addresses are as public as the IPv4 one they carry
+fn embedded_v4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    if let Some(v4) = ip.to_ipv4_mapped() {
+        return Some(v4);
+    }
+
+    let octets = ip.octets();
+    let last = Ipv4Addr::new(octets[12], octets[13], octets[14], octets[15]);
+    return match ip.segments() {
+        // :: and ::1 are checked as IPv6
+        [0, 0, 0, 0, 0, 0, 0, 0 | 1] => None,
+        [0, 0, 0, 0, 0, 0, _, _] => Some(last),
+        [0x64, 0xff9b, 0, 0, 0, 0, _, _] => Some(last),
+        [0x2002, ..] => Some(Ipv4Addr::new(octets[2], octets[3], octets[4], octets[5])),
+        _ => None,
+    };
+}
+
+// System resolver that drops addresses which aren't public
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let host = name.as_str().to_string();
+        return Box::pin(async move {
+            let addrs: Vec<_> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|a| is_public(a.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} resolves only to private addresses", host).into());
+            }
+            let addrs: reqwest::dns::Addrs = Box::new(addrs.into_iter());
+            return Ok(addrs);
+        });
+    }
+}
+
+// Retry-After header in seconds. HTTP-date form is ignored
+fn parse_retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+    return value.trim().parse::<u64>().ok().map(Duration::from_secs);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{storage::memory::MemoryStore, tenants::Tenant};
+
+    #[test]
+    fn signature_is_hmac_of_timestamp_and_body() {
+        assert_eq!(
+            sign("secret", 1700000000, br#"{"a":1}"#),
+            "sha256=49f24e537407743fa4a0242bb63b94b9a47ee99cbbe071ccd8a22550ae411686"
+        );
+    }
+
+    #[test]
+    fn signature_depends_on_timestamp_and_secret() {
+        let body = br#"{"a":1}"#;
+
+        assert_ne!(sign("secret", 1, body), sign("secret", 2, body));
+        assert_ne!(sign("secret", 1, body), sign("other", 1, body));
+    }
+
+    #[test]
+    fn empty_secret_is_rejected() {
+        assert!(
+            WebhookNotificator::new(String::new(), false, Arc::new(MemoryStore::new())).is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn tenants_sign_with_own_secret() {
+        let storage: Arc<dyn NotificationStore> = Arc::new(MemoryStore::new());
+        let notificator =
+            WebhookNotificator::new("server".to_string(), false, storage.clone()).unwrap();
+
+        let mut tenant = Tenant {
+            id: "acme".to_string(),
+            telegram_bot_token: None,
+            events_url: None,
+            webhook_secret: None,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+        storage.persist_tenant(&tenant).await.unwrap();
+        // Tenant saved without secret doesn't fall back to the server one
+        assert!(notificator.secret_of("acme").await.is_err());
+
+        tenant.webhook_secret = Some("acme-secret".to_string());
+        storage.persist_tenant(&tenant).await.unwrap();
+        assert_eq!(notificator.secret_of("acme").await.unwrap(), "acme-secret");
+        assert_eq!(
+            notificator.secret_of(DEFAULT_TENANT).await.unwrap(),
+            "server"
+        );
+        assert!(notificator.secret_of("missing").await.is_err());
+    }
+
+    #[test]
+    fn retry_after_header_in_seconds() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert_eq!(parse_retry_after(&headers), None);
+
+        headers.insert(reqwest::header::RETRY_AFTER, "120".parse().unwrap());
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(120)));
+
+        headers.insert(
+            reqwest::header::RETRY_AFTER,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(parse_retry_after(&headers), None);
+    }
+
+    fn check(url: &str) -> Result<(), String> {
+        return check_target(&reqwest::Url::parse(url).unwrap(), false);
+    }
+
+    #[test]
+    fn private_targets_are_rejected() {
+        for url in [
+            "http://localhost:8080/hook",
+            "http://api.localhost/hook",
+            "http://127.0.0.1/hook",
+            "http://10.1.2.3/hook",
+            "http://172.16.0.1/hook",
+            "http://192.168.1.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://100.64.0.1/hook",
+            "http://0.0.0.0/hook",
+            "http://[::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[fe80::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+            "http://192.0.0.8/hook",
+            "http://198.18.0.1/hook",
+            "http://198.19.255.254/hook",
+            "http://240.0.0.1/hook",
+            "http://255.255.255.255/hook",
+            "http://224.0.0.1/hook",
+            "http://0.1.2.3/hook",
+            "http://192.0.2.1/hook",
+            "http://[ff02::1]/hook",
+            "http://[2001:db8::1]/hook",
+            "http://[::127.0.0.1]/hook",
+            "http://[::10.0.0.1]/hook",
+            "http://[64:ff9b::a9fe:a9fe]/hook",
+            "http://[64:ff9b::127.0.0.1]/hook",
+            "http://[2002:7f00:1::]/hook",
+            "http://[2002:c0a8:101::1]/hook",
+        ] {
+            assert!(check(url).is_err(), "{} passed", url);
+        }
+    }
+
+    #[test]
+    fn public_targets_are_accepted() {
+        for url in [
+            "https://example.com/hook",
+            "http://93.184.216.34/hook",
+            "http://[2606:2800:220:1::]/hook",
+            "http://[64:ff9b::5db8:d822]/hook",
+            "http://[2002:5db8:d822::1]/hook",
+        ] {
+            assert!(check(url).is_ok(), "{} rejected", url);
+        }
+    }
+
+    #[test]
+    fn private_targets_pass_when_allowed() {
+        let url = reqwest::Url::parse("http://127.0.0.1:9000/hook").unwrap();
+        assert!(check_target(&url, true).is_ok());
+    }
+}
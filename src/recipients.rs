@@ -0,0 +1,183 @@
+use lettre::{Address, message::Mailbox};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{bot, notifications::NotificationPlatform, notificators::webhook};
+
+// Data, needed to send a message to certain person.
+// Every platform has it's own variant
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Recipient {
+    Telegram {
+        chat_id: i64,
+        // Forum topic in a supergroup. None means "General"
+        #[serde(
+            default,
+            alias = "message_thread_id",
+            skip_serializing_if = "Option::is_none"
+        )]
+        thread_id: Option<i32>,
+    },
+    Email {
+        address: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
+    Webhook {
+        url: String,
+    },
+    // Telegram chats subscribed to the topic with /subscribe
+    Topic {
+        topic: String,
+    },
+}
+
+// Recipient as it was stored before it became platform-agnostic.
+// Only telegram was supported back then
+#[derive(Deserialize)]
+struct LegacyContactData {
+    user_id: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredRecipient {
+    Current(Recipient),
+    Legacy(LegacyContactData),
+}
+
+// Loads both current and legacy ({ "user_id": 123 }) recipients
+pub fn deserialize_with_legacy<'de, D>(deserializer: D) -> Result<Recipient, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    return match StoredRecipient::deserialize(deserializer)? {
+        StoredRecipient::Current(r) => Ok(r),
+        StoredRecipient::Legacy(l) => Ok(Recipient::Telegram {
+            chat_id: l.user_id,
+            thread_id: None,
+        }),
+    };
+}
+
+// Recipient as it comes in a request: either a string
+// (stringified chat id, email address etc.) or a tagged object
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum RecipientPayload {
+    Raw(String),
+    Structured(Recipient),
+}
+
+impl Recipient {
+    pub fn platform(&self) -> NotificationPlatform {
+        return match self {
+            Recipient::Telegram { .. } | Recipient::Topic { .. } => NotificationPlatform::Telegram,
+            Recipient::Email { .. } => NotificationPlatform::Email,
+            Recipient::Webhook { .. } => NotificationPlatform::Webhook,
+        };
+    }
+
+    // Used for filtering: accepts both bare identifier
+    // ("123", "john@example.com") and it's display form ("telegram:123")
+    pub fn matches(&self, query: &str) -> bool {
+        let identifier = match self {
+            Recipient::Telegram { chat_id, .. } => chat_id.to_string(),
+            Recipient::Email { address, .. } => address.clone(),
+            Recipient::Webhook { url } => url.clone(),
+            Recipient::Topic { topic } => topic.clone(),
+        };
+
+        return identifier == query || self.to_string() == query;
+    }
+
+    pub fn from_payload(
+        payload: RecipientPayload,
+        platform: &NotificationPlatform,
+    ) -> Result<Self, String> {
+        let recipient = match payload {
+            RecipientPayload::Structured(r) => r,
+            RecipientPayload::Raw(raw) => Self::parse_raw(raw.trim(), platform)?,
+        };
+
+        recipient.validate_for(platform)?;
+        return Ok(recipient);
+    }
+
+    fn parse_raw(raw: &str, platform: &NotificationPlatform) -> Result<Self, String> {
+        return match platform {
+            NotificationPlatform::Telegram if raw.starts_with("topic:") => Ok(Recipient::Topic {
+                topic: raw["topic:".len()..].to_string(),
+            }),
+            NotificationPlatform::Telegram => match raw.parse::<i64>() {
+                Ok(chat_id) => Ok(Recipient::Telegram {
+                    chat_id,
+                    thread_id: None,
+                }),
+                Err(_) => Err(format!("\"{}\" is not a valid telegram chat id", raw)),
+            },
+            NotificationPlatform::Email => match raw.parse::<Mailbox>() {
+                Ok(mailbox) => Ok(Recipient::Email {
+                    address: mailbox.email.to_string(),
+                    name: mailbox.name,
+                }),
+                Err(_) => Err(format!("\"{}\" is not a valid email address", raw)),
+            },
+            NotificationPlatform::Webhook => Ok(Recipient::Webhook {
+                url: raw.to_string(),
+            }),
+        };
+    }
+
+    pub fn validate_for(&self, platform: &NotificationPlatform) -> Result<(), String> {
+        if &self.platform() != platform {
+            return Err(format!(
+                "Recipient of type {:?} can't be used with platform {:?}",
+                self.platform(),
+                platform
+            ));
+        }
+
+        return match self {
+            Recipient::Telegram { chat_id, thread_id } => {
+                if *chat_id == 0 {
+                    return Err("Telegram chat id can't be 0".to_string());
+                }
+                if let Some(t) = thread_id
+                    && *t <= 0
+                {
+                    return Err("Telegram thread id must be positive".to_string());
+                }
+                Ok(())
+            }
+            Recipient::Email { address, .. } => match address.parse::<Address>() {
+                Ok(_) => Ok(()),
+                Err(_) => Err(format!("\"{}\" is not a valid email address", address)),
+            },
+            Recipient::Webhook { url } => match reqwest::Url::parse(url) {
+                Ok(u) if u.scheme() == "http" || u.scheme() == "https" => {
+                    webhook::check_target(&u, webhook::private_targets_allowed())
+                        .map_err(|e| format!("Webhook url is not allowed: {}", e))
+                }
+                Ok(_) => Err("Webhook url must use http or https".to_string()),
+                Err(e) => Err(format!("\"{}\" is not a valid url: {}", url, e)),
+            },
+            Recipient::Topic { topic } => bot::validate_topic(topic),
+        };
+    }
+}
+
+impl std::fmt::Display for Recipient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            Recipient::Telegram {
+                chat_id,
+                thread_id: Some(t),
+            } => write!(f, "telegram:{}/{}", chat_id, t),
+            Recipient::Telegram { chat_id, .. } => write!(f, "telegram:{}", chat_id),
+            Recipient::Email { address, .. } => write!(f, "email:{}", address),
+            Recipient::Webhook { url } => write!(f, "webhook:{}", url),
+            Recipient::Topic { topic } => write!(f, "topic:{}", topic),
+        };
+    }
+}
@@ -1,82 +1,588 @@
-use chrono::{DateTime, Local, Timelike};
+use chrono::{DateTime, FixedOffset, Local, NaiveTime, Timelike, Utc};
+use chrono_tz::Tz;
+use croner::{
+    Cron,
+    parser::{CronParser, Seconds},
+};
+use serde::Serialize;
+use std::collections::HashMap;
 use std::str::FromStr;
-use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
 use tokio::time::sleep;
 
-use crate::notifications::Notification;
-use crate::notificators::TelegramNotificator;
+use crate::dispatcher::Dispatcher;
+use crate::notifications::{Notification, NotificationKind};
+use crate::storage::NotificationStore;
+use crate::utils::resolve_local;
+
+// Scheduled notification. Task handle is None while job is paused
+struct Job {
+    // Distinguishes job from the ones replaced it under the same uuid
+    id: u64,
+    notification: Notification,
+    handle: Option<JoinHandle<()>>,
+}
+
+// Public view of a job, used for inspection
+#[derive(Debug, Serialize, Clone)]
+pub struct JobInfo {
+    pub uuid: String,
+    pub paused: bool,
+    pub next_fire_at: Option<String>,
+}
+
+type Jobs = Arc<Mutex<HashMap<String, Job>>>;
 
 pub struct Scheduler {
-    tx: mpsc::Sender<Notification>,
+    dispatcher: Arc<Dispatcher>,
+    storage: Arc<dyn NotificationStore>,
+
+    // Every scheduled notification, keyed by it's uuid
+    jobs: Jobs,
+    next_job_id: AtomicU64,
 }
 
 /*
     Получилось сложнее, чем должно было быть.
-    1. Создание задачи для каждого времени отправки
-    1.1 Вычисление времени отправки
+    1. Создание задачи для каждого уведомления
+    1.1 Вычисление ближайшего времени отправки
     1.2 Если время прошло, отправляем на следующий день
     1.3 Если время не наступило, планируем на сегодня
     1.4 Отправляем уведомляху
 */
 
 impl Scheduler {
-    pub fn new(telegram: Arc<TelegramNotificator>) -> Self {
-        let (tx, mut rx): (mpsc::Sender<Notification>, mpsc::Receiver<Notification>) =
-            mpsc::channel(32);
-        let telegram_clone = telegram.clone();
+    pub fn new(dispatcher: Arc<Dispatcher>, storage: Arc<dyn NotificationStore>) -> Self {
+        Scheduler {
+            dispatcher,
+            storage,
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+            next_job_id: AtomicU64::new(0),
+        }
+    }
+
+    // Schedules notification. If it's already scheduled, old job is
+    // replaced, so edits are picked up immediately.
+    // Notifications with paused flag are registered, but not armed.
+    // Completed scheduled notifications are skipped
+    pub fn add_notification(&self, notification: &Notification) -> Result<(), String> {
+        validate_schedule(notification)?;
+
+        if notification.completed {
+            self.cancel(&notification.uuid)?;
+            return Ok(());
+        }
+
+        let id = self.next_job_id.fetch_add(1, Ordering::Relaxed);
+        let handle = match notification.paused {
+            true => None,
+            false => Some(self.spawn_job(notification.clone(), id)),
+        };
+
+        let job = Job {
+            id,
+            notification: notification.clone(),
+            handle,
+        };
+
+        let mut jobs = self.jobs.lock().map_err(|e| e.to_string())?;
+        if let Some(old) = jobs.insert(notification.uuid.clone(), job) {
+            old.abort();
+        }
+
+        Ok(())
+    }
+
+    // Same as add_notification, but keeps paused state of existing job
+    pub fn reschedule(&self, notification: &Notification) -> Result<(), String> {
+        let paused = {
+            let jobs = self.jobs.lock().map_err(|e| e.to_string())?;
+            jobs.get(&notification.uuid)
+                .map(|j| j.handle.is_none())
+                .unwrap_or(notification.paused)
+        };
+
+        let mut notification = notification.clone();
+        notification.paused = paused;
+        return self.add_notification(&notification);
+    }
+
+    // Removes job completely. Returns false if it wasn't scheduled
+    pub fn cancel(&self, uuid: &str) -> Result<bool, String> {
+        let mut jobs = self.jobs.lock().map_err(|e| e.to_string())?;
+
+        return match jobs.remove(uuid) {
+            Some(job) => {
+                job.abort();
+                Ok(true)
+            }
+            None => Ok(false),
+        };
+    }
+
+    // Disarms job, but keeps it registered. Returns false if it wasn't scheduled
+    pub fn pause(&self, uuid: &str) -> Result<bool, String> {
+        let mut jobs = self.jobs.lock().map_err(|e| e.to_string())?;
+
+        return match jobs.get_mut(uuid) {
+            Some(job) => {
+                job.abort();
+                job.handle = None;
+                job.notification.paused = true;
+                Ok(true)
+            }
+            None => Ok(false),
+        };
+    }
+
+    // Arms paused job again. Returns false if it wasn't scheduled
+    pub fn resume(&self, uuid: &str) -> Result<bool, String> {
+        let mut jobs = self.jobs.lock().map_err(|e| e.to_string())?;
+
+        return match jobs.get_mut(uuid) {
+            Some(job) => {
+                if job.handle.is_none() {
+                    job.id = self.next_job_id.fetch_add(1, Ordering::Relaxed);
+                    job.notification.paused = false;
+                    job.handle = Some(self.spawn_job(job.notification.clone(), job.id));
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        };
+    }
+
+    // Jobs of notifications passing the filter
+    pub fn jobs(&self, filter: impl Fn(&Notification) -> bool) -> Result<Vec<JobInfo>, String> {
+        let jobs = self.jobs.lock().map_err(|e| e.to_string())?;
+        let now = Utc::now();
+
+        let mut infos: Vec<JobInfo> = jobs
+            .iter()
+            .filter(|(_, job)| filter(&job.notification))
+            .map(|(uuid, job)| JobInfo {
+                uuid: uuid.clone(),
+                paused: job.handle.is_none(),
+                next_fire_at: match job.handle {
+                    Some(_) => next_fire_at(&job.notification, now).map(|dt| dt.to_rfc3339()),
+                    None => None,
+                },
+            })
+            .collect();
+
+        infos.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+        return Ok(infos);
+    }
+
+    fn spawn_job(&self, mut notification: Notification, job_id: u64) -> JoinHandle<()> {
+        let dispatcher = self.dispatcher.clone();
+        let storage = self.storage.clone();
+        let jobs = self.jobs.clone();
 
         tokio::spawn(async move {
-            // Ожидание новых уведомлений и обработка в лупе
-            while let Some(notification) = rx.recv().await {
-                for timestamp_str in &notification.daily_send_timestamps {
-                    if let Ok(dt) = DateTime::<Local>::from_str(timestamp_str) {
-                        let time = dt.time();
-                        let hour = time.hour();
-                        let minute = time.minute();
-                        let notification_clone = notification.clone();
-                        let telegram_clone = telegram_clone.clone();
-
-                        tokio::spawn(async move {
-                            loop {
-                                let now = Local::now();
-                                let target = now.date_naive().and_time(
-                                    chrono::NaiveTime::from_hms_opt(hour, minute, 0).unwrap(),
-                                );
-
-                                let duration = if now.naive_local() > target {
-                                    // Ждём до завтра
-                                    let tomorrow = now.date_naive().succ_opt().unwrap();
-                                    let next_target = tomorrow.and_time(
-                                        chrono::NaiveTime::from_hms_opt(hour, minute, 0).unwrap(),
-                                    );
-                                    (next_target - now.naive_local()).to_std().unwrap()
-                                } else {
-                                    // Или ждём нужного времени сегодня
-                                    (target - now.naive_local()).to_std().unwrap()
-                                };
-
-                                sleep(duration).await;
-
-                                if let Err(e) = notification_clone
-                                    .send_instant(telegram_clone.clone())
-                                    .await
-                                {
-                                    tracing::error!("Failed to send notification: {}", e);
-                                }
-                            }
-                        });
+            loop {
+                let now = Utc::now();
+                let next = match next_fire_at(&notification, now) {
+                    Some(n) => n,
+                    None => {
+                        tracing::error!(
+                            "notification {} has no upcoming sends, stopping job",
+                            notification.uuid
+                        );
+                        return;
                     }
+                };
+
+                sleep(next.signed_duration_since(now).to_std().unwrap_or_default()).await;
+
+                if notification.kind == NotificationKind::Scheduled {
+                    fire_once(notification, &dispatcher, storage.as_ref()).await;
+                    remove_job(&jobs, job_id);
+                    return;
+                }
+
+                // Recipient may have changed since the job was armed,
+                // e.g. dispatcher moved it to the upgraded supergroup
+                if let Ok(stored) = storage
+                    .get_notification(&notification.tenant, &notification.uuid)
+                    .await
+                {
+                    notification.send_to = stored.send_to;
+                }
+
+                // failures are logged and saved as dead letters by dispatcher
+                if let Err(e) = dispatcher.deliver(&notification).await
+                    && e.permanent
+                {
+                    // Dispatcher has deactivated the notification, job stays paused
+                    disarm_job(&jobs, job_id);
+                    return;
                 }
             }
-        });
+        })
+    }
+}
 
-        Scheduler { tx }
+// Sends scheduled notification and marks it completed. Completion is
+// persisted before sending, so the notification is never sent twice,
+// even if the process dies in between. Failed delivery keeps it completed,
+// sending it again is up to dead letter replay
+async fn fire_once(
+    notification: Notification,
+    dispatcher: &Arc<Dispatcher>,
+    storage: &dyn NotificationStore,
+) {
+    if let Err(e) = set_completed(&notification, storage).await {
+        tracing::error!(
+            "failed to mark notification {} completed, skipping send: {}",
+            notification.uuid,
+            e
+        );
+        return;
     }
 
-    pub fn add_notification(&self, notification: &Notification) -> Result<(), String> {
-        self.tx
-            .try_send((*notification).clone())
-            .map_err(|e| e.to_string())
+    // last_sent is updated and failures are saved as dead letters by dispatcher
+    if dispatcher.deliver(&notification).await.is_err() {
+        tracing::info!(
+            "one-shot notification {} failed and stays completed until replayed",
+            notification.uuid
+        );
+    }
+}
+
+// Fresh copy is loaded from storage, so edits made meanwhile are kept
+async fn set_completed(
+    notification: &Notification,
+    storage: &dyn NotificationStore,
+) -> Result<(), String> {
+    let mut stored = storage
+        .get_notification(&notification.tenant, &notification.uuid)
+        .await?;
+    stored.completed = true;
+    return storage.persist_notification(&stored).await;
+}
+
+// Removes finished job, unless it was replaced by a newer one
+fn remove_job(jobs: &Jobs, job_id: u64) {
+    let mut jobs = match jobs.lock() {
+        Ok(j) => j,
+        Err(e) => {
+            tracing::error!("failed to lock scheduler jobs: {}", e);
+            return;
+        }
+    };
+
+    jobs.retain(|_, job| job.id != job_id);
+}
+
+// Marks job paused from inside it's task, which stops right after
+fn disarm_job(jobs: &Jobs, job_id: u64) {
+    let mut jobs = match jobs.lock() {
+        Ok(j) => j,
+        Err(e) => {
+            tracing::error!("failed to lock scheduler jobs: {}", e);
+            return;
+        }
+    };
+
+    if let Some(job) = jobs.values_mut().find(|job| job.id == job_id) {
+        job.handle = None;
+        job.notification.paused = true;
+    }
+}
+
+impl Job {
+    fn abort(&self) {
+        if let Some(handle) = &self.handle {
+            handle.abort();
+        }
+    }
+}
+
+// Parses standard cron expression: 5 fields, or 6 with leading seconds
+pub fn parse_cron(expr: &str) -> Result<Cron, String> {
+    let fields = expr.split_whitespace().count();
+    if fields != 5 && fields != 6 {
+        return Err(format!(
+            "Invalid cron expression \"{}\": expected 5 or 6 fields, got {}",
+            expr, fields
+        ));
+    }
+
+    let cron = CronParser::builder()
+        .seconds(Seconds::Optional)
+        .build()
+        .parse(expr)
+        .map_err(|e| format!("Invalid cron expression \"{}\": {}", expr, e))?;
+
+    // Patterns like "0 0 30 2 *" parse fine, but never fire
+    if cron.find_next_occurrence(&Local::now(), false).is_err() {
+        return Err(format!(
+            "Cron expression \"{}\" has no upcoming occurrences",
+            expr
+        ));
+    }
+
+    return Ok(cron);
+}
+
+fn validate_schedule(notification: &Notification) -> Result<(), String> {
+    notification.tz()?;
+
+    match notification.kind {
+        NotificationKind::Cron => match &notification.cron {
+            Some(expr) => {
+                parse_cron(expr)?;
+            }
+            None => return Err("cron notification has no cron expression".to_string()),
+        },
+        NotificationKind::Scheduled => match &notification.send_at {
+            Some(send_at) => {
+                parse_stored_timestamp(send_at)?;
+            }
+            None => return Err("scheduled notification has no send_at".to_string()),
+        },
+        _ => {
+            for timestamp_str in &notification.daily_send_timestamps {
+                parse_stored_timestamp(timestamp_str)?;
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+// Nearest send time of notification strictly after now,
+// in the notification zone
+pub fn next_fire_at(notification: &Notification, now: DateTime<Utc>) -> Option<DateTime<Tz>> {
+    let tz = notification.tz().ok()?;
+    let now = now.with_timezone(&tz);
+
+    return match notification.kind {
+        NotificationKind::Cron => {
+            let cron = parse_cron(notification.cron.as_deref()?).ok()?;
+            // croner keeps sub-second part of the start time in the result
+            let now = now.with_nanosecond(0)?;
+            cron.find_next_occurrence(&now, false).ok()
+        }
+        NotificationKind::Daily => next_daily_fire_at(notification, &tz, now),
+        // Missed while service was down - fire right away
+        NotificationKind::Scheduled => match notification.completed {
+            true => None,
+            false => parse_stored_timestamp(notification.send_at.as_deref()?)
+                .ok()
+                .map(|dt| dt.with_timezone(&tz)),
+        },
+        NotificationKind::Instant => None,
+    };
+}
+
+fn next_daily_fire_at(
+    notification: &Notification,
+    tz: &Tz,
+    now: DateTime<Tz>,
+) -> Option<DateTime<Tz>> {
+    let mut nearest: Option<DateTime<Tz>> = None;
+
+    for timestamp_str in &notification.daily_send_timestamps {
+        let dt = match parse_stored_timestamp(timestamp_str) {
+            Ok(dt) => dt.with_timezone(tz),
+            Err(_) => continue,
+        };
+        let time = NaiveTime::from_hms_opt(dt.hour(), dt.minute(), 0)?;
+
+        // Сегодня, а если время прошло - завтра
+        let mut day = now.date_naive();
+        let candidate = loop {
+            if let Some(target) = resolve_local(tz, day.and_time(time))
+                && target > now
+            {
+                break target;
+            }
+            day = day.succ_opt()?;
+        };
+
+        if nearest.is_none_or(|n| candidate < n) {
+            nearest = Some(candidate);
+        }
+    }
+
+    return nearest;
+}
+
+// Daily timestamps are stored in RFC3339, older records
+// in chrono's default format ("2025-04-22 14:14:40 +03:00")
+pub fn parse_stored_timestamp(timestamp: &str) -> Result<DateTime<FixedOffset>, String> {
+    return DateTime::<FixedOffset>::from_str(timestamp)
+        .map_err(|e| format!("invalid timestamp \"{}\": {}", timestamp, e));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dispatcher::RetryPolicy,
+        events::Events,
+        notifications::{NotificationBuilder, NotificationPlatform},
+        notificators::{Notificators, TelegramNotificator},
+        recipients::Recipient,
+        storage::memory::MemoryStore,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn parse_cron_accepts_five_and_six_fields() {
+        assert!(parse_cron("*/5 * * * *").is_ok());
+        assert!(parse_cron("30 0 9 * * MON-FRI").is_ok());
+    }
+
+    #[test]
+    fn parse_cron_rejects_bad_expressions() {
+        assert!(parse_cron("* * * *").is_err());
+        assert!(parse_cron("* * * * * * *").is_err());
+        assert!(parse_cron("61 * * * *").is_err());
+        assert!(parse_cron("not a cron at all").is_err());
+        // Valid, but never fires
+        assert!(parse_cron("0 0 30 2 *").is_err());
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        return DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+    }
+
+    fn berlin(kind: NotificationKind) -> Notification {
+        return NotificationBuilder::new()
+            .kind(kind)
+            .timezone("Europe/Berlin".to_string())
+            .build();
+    }
+
+    #[test]
+    fn next_fire_at_cron_in_notification_zone() {
+        let mut notification = berlin(NotificationKind::Cron);
+        notification.cron = Some("0 9 * * *".to_string());
+
+        // Berlin is at +01:00 in winter
+        let next = next_fire_at(&notification, utc("2025-01-10T07:00:00Z")).unwrap();
+        assert_eq!(next.to_rfc3339(), "2025-01-10T09:00:00+01:00");
+
+        // Right at the fire time the next one is tomorrow, after the DST switch
+        let next = next_fire_at(&notification, utc("2025-03-29T08:00:00Z")).unwrap();
+        assert_eq!(next.to_rfc3339(), "2025-03-30T09:00:00+02:00");
+    }
+
+    #[test]
+    fn next_fire_at_daily_picks_nearest_time() {
+        let mut notification = berlin(NotificationKind::Daily);
+        notification.daily_send_timestamps = vec![
+            "2025-01-01T18:00:00+01:00".to_string(),
+            "2025-01-01T08:00:00+01:00".to_string(),
+        ];
+
+        let next = next_fire_at(&notification, utc("2025-01-10T10:00:00Z")).unwrap();
+        assert_eq!(next.to_rfc3339(), "2025-01-10T18:00:00+01:00");
+
+        let next = next_fire_at(&notification, utc("2025-01-10T18:00:00Z")).unwrap();
+        assert_eq!(next.to_rfc3339(), "2025-01-11T08:00:00+01:00");
+    }
+
+    #[test]
+    fn next_fire_at_daily_across_dst() {
+        let mut notification = berlin(NotificationKind::Daily);
+        notification.daily_send_timestamps = vec!["2025-01-01T02:30:00+01:00".to_string()];
+
+        // 02:30 doesn't exist on the day clocks jump forward
+        let next = next_fire_at(&notification, utc("2025-03-29T12:00:00Z")).unwrap();
+        assert_eq!(next.to_rfc3339(), "2025-03-30T03:30:00+02:00");
+
+        // and happens twice on the day they go back, first one is used
+        let next = next_fire_at(&notification, utc("2025-10-25T12:00:00Z")).unwrap();
+        assert_eq!(next.to_rfc3339(), "2025-10-26T02:30:00+02:00");
+    }
+
+    #[test]
+    fn next_fire_at_scheduled_fires_once() {
+        let mut notification = berlin(NotificationKind::Scheduled);
+        notification.send_at = Some("2025-01-10T12:00:00+01:00".to_string());
+
+        let next = next_fire_at(&notification, utc("2025-01-10T09:00:00Z")).unwrap();
+        assert_eq!(next.to_rfc3339(), "2025-01-10T12:00:00+01:00");
+
+        // Missed send is due right away
+        let next = next_fire_at(&notification, utc("2025-01-11T09:00:00Z")).unwrap();
+        assert!(next < utc("2025-01-11T09:00:00Z"));
+
+        notification.completed = true;
+        assert!(next_fire_at(&notification, utc("2025-01-10T09:00:00Z")).is_none());
+    }
+
+    #[test]
+    fn next_fire_at_instant_is_none() {
+        let notification = berlin(NotificationKind::Instant);
+        assert!(next_fire_at(&notification, Utc::now()).is_none());
+    }
+
+    #[tokio::test]
+    async fn failed_one_shot_is_not_armed_again() {
+        let storage: Arc<dyn NotificationStore> = Arc::new(MemoryStore::new());
+        // Email isn't configured, so every send fails
+        let notificators = Arc::new(Notificators {
+            telegram: Arc::new(TelegramNotificator::new(
+                "123:abc".to_string(),
+                storage.clone(),
+            )),
+            email: None,
+            webhook: None,
+        });
+        let events = Arc::new(Events::new(None, storage.clone()).unwrap());
+        let policy = RetryPolicy {
+            max_attempts: 1,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+            jitter: false,
+        };
+        let dispatcher = Arc::new(Dispatcher::new(
+            notificators,
+            storage.clone(),
+            policy,
+            events,
+        ));
+        let scheduler = Scheduler::new(dispatcher, storage.clone());
+
+        let mut notification = NotificationBuilder::new()
+            .kind(NotificationKind::Scheduled)
+            .platform(NotificationPlatform::Email)
+            .send_to(Recipient::Email {
+                address: "john@example.com".to_string(),
+                name: None,
+            })
+            .text("hello".to_string())
+            .build();
+        notification.send_at = Some("2025-01-10T12:00:00+00:00".to_string());
+        storage.persist_notification(&notification).await.unwrap();
+
+        // Missed send fires right away
+        scheduler.add_notification(&notification).unwrap();
+        for _ in 0..100 {
+            if scheduler.jobs(|_| true).unwrap().is_empty() {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert!(scheduler.jobs(|_| true).unwrap().is_empty());
+
+        let stored = storage
+            .get_notification(&notification.tenant, &notification.uuid)
+            .await
+            .unwrap();
+        assert!(stored.completed);
+        assert!(stored.last_sent.is_none());
+
+        // Restart loads it again, it stays unscheduled
+        scheduler.add_notification(&stored).unwrap();
+        assert!(scheduler.jobs(|_| true).unwrap().is_empty());
+        assert!(next_fire_at(&stored, Utc::now()).is_none());
     }
 }
@@ -0,0 +1,664 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::{
+    attachments::Attachment,
+    auth::ApiKey,
+    bot::{ButtonPress, Subscription},
+    dispatcher::{DeadLetter, DeliveryRecord},
+    notifications::Notification,
+    storage::NotificationStore,
+    templates::Template,
+    tenants::Tenant,
+};
+
+// Everything tenant owned lives in its own partition
+#[derive(Default)]
+struct Partition {
+    notifications: HashMap<String, Notification>,
+    dead_letters: HashMap<String, DeadLetter>,
+    // Deliveries of every notification, oldest first
+    deliveries: HashMap<String, Vec<DeliveryRecord>>,
+    templates: HashMap<String, Template>,
+    // Button presses of every notification, oldest first
+    presses: HashMap<String, Vec<ButtonPress>>,
+    subscriptions: HashMap<String, Subscription>,
+    // Metadata along with contents
+    attachments: HashMap<String, (Attachment, Vec<u8>)>,
+}
+
+#[derive(Default)]
+struct Data {
+    partitions: HashMap<String, Partition>,
+    // Keyed by key hash
+    api_keys: HashMap<String, ApiKey>,
+    tenants: HashMap<String, Tenant>,
+}
+
+impl Data {
+    fn partition(&mut self, tenant: &str) -> &mut Partition {
+        return self.partitions.entry(tenant.to_string()).or_default();
+    }
+}
+
+// Keeps everything in process memory. Used for tests and local
+// experiments, nothing survives a restart
+pub struct MemoryStore {
+    data: Mutex<Data>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        return MemoryStore {
+            data: Mutex::new(Data::default()),
+        };
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Data>, String> {
+        return self
+            .data
+            .lock()
+            .map_err(|e| format!("Failed to lock memory storage: {}", e));
+    }
+}
+
+#[async_trait]
+impl NotificationStore for MemoryStore {
+    async fn persist_notification(&self, notification: &Notification) -> Result<(), String> {
+        self.lock()?
+            .partition(&notification.tenant)
+            .notifications
+            .insert(notification.uuid.clone(), notification.clone());
+        return Ok(());
+    }
+
+    async fn get_notification(&self, tenant: &str, key: &str) -> Result<Notification, String> {
+        return self
+            .lock()?
+            .partition(tenant)
+            .notifications
+            .get(key)
+            .cloned()
+            .ok_or_else(|| format!("Key '{}' not found", key));
+    }
+
+    async fn get_all_notifications(&self, tenant: &str) -> Result<Vec<Notification>, String> {
+        return Ok(self
+            .lock()?
+            .partition(tenant)
+            .notifications
+            .values()
+            .cloned()
+            .collect());
+    }
+
+    async fn delete_notification(&self, tenant: &str, key: &str) -> Result<(), String> {
+        self.lock()?.partition(tenant).notifications.remove(key);
+        return Ok(());
+    }
+
+    async fn exists(&self, tenant: &str, key: &str) -> Result<bool, String> {
+        return Ok(self
+            .lock()?
+            .partition(tenant)
+            .notifications
+            .contains_key(key));
+    }
+
+    async fn persist_dead_letter(&self, dead_letter: &DeadLetter) -> Result<(), String> {
+        self.lock()?
+            .partition(&dead_letter.notification.tenant)
+            .dead_letters
+            .insert(dead_letter.id.clone(), dead_letter.clone());
+        return Ok(());
+    }
+
+    async fn get_dead_letter(&self, tenant: &str, id: &str) -> Result<Option<DeadLetter>, String> {
+        return Ok(self.lock()?.partition(tenant).dead_letters.get(id).cloned());
+    }
+
+    async fn get_all_dead_letters(&self, tenant: &str) -> Result<Vec<DeadLetter>, String> {
+        return Ok(self
+            .lock()?
+            .partition(tenant)
+            .dead_letters
+            .values()
+            .cloned()
+            .collect());
+    }
+
+    async fn delete_dead_letter(&self, tenant: &str, id: &str) -> Result<bool, String> {
+        return Ok(self
+            .lock()?
+            .partition(tenant)
+            .dead_letters
+            .remove(id)
+            .is_some());
+    }
+
+    async fn persist_delivery(&self, delivery: &DeliveryRecord) -> Result<(), String> {
+        self.lock()?
+            .partition(&delivery.tenant)
+            .deliveries
+            .entry(delivery.notification_id.clone())
+            .or_default()
+            .push(delivery.clone());
+        return Ok(());
+    }
+
+    async fn get_deliveries(
+        &self,
+        tenant: &str,
+        notification_id: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<DeliveryRecord>, usize), String> {
+        let mut data = self.lock()?;
+        let deliveries = match data.partition(tenant).deliveries.get(notification_id) {
+            Some(d) => d,
+            None => return Ok((Vec::new(), 0)),
+        };
+
+        let page = deliveries
+            .iter()
+            .rev()
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect();
+
+        return Ok((page, deliveries.len()));
+    }
+
+    async fn get_delivery(&self, tenant: &str, id: &str) -> Result<Option<DeliveryRecord>, String> {
+        return Ok(self
+            .lock()?
+            .partition(tenant)
+            .deliveries
+            .values()
+            .flatten()
+            .find(|d| d.id == id)
+            .cloned());
+    }
+
+    async fn update_delivery(&self, delivery: &DeliveryRecord) -> Result<(), String> {
+        let mut data = self.lock()?;
+        let saved = data
+            .partition(&delivery.tenant)
+            .deliveries
+            .get_mut(&delivery.notification_id)
+            .and_then(|d| d.iter_mut().find(|d| d.id == delivery.id));
+
+        return match saved {
+            Some(saved) => {
+                *saved = delivery.clone();
+                Ok(())
+            }
+            None => Err(format!("Delivery {} not found", delivery.id)),
+        };
+    }
+
+    async fn delete_deliveries(&self, tenant: &str, notification_id: &str) -> Result<(), String> {
+        self.lock()?
+            .partition(tenant)
+            .deliveries
+            .remove(notification_id);
+        return Ok(());
+    }
+
+    async fn persist_button_press(&self, press: &ButtonPress) -> Result<(), String> {
+        self.lock()?
+            .partition(&press.tenant)
+            .presses
+            .entry(press.notification_id.clone())
+            .or_default()
+            .push(press.clone());
+        return Ok(());
+    }
+
+    async fn get_button_presses(
+        &self,
+        tenant: &str,
+        notification_id: &str,
+    ) -> Result<Vec<ButtonPress>, String> {
+        return Ok(self
+            .lock()?
+            .partition(tenant)
+            .presses
+            .get(notification_id)
+            .map(|p| p.iter().rev().cloned().collect())
+            .unwrap_or_default());
+    }
+
+    async fn delete_button_presses(
+        &self,
+        tenant: &str,
+        notification_id: &str,
+    ) -> Result<(), String> {
+        self.lock()?
+            .partition(tenant)
+            .presses
+            .remove(notification_id);
+        return Ok(());
+    }
+
+    async fn persist_subscription(&self, subscription: &Subscription) -> Result<(), String> {
+        self.lock()?
+            .partition(&subscription.tenant)
+            .subscriptions
+            .insert(subscription.id.clone(), subscription.clone());
+        return Ok(());
+    }
+
+    async fn get_all_subscriptions(&self, tenant: &str) -> Result<Vec<Subscription>, String> {
+        return Ok(self
+            .lock()?
+            .partition(tenant)
+            .subscriptions
+            .values()
+            .cloned()
+            .collect());
+    }
+
+    async fn delete_subscription(&self, tenant: &str, id: &str) -> Result<bool, String> {
+        return Ok(self
+            .lock()?
+            .partition(tenant)
+            .subscriptions
+            .remove(id)
+            .is_some());
+    }
+
+    async fn persist_template(&self, template: &Template) -> Result<(), String> {
+        self.lock()?
+            .partition(&template.tenant)
+            .templates
+            .insert(template.id.clone(), template.clone());
+        return Ok(());
+    }
+
+    async fn get_template(&self, tenant: &str, id: &str) -> Result<Option<Template>, String> {
+        return Ok(self.lock()?.partition(tenant).templates.get(id).cloned());
+    }
+
+    async fn get_all_templates(&self, tenant: &str) -> Result<Vec<Template>, String> {
+        return Ok(self
+            .lock()?
+            .partition(tenant)
+            .templates
+            .values()
+            .cloned()
+            .collect());
+    }
+
+    async fn delete_template(&self, tenant: &str, id: &str) -> Result<bool, String> {
+        return Ok(self
+            .lock()?
+            .partition(tenant)
+            .templates
+            .remove(id)
+            .is_some());
+    }
+
+    async fn persist_attachment(&self, attachment: &Attachment, data: &[u8]) -> Result<(), String> {
+        self.lock()?
+            .partition(&attachment.tenant)
+            .attachments
+            .insert(attachment.id.clone(), (attachment.clone(), data.to_vec()));
+        return Ok(());
+    }
+
+    async fn get_attachment(&self, tenant: &str, id: &str) -> Result<Option<Attachment>, String> {
+        return Ok(self
+            .lock()?
+            .partition(tenant)
+            .attachments
+            .get(id)
+            .map(|(a, _)| a.clone()));
+    }
+
+    async fn get_attachment_data(&self, tenant: &str, id: &str) -> Result<Option<Vec<u8>>, String> {
+        return Ok(self
+            .lock()?
+            .partition(tenant)
+            .attachments
+            .get(id)
+            .map(|(_, d)| d.clone()));
+    }
+
+    async fn get_all_attachments(&self, tenant: &str) -> Result<Vec<Attachment>, String> {
+        return Ok(self
+            .lock()?
+            .partition(tenant)
+            .attachments
+            .values()
+            .map(|(a, _)| a.clone())
+            .collect());
+    }
+
+    async fn delete_attachment(&self, tenant: &str, id: &str) -> Result<bool, String> {
+        return Ok(self
+            .lock()?
+            .partition(tenant)
+            .attachments
+            .remove(id)
+            .is_some());
+    }
+
+    async fn persist_api_key(&self, api_key: &ApiKey) -> Result<(), String> {
+        self.lock()?
+            .api_keys
+            .insert(api_key.key_hash.clone(), api_key.clone());
+        return Ok(());
+    }
+
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, String> {
+        return Ok(self.lock()?.api_keys.get(key_hash).cloned());
+    }
+
+    async fn get_all_api_keys(&self) -> Result<Vec<ApiKey>, String> {
+        return Ok(self.lock()?.api_keys.values().cloned().collect());
+    }
+
+    async fn delete_api_key(&self, id: &str) -> Result<bool, String> {
+        let mut data = self.lock()?;
+        let before = data.api_keys.len();
+        data.api_keys.retain(|_, key| key.id != id);
+        return Ok(data.api_keys.len() < before);
+    }
+
+    async fn persist_tenant(&self, tenant: &Tenant) -> Result<(), String> {
+        self.lock()?
+            .tenants
+            .insert(tenant.id.clone(), tenant.clone());
+        return Ok(());
+    }
+
+    async fn get_tenant(&self, id: &str) -> Result<Option<Tenant>, String> {
+        return Ok(self.lock()?.tenants.get(id).cloned());
+    }
+
+    async fn get_all_tenants(&self) -> Result<Vec<Tenant>, String> {
+        return Ok(self.lock()?.tenants.values().cloned().collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dispatcher::DeliveryOutcome,
+        notifications::{ButtonAction, NotificationBuilder},
+        recipients::Recipient,
+        storage::delete_history,
+    };
+
+    fn notification(tenant: &str) -> Notification {
+        return NotificationBuilder::new()
+            .tenant(tenant.to_string())
+            .text("hello".to_string())
+            .build();
+    }
+
+    fn delivery(id: &str, notification: &Notification, attempt: u32) -> DeliveryRecord {
+        return DeliveryRecord {
+            id: id.to_string(),
+            tenant: notification.tenant.clone(),
+            notification_id: notification.uuid.clone(),
+            attempt,
+            platform: notification.platform.clone(),
+            recipient: notification.send_to.clone(),
+            outcome: DeliveryOutcome::Sent,
+            error: None,
+            provider_message_ids: vec![attempt.to_string()],
+            attempted_at: chrono::Utc::now().to_rfc3339(),
+            actions: Vec::new(),
+            attachment_messages: 0,
+            buttons: Vec::new(),
+        };
+    }
+
+    #[tokio::test]
+    async fn notifications_round_trip_within_tenant() {
+        let store = MemoryStore::new();
+        let mut n = notification("acme");
+        store.persist_notification(&n).await.unwrap();
+
+        assert_eq!(
+            store.get_notification("acme", &n.uuid).await.unwrap().text,
+            "hello"
+        );
+        assert!(store.exists("acme", &n.uuid).await.unwrap());
+        assert!(!store.exists("default", &n.uuid).await.unwrap());
+        assert!(store.get_notification("default", &n.uuid).await.is_err());
+
+        n.text = "updated".to_string();
+        store.persist_notification(&n).await.unwrap();
+        let all = store.get_all_notifications("acme").await.unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].text, "updated");
+
+        store.delete_notification("acme", &n.uuid).await.unwrap();
+        assert!(!store.exists("acme", &n.uuid).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn dead_letters_round_trip() {
+        let store = MemoryStore::new();
+        let dead_letter = DeadLetter {
+            id: "dl".to_string(),
+            notification: notification("default"),
+            error: "failed".to_string(),
+            attempts: 3,
+            failed_at: chrono::Utc::now().to_rfc3339(),
+        };
+        store.persist_dead_letter(&dead_letter).await.unwrap();
+
+        let saved = store
+            .get_dead_letter("default", "dl")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(saved.attempts, 3);
+        assert!(store.get_dead_letter("acme", "dl").await.unwrap().is_none());
+        assert_eq!(
+            store.get_all_dead_letters("default").await.unwrap().len(),
+            1
+        );
+
+        assert!(store.delete_dead_letter("default", "dl").await.unwrap());
+        assert!(!store.delete_dead_letter("default", "dl").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn deliveries_are_paged_newest_first() {
+        let store = MemoryStore::new();
+        let n = notification("default");
+        for attempt in 1..=5 {
+            store
+                .persist_delivery(&delivery(&format!("d{}", attempt), &n, attempt))
+                .await
+                .unwrap();
+        }
+
+        let (page, total) = store
+            .get_deliveries("default", &n.uuid, 0, 2)
+            .await
+            .unwrap();
+        assert_eq!(total, 5);
+        assert_eq!(
+            page.iter().map(|d| d.attempt).collect::<Vec<_>>(),
+            vec![5, 4]
+        );
+
+        let (page, _) = store
+            .get_deliveries("default", &n.uuid, 4, 2)
+            .await
+            .unwrap();
+        assert_eq!(page.iter().map(|d| d.attempt).collect::<Vec<_>>(), vec![1]);
+
+        let (page, total) = store.get_deliveries("acme", &n.uuid, 0, 10).await.unwrap();
+        assert!(page.is_empty());
+        assert_eq!(total, 0);
+    }
+
+    #[tokio::test]
+    async fn delivery_update_keeps_its_place() {
+        let store = MemoryStore::new();
+        let n = notification("default");
+        let mut first = delivery("d1", &n, 1);
+        assert!(store.update_delivery(&first).await.is_err());
+
+        store.persist_delivery(&first).await.unwrap();
+        store
+            .persist_delivery(&delivery("d2", &n, 2))
+            .await
+            .unwrap();
+
+        first.error = Some("edited".to_string());
+        store.update_delivery(&first).await.unwrap();
+
+        let saved = store.get_delivery("default", "d1").await.unwrap().unwrap();
+        assert_eq!(saved.error.as_deref(), Some("edited"));
+        assert!(store.get_delivery("acme", "d1").await.unwrap().is_none());
+
+        let (page, total) = store
+            .get_deliveries("default", &n.uuid, 0, 10)
+            .await
+            .unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(page[1].id, "d1");
+    }
+
+    #[tokio::test]
+    async fn deliveries_are_deleted_per_notification() {
+        let store = MemoryStore::new();
+        let n = notification("default");
+        let other = notification("default");
+        store
+            .persist_delivery(&delivery("d1", &n, 1))
+            .await
+            .unwrap();
+        store
+            .persist_delivery(&delivery("d2", &other, 1))
+            .await
+            .unwrap();
+
+        store.delete_deliveries("default", &n.uuid).await.unwrap();
+
+        let (_, total) = store
+            .get_deliveries("default", &n.uuid, 0, 10)
+            .await
+            .unwrap();
+        assert_eq!(total, 0);
+        let (_, total) = store
+            .get_deliveries("default", &other.uuid, 0, 10)
+            .await
+            .unwrap();
+        assert_eq!(total, 1);
+    }
+
+    #[tokio::test]
+    async fn subscriptions_round_trip() {
+        let store = MemoryStore::new();
+        let subscription = Subscription {
+            id: "s".to_string(),
+            tenant: "default".to_string(),
+            topic: "releases".to_string(),
+            chat_id: -100,
+            thread_id: Some(7),
+            user_id: Some(1),
+            username: None,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+        store.persist_subscription(&subscription).await.unwrap();
+
+        let all = store.get_all_subscriptions("default").await.unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(
+            all[0].recipient(),
+            Recipient::Telegram {
+                chat_id: -100,
+                thread_id: Some(7)
+            }
+        );
+        assert!(
+            store
+                .get_all_subscriptions("acme")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        assert!(store.delete_subscription("default", "s").await.unwrap());
+        assert!(!store.delete_subscription("default", "s").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn history_is_deleted_with_notification() {
+        let store = MemoryStore::new();
+        let deleted = notification("default");
+        let kept = notification("default");
+
+        for (i, n) in [&deleted, &kept].into_iter().enumerate() {
+            store
+                .persist_delivery(&delivery(&format!("d{}", i), n, 1))
+                .await
+                .unwrap();
+            store
+                .persist_button_press(&ButtonPress {
+                    id: format!("p{}", i),
+                    tenant: n.tenant.clone(),
+                    notification_id: n.uuid.clone(),
+                    action: ButtonAction::Ack,
+                    button_text: None,
+                    user_id: 1,
+                    username: None,
+                    chat_id: None,
+                    message_id: None,
+                    snoozed_notification_id: None,
+                    pressed_at: chrono::Utc::now().to_rfc3339(),
+                })
+                .await
+                .unwrap();
+            store
+                .persist_dead_letter(&DeadLetter {
+                    id: format!("dl{}", i),
+                    notification: n.clone(),
+                    error: "failed".to_string(),
+                    attempts: 1,
+                    failed_at: chrono::Utc::now().to_rfc3339(),
+                })
+                .await
+                .unwrap();
+        }
+
+        delete_history(&store, "default", &deleted.uuid).await;
+
+        let (deliveries, _) = store
+            .get_deliveries("default", &deleted.uuid, 0, 10)
+            .await
+            .unwrap();
+        assert!(deliveries.is_empty());
+        assert!(
+            store
+                .get_button_presses("default", &deleted.uuid)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let dead_letters = store.get_all_dead_letters("default").await.unwrap();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].notification.uuid, kept.uuid);
+        assert_eq!(
+            store
+                .get_button_presses("default", &kept.uuid)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use async_trait::async_trait;
+use std::{env, sync::Arc};
+
+use crate::{
+    AppMode,
+    attachments::Attachment,
+    auth::ApiKey,
+    bot::{ButtonPress, Subscription},
+    dispatcher::{DeadLetter, DeliveryRecord},
+    notifications::Notification,
+    templates::Template,
+    tenants::Tenant,
+};
+
+pub mod memory;
+pub mod redis_json;
+pub mod sqlite;
+pub use memory::MemoryStore;
+pub use redis_json::RedisStore;
+pub use sqlite::SqliteStore;
+
+const DEFAULT_SQLITE_PATH: &str = "notificator.db";
+
+// Everything except api keys is partitioned by tenant. Records are
+// persisted under the tenant they carry, other methods take it explicitly
+#[async_trait]
+pub trait NotificationStore: Send + Sync {
+    async fn persist_notification(&self, notification: &Notification) -> Result<(), String>;
+    // Err if notification doesn't exist
+    async fn get_notification(&self, tenant: &str, key: &str) -> Result<Notification, String>;
+    async fn get_all_notifications(&self, tenant: &str) -> Result<Vec<Notification>, String>;
+    async fn delete_notification(&self, tenant: &str, key: &str) -> Result<(), String>;
+    async fn exists(&self, tenant: &str, key: &str) -> Result<bool, String>;
+
+    async fn persist_dead_letter(&self, dead_letter: &DeadLetter) -> Result<(), String>;
+    async fn get_dead_letter(&self, tenant: &str, id: &str) -> Result<Option<DeadLetter>, String>;
+    async fn get_all_dead_letters(&self, tenant: &str) -> Result<Vec<DeadLetter>, String>;
+    // Returns false if there was no such dead letter
+    async fn delete_dead_letter(&self, tenant: &str, id: &str) -> Result<bool, String>;
+
+    async fn persist_delivery(&self, delivery: &DeliveryRecord) -> Result<(), String>;
+    // Page of notification deliveries, newest first, along with their total count
+    async fn get_deliveries(
+        &self,
+        tenant: &str,
+        notification_id: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<DeliveryRecord>, usize), String>;
+    async fn get_delivery(&self, tenant: &str, id: &str) -> Result<Option<DeliveryRecord>, String>;
+    // Replaces saved delivery, keeping it's place in history
+    async fn update_delivery(&self, delivery: &DeliveryRecord) -> Result<(), String>;
+    // Drops delivery history of notification
+    async fn delete_deliveries(&self, tenant: &str, notification_id: &str) -> Result<(), String>;
+
+    async fn persist_button_press(&self, press: &ButtonPress) -> Result<(), String>;
+    // Presses of notification buttons, newest first
+    async fn get_button_presses(
+        &self,
+        tenant: &str,
+        notification_id: &str,
+    ) -> Result<Vec<ButtonPress>, String>;
+    async fn delete_button_presses(
+        &self,
+        tenant: &str,
+        notification_id: &str,
+    ) -> Result<(), String>;
+
+    async fn persist_subscription(&self, subscription: &Subscription) -> Result<(), String>;
+    async fn get_all_subscriptions(&self, tenant: &str) -> Result<Vec<Subscription>, String>;
+    // Returns false if there was no such subscription
+    async fn delete_subscription(&self, tenant: &str, id: &str) -> Result<bool, String>;
+
+    async fn persist_template(&self, template: &Template) -> Result<(), String>;
+    async fn get_template(&self, tenant: &str, id: &str) -> Result<Option<Template>, String>;
+    async fn get_all_templates(&self, tenant: &str) -> Result<Vec<Template>, String>;
+    // Returns false if there was no such template
+    async fn delete_template(&self, tenant: &str, id: &str) -> Result<bool, String>;
+
+    // Contents are kept apart from metadata, so listing doesn't load them
+    async fn persist_attachment(&self, attachment: &Attachment, data: &[u8]) -> Result<(), String>;
+    async fn get_attachment(&self, tenant: &str, id: &str) -> Result<Option<Attachment>, String>;
+    async fn get_attachment_data(&self, tenant: &str, id: &str) -> Result<Option<Vec<u8>>, String>;
+    async fn get_all_attachments(&self, tenant: &str) -> Result<Vec<Attachment>, String>;
+    // Returns false if there was no such attachment
+    async fn delete_attachment(&self, tenant: &str, id: &str) -> Result<bool, String>;
+
+    async fn persist_api_key(&self, api_key: &ApiKey) -> Result<(), String>;
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, String>;
+    async fn get_all_api_keys(&self) -> Result<Vec<ApiKey>, String>;
+    // Returns false if there was no such key
+    async fn delete_api_key(&self, id: &str) -> Result<bool, String>;
+
+    async fn persist_tenant(&self, tenant: &Tenant) -> Result<(), String>;
+    async fn get_tenant(&self, id: &str) -> Result<Option<Tenant>, String>;
+    // Registered tenants. Default one is there only if it was configured
+    async fn get_all_tenants(&self) -> Result<Vec<Tenant>, String>;
+}
+
+// Picks storage backend by STORAGE_BACKEND env: "redis" (default), "sqlite" or "memory"
+// Drops delivery history, button presses and dead letters of deleted
+// notification. Best effort, the notification itself is already gone
+pub async fn delete_history(storage: &dyn NotificationStore, tenant: &str, notification_id: &str) {
+    if let Err(e) = storage.delete_deliveries(tenant, notification_id).await {
+        tracing::error!(
+            "failed to delete deliveries of notification {}: {}",
+            notification_id,
+            e
+        );
+    }
+
+    if let Err(e) = storage.delete_button_presses(tenant, notification_id).await {
+        tracing::error!(
+            "failed to delete button presses of notification {}: {}",
+            notification_id,
+            e
+        );
+    }
+
+    let dead_letters = match storage.get_all_dead_letters(tenant).await {
+        Ok(d) => d,
+        Err(e) => {
+            tracing::error!("failed to get dead letters: {}", e);
+            return;
+        }
+    };
+
+    for dead_letter in dead_letters
+        .iter()
+        .filter(|d| d.notification.uuid == notification_id)
+    {
+        if let Err(e) = storage.delete_dead_letter(tenant, &dead_letter.id).await {
+            tracing::error!("failed to delete dead letter {}: {}", dead_letter.id, e);
+        }
+    }
+}
+
+pub async fn from_env(mode: &AppMode) -> Result<Arc<dyn NotificationStore>, String> {
+    let backend = env::var("STORAGE_BACKEND").unwrap_or("redis".to_string());
+
+    return match backend.trim().to_lowercase().as_str() {
+        "redis" => Ok(Arc::new(RedisStore::new(mode).await?)),
+        "sqlite" => {
+            let path = env::var("SQLITE_PATH").unwrap_or(DEFAULT_SQLITE_PATH.to_string());
+            tracing::info!("using sqlite storage at {}", path);
+            Ok(Arc::new(SqliteStore::open(&path)?))
+        }
+        "memory" => {
+            tracing::warn!("using in-memory storage, notifications are lost on restart");
+            Ok(Arc::new(MemoryStore::new()))
+        }
+        _ => Err(format!(
+            "invalid STORAGE_BACKEND: \"{}\". Expected \"redis\", \"sqlite\" or \"memory\"",
+            backend
+        )),
+    };
+}
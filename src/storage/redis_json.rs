@@ -0,0 +1,920 @@
+use async_trait::async_trait;
+use redis::{
+    AsyncCommands, JsonAsyncCommands,
+    aio::{ConnectionManager, ConnectionManagerConfig},
+};
+use serde_json::Value;
+use std::{env, time::Duration};
+use uuid::Uuid;
+
+use crate::{
+    AppMode,
+    attachments::Attachment,
+    auth::ApiKey,
+    bot::{ButtonPress, Subscription},
+    dispatcher::{DeadLetter, DeliveryRecord},
+    notifications::{JSON_NOTIFICATION_KEY, Notification},
+    storage::NotificationStore,
+    templates::Template,
+    tenants::{DEFAULT_TENANT, Tenant},
+};
+
+const DEFAULT_KEY_PREFIX: &str = "notificator";
+
+// Set in redis once legacy keys are moved under the prefix
+const LEGACY_MIGRATION_MARKER: &str = "migrations:legacy_keys";
+
+// Number of notifications fetched by a single pipeline on bulk loads
+const LOAD_BATCH_SIZE: usize = 100;
+
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+// Reconnection attempts, made with exponential backoff
+const RECONNECT_RETRIES: usize = 5;
+
+/*
+    All keys live under a configurable prefix, "notificator" by default.
+    Keys of tenants other than the default one are put under <prefix>:tenant:<id>:
+    - <prefix>:ntf:<uuid> - notification JSON (RedisJSON)
+    - <prefix>:ntf_ids - set of uuids of all stored notifications
+    - <prefix>:dead_letters - hash of failed deliveries: dead letter id -> JSON
+    - <prefix>:delivery:<id> - delivery record JSON
+    - <prefix>:deliveries:<uuid> - list of notification delivery ids, newest first
+    - <prefix>:presses:<uuid> - list of notification button press JSONs, newest first
+    - <prefix>:subscriptions - hash of topic subscriptions made with the bot: id -> JSON
+    - <prefix>:templates - hash of message templates: template id -> JSON
+    - <prefix>:attachments - hash of uploaded files: attachment id -> metadata JSON
+    - <prefix>:attachment_data:<id> - contents of uploaded file
+    Shared by all tenants:
+    - <prefix>:api_keys - hash of api keys: key hash -> JSON
+    - <prefix>:tenants - hash of tenants: id -> JSON
+*/
+
+// Requires Redis Stack, notifications are stored with RedisJSON
+// Connection is multiplexed between all callers
+// and reestablished automatically once it breaks
+pub struct RedisStore {
+    conn: ConnectionManager,
+    prefix: String,
+}
+
+fn get_redis_path(mode: &AppMode) -> String {
+    match mode {
+        AppMode::Docker => "redis://redis:6379/".to_string(),
+        AppMode::Native => "redis://127.0.0.1:6379/".to_string(),
+    }
+}
+
+fn get_key_prefix() -> String {
+    let prefix = env::var("REDIS_KEY_PREFIX").unwrap_or(DEFAULT_KEY_PREFIX.to_string());
+    let prefix = prefix.trim().trim_end_matches(':');
+
+    if prefix.is_empty() {
+        panic!("REDIS_KEY_PREFIX can't be empty");
+    }
+
+    return prefix.to_string();
+}
+
+impl RedisStore {
+    pub async fn new(mode: &AppMode) -> Result<Self, String> {
+        let redis_path = get_redis_path(mode);
+        let client = redis::Client::open(redis_path)
+            .map_err(|e| format!("invalid redis connection string: {}", e))?;
+
+        let config = ConnectionManagerConfig::new()
+            .set_connection_timeout(CONNECTION_TIMEOUT)
+            .set_response_timeout(RESPONSE_TIMEOUT)
+            .set_number_of_retries(RECONNECT_RETRIES);
+        let conn = client
+            .get_connection_manager_with_config(config)
+            .await
+            .map_err(|e| format!("failed to connect to redis: {}", e))?;
+
+        let store = RedisStore {
+            conn,
+            prefix: get_key_prefix(),
+        };
+
+        // Not fatal: notifications under legacy keys just stay invisible
+        // until the next start
+        if let Err(e) = store.migrate_legacy_keys().await {
+            tracing::error!("failed to migrate legacy redis keys: {}", e);
+        }
+
+        return Ok(store);
+    }
+
+    // Cheap, clones share the same connection
+    fn get_conn(&self) -> ConnectionManager {
+        return self.conn.clone();
+    }
+
+    fn key(&self, name: &str) -> String {
+        return format!("{}:{}", self.prefix, name);
+    }
+
+    // Default tenant keeps keys it had before tenants were introduced
+    fn tenant_key(&self, tenant: &str, name: &str) -> String {
+        if tenant == DEFAULT_TENANT {
+            return self.key(name);
+        }
+
+        return self.key(&format!("tenant:{}:{}", tenant, name));
+    }
+
+    fn notification_key(&self, tenant: &str, uuid: &str) -> String {
+        return self.tenant_key(tenant, &format!("ntf:{}", uuid));
+    }
+
+    fn notification_ids_key(&self, tenant: &str) -> String {
+        return self.tenant_key(tenant, "ntf_ids");
+    }
+
+    fn dead_letters_key(&self, tenant: &str) -> String {
+        return self.tenant_key(tenant, "dead_letters");
+    }
+
+    fn delivery_key(&self, tenant: &str, id: &str) -> String {
+        return self.tenant_key(tenant, &format!("delivery:{}", id));
+    }
+
+    fn deliveries_key(&self, tenant: &str, notification_id: &str) -> String {
+        return self.tenant_key(tenant, &format!("deliveries:{}", notification_id));
+    }
+
+    fn presses_key(&self, tenant: &str, notification_id: &str) -> String {
+        return self.tenant_key(tenant, &format!("presses:{}", notification_id));
+    }
+
+    fn subscriptions_key(&self, tenant: &str) -> String {
+        return self.tenant_key(tenant, "subscriptions");
+    }
+
+    fn templates_key(&self, tenant: &str) -> String {
+        return self.tenant_key(tenant, "templates");
+    }
+
+    fn attachments_key(&self, tenant: &str) -> String {
+        return self.tenant_key(tenant, "attachments");
+    }
+
+    fn attachment_data_key(&self, tenant: &str, id: &str) -> String {
+        return self.tenant_key(tenant, &format!("attachment_data:{}", id));
+    }
+
+    fn api_keys_key(&self) -> String {
+        return self.key("api_keys");
+    }
+
+    fn tenants_key(&self) -> String {
+        return self.key("tenants");
+    }
+
+    // Older versions kept notifications at bare uuid keys. Moves them under
+    // the prefix once, existing keys are never overwritten. Keys of other
+    // apps sharing the instance are left alone
+    async fn migrate_legacy_keys(&self) -> Result<(), String> {
+        let mut con = self.get_conn();
+        let marker = self.key(LEGACY_MIGRATION_MARKER);
+
+        let migrated: bool = con
+            .exists(&marker)
+            .await
+            .map_err(|e| format!("Failed to check migration marker: {}", e))?;
+        if migrated {
+            return Ok(());
+        }
+
+        // Collected first, since connection is busy while iterating
+        let mut keys: Vec<String> = Vec::new();
+        let mut iter = con
+            .scan()
+            .await
+            .map_err(|e| format!("Failed to scan keys: {}", e))?;
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+        drop(iter);
+
+        let mut moved = 0;
+        for key in keys {
+            if Uuid::try_parse(&key).is_err() {
+                continue;
+            }
+            let key_type: String = con
+                .key_type(&key)
+                .await
+                .map_err(|e| format!("Failed to get type of key {}: {}", key, e))?;
+            if key_type != "ReJSON-RL" {
+                continue;
+            }
+            let target = self.notification_key(DEFAULT_TENANT, &key);
+
+            let renamed: bool = con
+                .rename_nx(&key, &target)
+                .await
+                .map_err(|e| format!("Failed to move key {}: {}", key, e))?;
+            if !renamed {
+                tracing::warn!("not migrating key {}: {} already exists", key, target);
+                continue;
+            }
+
+            con.sadd::<_, _, ()>(self.notification_ids_key(DEFAULT_TENANT), &key)
+                .await
+                .map_err(|e| format!("Failed to index notification {}: {}", key, e))?;
+            moved += 1;
+        }
+
+        con.set::<_, _, ()>(&marker, chrono::Utc::now().to_rfc3339())
+            .await
+            .map_err(|e| format!("Failed to set migration marker: {}", e))?;
+
+        if moved > 0 {
+            tracing::info!(
+                "moved {} legacy redis keys under \"{}\"",
+                moved,
+                self.prefix
+            );
+        }
+
+        return Ok(());
+    }
+}
+
+// JSON.GET with "$" path wraps the value into array
+fn parse_notification(json: &str) -> Result<Notification, String> {
+    let value: Value =
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+    let obj = if value.is_array() {
+        value
+            .as_array()
+            .and_then(|arr| arr.first())
+            .ok_or_else(|| "Invalid JSON structure".to_string())?
+    } else {
+        &value
+    };
+
+    return serde_json::from_value(obj.clone())
+        .map_err(|e| format!("Failed to deserialize JSON: {}", e));
+}
+
+#[async_trait]
+impl NotificationStore for RedisStore {
+    async fn persist_notification(&self, notification: &Notification) -> Result<(), String> {
+        let mut con = self.get_conn();
+
+        redis::pipe()
+            .atomic()
+            .json_set(
+                self.notification_key(&notification.tenant, &notification.uuid),
+                JSON_NOTIFICATION_KEY,
+                notification,
+            )
+            .map_err(|e| format!("Failed to serialize notification: {}", e))?
+            .ignore()
+            .sadd(
+                self.notification_ids_key(&notification.tenant),
+                &notification.uuid,
+            )
+            .ignore()
+            .query_async::<()>(&mut con)
+            .await
+            .map_err(|e| format!("Failed to set JSON value: {}", e))?;
+
+        return Ok(());
+    }
+
+    async fn get_notification(&self, tenant: &str, key: &str) -> Result<Notification, String> {
+        let mut con = self.get_conn();
+
+        let result: Option<String> = con
+            .json_get(self.notification_key(tenant, key), JSON_NOTIFICATION_KEY)
+            .await
+            .map_err(|e| format!("Failed to get JSON value: {}", e))?;
+
+        return match result {
+            Some(json) => parse_notification(&json),
+            None => Err(format!("Key '{}' not found", key)),
+        };
+    }
+
+    async fn get_all_notifications(&self, tenant: &str) -> Result<Vec<Notification>, String> {
+        let mut con = self.get_conn();
+
+        let mut ids: Vec<String> = Vec::new();
+        let mut iter = con
+            .sscan(self.notification_ids_key(tenant))
+            .await
+            .map_err(|e| format!("Failed to scan notification ids: {}", e))?;
+        while let Some(id) = iter.next_item().await {
+            ids.push(id);
+        }
+        drop(iter);
+
+        let mut notifications = Vec::new();
+        let mut dangling = Vec::new();
+
+        for batch in ids.chunks(LOAD_BATCH_SIZE) {
+            let mut pipe = redis::pipe();
+            for id in batch {
+                pipe.json_get(self.notification_key(tenant, id), JSON_NOTIFICATION_KEY)
+                    .map_err(|e| format!("Failed to build JSON.GET: {}", e))?;
+            }
+
+            let values: Vec<Option<String>> = pipe
+                .query_async(&mut con)
+                .await
+                .map_err(|e| format!("Failed to get notifications: {}", e))?;
+
+            for (id, value) in batch.iter().zip(values) {
+                match value.map(|json| parse_notification(&json)) {
+                    Some(Ok(notification)) => notifications.push(notification),
+                    Some(Err(e)) => {
+                        tracing::error!("Failed to get notification for key {}: {}", id, e)
+                    }
+                    None => dangling.push(id.clone()),
+                }
+            }
+        }
+
+        // Index entries whose notification is gone, e.g. deleted by hand
+        if !dangling.is_empty() {
+            con.srem::<_, _, ()>(self.notification_ids_key(tenant), &dangling)
+                .await
+                .map_err(|e| format!("Failed to clean up notification ids: {}", e))?;
+        }
+
+        Ok(notifications)
+    }
+
+    async fn delete_notification(&self, tenant: &str, key: &str) -> Result<(), String> {
+        let mut con = self.get_conn();
+
+        redis::pipe()
+            .atomic()
+            .del(self.notification_key(tenant, key))
+            .ignore()
+            .srem(self.notification_ids_key(tenant), key)
+            .ignore()
+            .query_async::<()>(&mut con)
+            .await
+            .map_err(|e| format!("Failed to delete key: {}", e))?;
+
+        return Ok(());
+    }
+
+    async fn exists(&self, tenant: &str, key: &str) -> Result<bool, String> {
+        let mut con = self.get_conn();
+
+        return con
+            .exists(self.notification_key(tenant, key))
+            .await
+            .map_err(|e| format!("Failed to check key existence: {}", e));
+    }
+
+    async fn persist_dead_letter(&self, dead_letter: &DeadLetter) -> Result<(), String> {
+        let mut con = self.get_conn();
+        let json = serde_json::to_string(dead_letter)
+            .map_err(|e| format!("Failed to serialize dead letter: {}", e))?;
+
+        con.hset::<_, _, _, ()>(
+            self.dead_letters_key(&dead_letter.notification.tenant),
+            &dead_letter.id,
+            json,
+        )
+        .await
+        .map_err(|e| format!("Failed to save dead letter: {}", e))?;
+
+        return Ok(());
+    }
+
+    async fn get_dead_letter(&self, tenant: &str, id: &str) -> Result<Option<DeadLetter>, String> {
+        let mut con = self.get_conn();
+
+        let json: Option<String> = con
+            .hget(self.dead_letters_key(tenant), id)
+            .await
+            .map_err(|e| format!("Failed to get dead letter: {}", e))?;
+
+        return match json {
+            Some(j) => serde_json::from_str(&j)
+                .map(Some)
+                .map_err(|e| format!("Failed to deserialize dead letter: {}", e)),
+            None => Ok(None),
+        };
+    }
+
+    async fn get_all_dead_letters(&self, tenant: &str) -> Result<Vec<DeadLetter>, String> {
+        let mut con = self.get_conn();
+
+        let values: Vec<String> = con
+            .hvals(self.dead_letters_key(tenant))
+            .await
+            .map_err(|e| format!("Failed to get dead letters: {}", e))?;
+
+        let mut dead_letters = Vec::new();
+        for value in values {
+            match serde_json::from_str(&value) {
+                Ok(d) => dead_letters.push(d),
+                Err(e) => tracing::error!("Failed to deserialize dead letter: {}", e),
+            }
+        }
+
+        return Ok(dead_letters);
+    }
+
+    // Returns false if there was no such dead letter
+    async fn delete_dead_letter(&self, tenant: &str, id: &str) -> Result<bool, String> {
+        let mut con = self.get_conn();
+
+        let removed: i64 = con
+            .hdel(self.dead_letters_key(tenant), id)
+            .await
+            .map_err(|e| format!("Failed to delete dead letter: {}", e))?;
+
+        return Ok(removed > 0);
+    }
+
+    async fn persist_delivery(&self, delivery: &DeliveryRecord) -> Result<(), String> {
+        let mut con = self.get_conn();
+        let json = serde_json::to_string(delivery)
+            .map_err(|e| format!("Failed to serialize delivery: {}", e))?;
+
+        redis::pipe()
+            .atomic()
+            .set(self.delivery_key(&delivery.tenant, &delivery.id), json)
+            .ignore()
+            .lpush(
+                self.deliveries_key(&delivery.tenant, &delivery.notification_id),
+                &delivery.id,
+            )
+            .ignore()
+            .query_async::<()>(&mut con)
+            .await
+            .map_err(|e| format!("Failed to save delivery: {}", e))?;
+
+        return Ok(());
+    }
+
+    // Page of notification deliveries, newest first, along with their total count
+    async fn get_deliveries(
+        &self,
+        tenant: &str,
+        notification_id: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<DeliveryRecord>, usize), String> {
+        let mut con = self.get_conn();
+        let list_key = self.deliveries_key(tenant, notification_id);
+
+        let total: usize = con
+            .llen(&list_key)
+            .await
+            .map_err(|e| format!("Failed to count deliveries: {}", e))?;
+
+        if limit == 0 || offset >= total {
+            return Ok((Vec::new(), total));
+        }
+
+        let ids: Vec<String> = con
+            .lrange(&list_key, offset as isize, (offset + limit - 1) as isize)
+            .await
+            .map_err(|e| format!("Failed to get delivery ids: {}", e))?;
+
+        let mut pipe = redis::pipe();
+        for id in &ids {
+            pipe.get(self.delivery_key(tenant, id));
+        }
+
+        let values: Vec<Option<String>> = pipe
+            .query_async(&mut con)
+            .await
+            .map_err(|e| format!("Failed to get deliveries: {}", e))?;
+
+        let mut deliveries = Vec::new();
+        for value in values.into_iter().flatten() {
+            match serde_json::from_str(&value) {
+                Ok(d) => deliveries.push(d),
+                Err(e) => tracing::error!("Failed to deserialize delivery: {}", e),
+            }
+        }
+
+        return Ok((deliveries, total));
+    }
+
+    async fn get_delivery(&self, tenant: &str, id: &str) -> Result<Option<DeliveryRecord>, String> {
+        let mut con = self.get_conn();
+
+        let json: Option<String> = con
+            .get(self.delivery_key(tenant, id))
+            .await
+            .map_err(|e| format!("Failed to get delivery: {}", e))?;
+
+        return match json {
+            Some(j) => serde_json::from_str(&j)
+                .map(Some)
+                .map_err(|e| format!("Failed to deserialize delivery: {}", e)),
+            None => Ok(None),
+        };
+    }
+
+    async fn update_delivery(&self, delivery: &DeliveryRecord) -> Result<(), String> {
+        let mut con = self.get_conn();
+        let json = serde_json::to_string(delivery)
+            .map_err(|e| format!("Failed to serialize delivery: {}", e))?;
+
+        // Only existing record is replaced, history list already has it
+        let updated: bool = redis::cmd("SET")
+            .arg(self.delivery_key(&delivery.tenant, &delivery.id))
+            .arg(json)
+            .arg("XX")
+            .query_async::<Option<String>>(&mut con)
+            .await
+            .map(|r| r.is_some())
+            .map_err(|e| format!("Failed to save delivery: {}", e))?;
+
+        if !updated {
+            return Err(format!("Delivery {} not found", delivery.id));
+        }
+
+        return Ok(());
+    }
+
+    async fn delete_deliveries(&self, tenant: &str, notification_id: &str) -> Result<(), String> {
+        let mut con = self.get_conn();
+        let list_key = self.deliveries_key(tenant, notification_id);
+
+        let ids: Vec<String> = con
+            .lrange(&list_key, 0, -1)
+            .await
+            .map_err(|e| format!("Failed to get delivery ids: {}", e))?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic().del(&list_key).ignore();
+        for id in &ids {
+            pipe.del(self.delivery_key(tenant, id)).ignore();
+        }
+        pipe.query_async::<()>(&mut con)
+            .await
+            .map_err(|e| format!("Failed to delete deliveries: {}", e))?;
+
+        return Ok(());
+    }
+
+    async fn persist_button_press(&self, press: &ButtonPress) -> Result<(), String> {
+        let mut con = self.get_conn();
+        let json = serde_json::to_string(press)
+            .map_err(|e| format!("Failed to serialize button press: {}", e))?;
+
+        con.lpush::<_, _, ()>(
+            self.presses_key(&press.tenant, &press.notification_id),
+            json,
+        )
+        .await
+        .map_err(|e| format!("Failed to save button press: {}", e))?;
+
+        return Ok(());
+    }
+
+    async fn get_button_presses(
+        &self,
+        tenant: &str,
+        notification_id: &str,
+    ) -> Result<Vec<ButtonPress>, String> {
+        let mut con = self.get_conn();
+
+        let values: Vec<String> = con
+            .lrange(self.presses_key(tenant, notification_id), 0, -1)
+            .await
+            .map_err(|e| format!("Failed to get button presses: {}", e))?;
+
+        let mut presses = Vec::new();
+        for value in values {
+            match serde_json::from_str(&value) {
+                Ok(p) => presses.push(p),
+                Err(e) => tracing::error!("Failed to deserialize button press: {}", e),
+            }
+        }
+
+        return Ok(presses);
+    }
+
+    async fn delete_button_presses(
+        &self,
+        tenant: &str,
+        notification_id: &str,
+    ) -> Result<(), String> {
+        let mut con = self.get_conn();
+
+        con.del::<_, ()>(self.presses_key(tenant, notification_id))
+            .await
+            .map_err(|e| format!("Failed to delete button presses: {}", e))?;
+
+        return Ok(());
+    }
+
+    async fn persist_subscription(&self, subscription: &Subscription) -> Result<(), String> {
+        let mut con = self.get_conn();
+        let json = serde_json::to_string(subscription)
+            .map_err(|e| format!("Failed to serialize subscription: {}", e))?;
+
+        con.hset::<_, _, _, ()>(
+            self.subscriptions_key(&subscription.tenant),
+            &subscription.id,
+            json,
+        )
+        .await
+        .map_err(|e| format!("Failed to save subscription: {}", e))?;
+
+        return Ok(());
+    }
+
+    async fn get_all_subscriptions(&self, tenant: &str) -> Result<Vec<Subscription>, String> {
+        let mut con = self.get_conn();
+
+        let values: Vec<String> = con
+            .hvals(self.subscriptions_key(tenant))
+            .await
+            .map_err(|e| format!("Failed to get subscriptions: {}", e))?;
+
+        let mut subscriptions = Vec::new();
+        for value in values {
+            match serde_json::from_str(&value) {
+                Ok(s) => subscriptions.push(s),
+                Err(e) => tracing::error!("Failed to deserialize subscription: {}", e),
+            }
+        }
+
+        return Ok(subscriptions);
+    }
+
+    async fn delete_subscription(&self, tenant: &str, id: &str) -> Result<bool, String> {
+        let mut con = self.get_conn();
+
+        let removed: i64 = con
+            .hdel(self.subscriptions_key(tenant), id)
+            .await
+            .map_err(|e| format!("Failed to delete subscription: {}", e))?;
+
+        return Ok(removed > 0);
+    }
+
+    async fn persist_template(&self, template: &Template) -> Result<(), String> {
+        let mut con = self.get_conn();
+        let json = serde_json::to_string(template)
+            .map_err(|e| format!("Failed to serialize template: {}", e))?;
+
+        con.hset::<_, _, _, ()>(self.templates_key(&template.tenant), &template.id, json)
+            .await
+            .map_err(|e| format!("Failed to save template: {}", e))?;
+
+        return Ok(());
+    }
+
+    async fn get_template(&self, tenant: &str, id: &str) -> Result<Option<Template>, String> {
+        let mut con = self.get_conn();
+
+        let json: Option<String> = con
+            .hget(self.templates_key(tenant), id)
+            .await
+            .map_err(|e| format!("Failed to get template: {}", e))?;
+
+        return match json {
+            Some(j) => serde_json::from_str(&j)
+                .map(Some)
+                .map_err(|e| format!("Failed to deserialize template: {}", e)),
+            None => Ok(None),
+        };
+    }
+
+    async fn get_all_templates(&self, tenant: &str) -> Result<Vec<Template>, String> {
+        let mut con = self.get_conn();
+
+        let values: Vec<String> = con
+            .hvals(self.templates_key(tenant))
+            .await
+            .map_err(|e| format!("Failed to get templates: {}", e))?;
+
+        let mut templates = Vec::new();
+        for value in values {
+            match serde_json::from_str(&value) {
+                Ok(t) => templates.push(t),
+                Err(e) => tracing::error!("Failed to deserialize template: {}", e),
+            }
+        }
+
+        return Ok(templates);
+    }
+
+    async fn delete_template(&self, tenant: &str, id: &str) -> Result<bool, String> {
+        let mut con = self.get_conn();
+
+        let removed: i64 = con
+            .hdel(self.templates_key(tenant), id)
+            .await
+            .map_err(|e| format!("Failed to delete template: {}", e))?;
+
+        return Ok(removed > 0);
+    }
+
+    async fn persist_attachment(&self, attachment: &Attachment, data: &[u8]) -> Result<(), String> {
+        let mut con = self.get_conn();
+        let json = serde_json::to_string(attachment)
+            .map_err(|e| format!("Failed to serialize attachment: {}", e))?;
+
+        redis::pipe()
+            .atomic()
+            .set(
+                self.attachment_data_key(&attachment.tenant, &attachment.id),
+                data,
+            )
+            .ignore()
+            .hset(
+                self.attachments_key(&attachment.tenant),
+                &attachment.id,
+                json,
+            )
+            .ignore()
+            .query_async::<()>(&mut con)
+            .await
+            .map_err(|e| format!("Failed to save attachment: {}", e))?;
+
+        return Ok(());
+    }
+
+    async fn get_attachment(&self, tenant: &str, id: &str) -> Result<Option<Attachment>, String> {
+        let mut con = self.get_conn();
+
+        let json: Option<String> = con
+            .hget(self.attachments_key(tenant), id)
+            .await
+            .map_err(|e| format!("Failed to get attachment: {}", e))?;
+
+        return match json {
+            Some(j) => serde_json::from_str(&j)
+                .map(Some)
+                .map_err(|e| format!("Failed to deserialize attachment: {}", e)),
+            None => Ok(None),
+        };
+    }
+
+    async fn get_attachment_data(&self, tenant: &str, id: &str) -> Result<Option<Vec<u8>>, String> {
+        let mut con = self.get_conn();
+
+        return con
+            .get(self.attachment_data_key(tenant, id))
+            .await
+            .map_err(|e| format!("Failed to get attachment contents: {}", e));
+    }
+
+    async fn get_all_attachments(&self, tenant: &str) -> Result<Vec<Attachment>, String> {
+        let mut con = self.get_conn();
+
+        let values: Vec<String> = con
+            .hvals(self.attachments_key(tenant))
+            .await
+            .map_err(|e| format!("Failed to get attachments: {}", e))?;
+
+        let mut attachments = Vec::new();
+        for value in values {
+            match serde_json::from_str(&value) {
+                Ok(a) => attachments.push(a),
+                Err(e) => tracing::error!("Failed to deserialize attachment: {}", e),
+            }
+        }
+
+        return Ok(attachments);
+    }
+
+    async fn delete_attachment(&self, tenant: &str, id: &str) -> Result<bool, String> {
+        let mut con = self.get_conn();
+
+        let (removed,): (i64,) = redis::pipe()
+            .atomic()
+            .hdel(self.attachments_key(tenant), id)
+            .del(self.attachment_data_key(tenant, id))
+            .ignore()
+            .query_async(&mut con)
+            .await
+            .map_err(|e| format!("Failed to delete attachment: {}", e))?;
+
+        return Ok(removed > 0);
+    }
+
+    async fn persist_api_key(&self, api_key: &ApiKey) -> Result<(), String> {
+        let mut con = self.get_conn();
+        let json = serde_json::to_string(api_key)
+            .map_err(|e| format!("Failed to serialize api key: {}", e))?;
+
+        con.hset::<_, _, _, ()>(self.api_keys_key(), &api_key.key_hash, json)
+            .await
+            .map_err(|e| format!("Failed to save api key: {}", e))?;
+
+        return Ok(());
+    }
+
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, String> {
+        let mut con = self.get_conn();
+
+        let json: Option<String> = con
+            .hget(self.api_keys_key(), key_hash)
+            .await
+            .map_err(|e| format!("Failed to get api key: {}", e))?;
+
+        return match json {
+            Some(j) => serde_json::from_str(&j)
+                .map(Some)
+                .map_err(|e| format!("Failed to deserialize api key: {}", e)),
+            None => Ok(None),
+        };
+    }
+
+    async fn get_all_api_keys(&self) -> Result<Vec<ApiKey>, String> {
+        let mut con = self.get_conn();
+
+        let values: Vec<String> = con
+            .hvals(self.api_keys_key())
+            .await
+            .map_err(|e| format!("Failed to get api keys: {}", e))?;
+
+        let mut api_keys = Vec::new();
+        for value in values {
+            match serde_json::from_str(&value) {
+                Ok(k) => api_keys.push(k),
+                Err(e) => tracing::error!("Failed to deserialize api key: {}", e),
+            }
+        }
+
+        return Ok(api_keys);
+    }
+
+    // Keys are stored by hash, so the one to delete is looked up among all
+    async fn delete_api_key(&self, id: &str) -> Result<bool, String> {
+        let key_hash = match self
+            .get_all_api_keys()
+            .await?
+            .into_iter()
+            .find(|k| k.id == id)
+        {
+            Some(k) => k.key_hash,
+            None => return Ok(false),
+        };
+
+        let mut con = self.get_conn();
+        let removed: i64 = con
+            .hdel(self.api_keys_key(), key_hash)
+            .await
+            .map_err(|e| format!("Failed to delete api key: {}", e))?;
+
+        return Ok(removed > 0);
+    }
+
+    async fn persist_tenant(&self, tenant: &Tenant) -> Result<(), String> {
+        let mut con = self.get_conn();
+        let json = serde_json::to_string(tenant)
+            .map_err(|e| format!("Failed to serialize tenant: {}", e))?;
+
+        con.hset::<_, _, _, ()>(self.tenants_key(), &tenant.id, json)
+            .await
+            .map_err(|e| format!("Failed to save tenant: {}", e))?;
+
+        return Ok(());
+    }
+
+    async fn get_tenant(&self, id: &str) -> Result<Option<Tenant>, String> {
+        let mut con = self.get_conn();
+
+        let json: Option<String> = con
+            .hget(self.tenants_key(), id)
+            .await
+            .map_err(|e| format!("Failed to get tenant: {}", e))?;
+
+        return match json {
+            Some(j) => serde_json::from_str(&j)
+                .map(Some)
+                .map_err(|e| format!("Failed to deserialize tenant: {}", e)),
+            None => Ok(None),
+        };
+    }
+
+    async fn get_all_tenants(&self) -> Result<Vec<Tenant>, String> {
+        let mut con = self.get_conn();
+
+        let values: Vec<String> = con
+            .hvals(self.tenants_key())
+            .await
+            .map_err(|e| format!("Failed to get tenants: {}", e))?;
+
+        let mut tenants = Vec::new();
+        for value in values {
+            match serde_json::from_str(&value) {
+                Ok(t) => tenants.push(t),
+                Err(e) => tracing::error!("Failed to deserialize tenant: {}", e),
+            }
+        }
+
+        return Ok(tenants);
+    }
+}
@@ -0,0 +1,785 @@
+use async_trait::async_trait;
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter};
+use serde::{Serialize, de::DeserializeOwned};
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    attachments::Attachment,
+    auth::ApiKey,
+    bot::{ButtonPress, Subscription},
+    dispatcher::{DeadLetter, DeliveryRecord},
+    notifications::Notification,
+    storage::NotificationStore,
+    templates::Template,
+    tenants::Tenant,
+};
+
+// Records are kept as JSON, same as in redis. Only the columns
+// needed for lookups are extracted
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS notifications (
+        uuid TEXT PRIMARY KEY,
+        tenant TEXT NOT NULL DEFAULT 'default',
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS dead_letters (
+        id TEXT PRIMARY KEY,
+        tenant TEXT NOT NULL DEFAULT 'default',
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS deliveries (
+        id TEXT PRIMARY KEY,
+        tenant TEXT NOT NULL DEFAULT 'default',
+        notification_id TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS api_keys (
+        id TEXT PRIMARY KEY,
+        key_hash TEXT NOT NULL UNIQUE,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS tenants (
+        id TEXT PRIMARY KEY,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS templates (
+        id TEXT PRIMARY KEY,
+        tenant TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS attachments (
+        id TEXT PRIMARY KEY,
+        tenant TEXT NOT NULL,
+        data TEXT NOT NULL,
+        content BLOB NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS button_presses (
+        id TEXT PRIMARY KEY,
+        tenant TEXT NOT NULL,
+        notification_id TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS subscriptions (
+        id TEXT PRIMARY KEY,
+        tenant TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+";
+
+// Created after tenant columns are in place
+const INDEXES: &str = "
+    CREATE INDEX IF NOT EXISTS notifications_tenant ON notifications (tenant);
+    CREATE INDEX IF NOT EXISTS dead_letters_tenant ON dead_letters (tenant);
+    CREATE INDEX IF NOT EXISTS templates_tenant ON templates (tenant);
+    CREATE INDEX IF NOT EXISTS attachments_tenant ON attachments (tenant);
+    CREATE INDEX IF NOT EXISTS button_presses_tenant_notification_id
+        ON button_presses (tenant, notification_id);
+    CREATE INDEX IF NOT EXISTS subscriptions_tenant ON subscriptions (tenant);
+    DROP INDEX IF EXISTS deliveries_notification_id;
+    CREATE INDEX IF NOT EXISTS deliveries_tenant_notification_id
+        ON deliveries (tenant, notification_id);
+";
+
+// Tables, which got tenant column after they were first released
+const TENANT_TABLES: [&str; 3] = ["notifications", "dead_letters", "deliveries"];
+
+// Single-file storage for setups without redis.
+// rusqlite is blocking, so queries run on tokio's blocking pool
+pub struct SqliteStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStore {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let conn = Connection::open(path)
+            .map_err(|e| format!("Failed to open sqlite database {}: {}", path, e))?;
+        conn.execute_batch(SCHEMA)
+            .map_err(|e| format!("Failed to create sqlite schema: {}", e))?;
+        add_tenant_columns(&conn)?;
+        conn.execute_batch(INDEXES)
+            .map_err(|e| format!("Failed to create sqlite indexes: {}", e))?;
+
+        return Ok(SqliteStore {
+            conn: Arc::new(Mutex::new(conn)),
+        });
+    }
+
+    async fn with_conn<T, F>(&self, f: F) -> Result<T, String>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> Result<T, String> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+
+        return tokio::task::spawn_blocking(move || {
+            let conn = conn
+                .lock()
+                .map_err(|e| format!("Failed to lock sqlite connection: {}", e))?;
+            f(&conn)
+        })
+        .await
+        .map_err(|e| format!("sqlite task failed: {}", e))?;
+    }
+
+    // Runs query, selecting JSON data column, and deserializes every row
+    async fn query_all<T: DeserializeOwned + Send + 'static>(
+        &self,
+        sql: &'static str,
+        params: Vec<String>,
+    ) -> Result<Vec<T>, String> {
+        return self
+            .with_conn(move |conn| {
+                let mut stmt = conn.prepare(sql).map_err(to_string)?;
+                let rows = stmt
+                    .query_map(params_from_iter(params), |row| row.get::<_, String>(0))
+                    .map_err(to_string)?;
+
+                let mut values = Vec::new();
+                for row in rows {
+                    values.push(from_json(&row.map_err(to_string)?)?);
+                }
+
+                Ok(values)
+            })
+            .await;
+    }
+}
+
+// Databases created before tenants have no tenant columns.
+// Existing rows end up in the default tenant
+fn add_tenant_columns(conn: &Connection) -> Result<(), String> {
+    for table in TENANT_TABLES {
+        let mut stmt = conn
+            .prepare(&format!("PRAGMA table_info({})", table))
+            .map_err(to_string)?;
+        let has_tenant = stmt
+            .query_map([], |row| row.get::<_, String>(1))
+            .map_err(to_string)?
+            .filter_map(Result::ok)
+            .any(|column| column == "tenant");
+
+        if !has_tenant {
+            conn.execute_batch(&format!(
+                "ALTER TABLE {} ADD COLUMN tenant TEXT NOT NULL DEFAULT 'default'",
+                table
+            ))
+            .map_err(|e| format!("Failed to add tenant column to {}: {}", table, e))?;
+        }
+    }
+
+    return Ok(());
+}
+
+fn to_string(e: rusqlite::Error) -> String {
+    return format!("sqlite error: {}", e);
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, String> {
+    return serde_json::to_string(value).map_err(|e| format!("Failed to serialize: {}", e));
+}
+
+fn from_json<T: DeserializeOwned>(data: &str) -> Result<T, String> {
+    return serde_json::from_str(data).map_err(|e| format!("Failed to deserialize: {}", e));
+}
+
+#[async_trait]
+impl NotificationStore for SqliteStore {
+    async fn persist_notification(&self, notification: &Notification) -> Result<(), String> {
+        let uuid = notification.uuid.clone();
+        let tenant = notification.tenant.clone();
+        let data = to_json(notification)?;
+
+        return self
+            .with_conn(move |conn| {
+                conn.execute(
+                    "INSERT OR REPLACE INTO notifications (uuid, tenant, data) VALUES (?1, ?2, ?3)",
+                    params![uuid, tenant, data],
+                )
+                .map_err(to_string)?;
+                Ok(())
+            })
+            .await;
+    }
+
+    async fn get_notification(&self, tenant: &str, key: &str) -> Result<Notification, String> {
+        let tenant = tenant.to_string();
+        let key = key.to_string();
+
+        return self
+            .with_conn(move |conn| {
+                let data: Option<String> = conn
+                    .query_row(
+                        "SELECT data FROM notifications WHERE tenant = ?1 AND uuid = ?2",
+                        params![tenant, key],
+                        |row| row.get(0),
+                    )
+                    .optional()
+                    .map_err(to_string)?;
+
+                match data {
+                    Some(d) => from_json(&d),
+                    None => Err(format!("Key '{}' not found", key)),
+                }
+            })
+            .await;
+    }
+
+    async fn get_all_notifications(&self, tenant: &str) -> Result<Vec<Notification>, String> {
+        return self
+            .query_all(
+                "SELECT data FROM notifications WHERE tenant = ?1",
+                vec![tenant.to_string()],
+            )
+            .await;
+    }
+
+    async fn delete_notification(&self, tenant: &str, key: &str) -> Result<(), String> {
+        let tenant = tenant.to_string();
+        let key = key.to_string();
+
+        return self
+            .with_conn(move |conn| {
+                conn.execute(
+                    "DELETE FROM notifications WHERE tenant = ?1 AND uuid = ?2",
+                    params![tenant, key],
+                )
+                .map_err(to_string)?;
+                Ok(())
+            })
+            .await;
+    }
+
+    async fn exists(&self, tenant: &str, key: &str) -> Result<bool, String> {
+        let tenant = tenant.to_string();
+        let key = key.to_string();
+
+        return self
+            .with_conn(move |conn| {
+                conn.query_row(
+                    "SELECT EXISTS(SELECT 1 FROM notifications WHERE tenant = ?1 AND uuid = ?2)",
+                    params![tenant, key],
+                    |row| row.get(0),
+                )
+                .map_err(to_string)
+            })
+            .await;
+    }
+
+    async fn persist_dead_letter(&self, dead_letter: &DeadLetter) -> Result<(), String> {
+        let id = dead_letter.id.clone();
+        let tenant = dead_letter.notification.tenant.clone();
+        let data = to_json(dead_letter)?;
+
+        return self
+            .with_conn(move |conn| {
+                conn.execute(
+                    "INSERT OR REPLACE INTO dead_letters (id, tenant, data) VALUES (?1, ?2, ?3)",
+                    params![id, tenant, data],
+                )
+                .map_err(to_string)?;
+                Ok(())
+            })
+            .await;
+    }
+
+    async fn get_dead_letter(&self, tenant: &str, id: &str) -> Result<Option<DeadLetter>, String> {
+        let tenant = tenant.to_string();
+        let id = id.to_string();
+
+        return self
+            .with_conn(move |conn| {
+                let data: Option<String> = conn
+                    .query_row(
+                        "SELECT data FROM dead_letters WHERE tenant = ?1 AND id = ?2",
+                        params![tenant, id],
+                        |row| row.get(0),
+                    )
+                    .optional()
+                    .map_err(to_string)?;
+
+                data.map(|d| from_json(&d)).transpose()
+            })
+            .await;
+    }
+
+    async fn get_all_dead_letters(&self, tenant: &str) -> Result<Vec<DeadLetter>, String> {
+        return self
+            .query_all(
+                "SELECT data FROM dead_letters WHERE tenant = ?1",
+                vec![tenant.to_string()],
+            )
+            .await;
+    }
+
+    async fn delete_dead_letter(&self, tenant: &str, id: &str) -> Result<bool, String> {
+        let tenant = tenant.to_string();
+        let id = id.to_string();
+
+        return self
+            .with_conn(move |conn| {
+                let removed = conn
+                    .execute(
+                        "DELETE FROM dead_letters WHERE tenant = ?1 AND id = ?2",
+                        params![tenant, id],
+                    )
+                    .map_err(to_string)?;
+                Ok(removed > 0)
+            })
+            .await;
+    }
+
+    async fn persist_delivery(&self, delivery: &DeliveryRecord) -> Result<(), String> {
+        let id = delivery.id.clone();
+        let tenant = delivery.tenant.clone();
+        let notification_id = delivery.notification_id.clone();
+        let data = to_json(delivery)?;
+
+        return self
+            .with_conn(move |conn| {
+                conn.execute(
+                    "INSERT INTO deliveries (id, tenant, notification_id, data)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![id, tenant, notification_id, data],
+                )
+                .map_err(to_string)?;
+                Ok(())
+            })
+            .await;
+    }
+
+    async fn get_deliveries(
+        &self,
+        tenant: &str,
+        notification_id: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<DeliveryRecord>, usize), String> {
+        let tenant = tenant.to_string();
+        let notification_id = notification_id.to_string();
+
+        return self
+            .with_conn(move |conn| {
+                let total: i64 = conn
+                    .query_row(
+                        "SELECT COUNT(*) FROM deliveries WHERE tenant = ?1 AND notification_id = ?2",
+                        params![tenant, notification_id],
+                        |row| row.get(0),
+                    )
+                    .map_err(to_string)?;
+
+                // rowid grows with every insert, so it gives the insertion order
+                let mut stmt = conn
+                    .prepare(
+                        "SELECT data FROM deliveries WHERE tenant = ?1 AND notification_id = ?2
+                         ORDER BY rowid DESC LIMIT ?3 OFFSET ?4",
+                    )
+                    .map_err(to_string)?;
+                let rows = stmt
+                    .query_map(
+                        params![tenant, notification_id, limit as i64, offset as i64],
+                        |row| row.get::<_, String>(0),
+                    )
+                    .map_err(to_string)?;
+
+                let mut deliveries = Vec::new();
+                for row in rows {
+                    deliveries.push(from_json(&row.map_err(to_string)?)?);
+                }
+
+                Ok((deliveries, total as usize))
+            })
+            .await;
+    }
+
+    async fn get_delivery(&self, tenant: &str, id: &str) -> Result<Option<DeliveryRecord>, String> {
+        let tenant = tenant.to_string();
+        let id = id.to_string();
+
+        return self
+            .with_conn(move |conn| {
+                let data: Option<String> = conn
+                    .query_row(
+                        "SELECT data FROM deliveries WHERE tenant = ?1 AND id = ?2",
+                        params![tenant, id],
+                        |row| row.get(0),
+                    )
+                    .optional()
+                    .map_err(to_string)?;
+
+                data.map(|d| from_json(&d)).transpose()
+            })
+            .await;
+    }
+
+    async fn update_delivery(&self, delivery: &DeliveryRecord) -> Result<(), String> {
+        let id = delivery.id.clone();
+        let tenant = delivery.tenant.clone();
+        let data = to_json(delivery)?;
+
+        return self
+            .with_conn(move |conn| {
+                let updated = conn
+                    .execute(
+                        "UPDATE deliveries SET data = ?3 WHERE tenant = ?1 AND id = ?2",
+                        params![tenant, id, data],
+                    )
+                    .map_err(to_string)?;
+                match updated {
+                    0 => Err(format!("Delivery {} not found", id)),
+                    _ => Ok(()),
+                }
+            })
+            .await;
+    }
+
+    async fn delete_deliveries(&self, tenant: &str, notification_id: &str) -> Result<(), String> {
+        let tenant = tenant.to_string();
+        let notification_id = notification_id.to_string();
+
+        return self
+            .with_conn(move |conn| {
+                conn.execute(
+                    "DELETE FROM deliveries WHERE tenant = ?1 AND notification_id = ?2",
+                    params![tenant, notification_id],
+                )
+                .map_err(to_string)?;
+                Ok(())
+            })
+            .await;
+    }
+
+    async fn persist_button_press(&self, press: &ButtonPress) -> Result<(), String> {
+        let id = press.id.clone();
+        let tenant = press.tenant.clone();
+        let notification_id = press.notification_id.clone();
+        let data = to_json(press)?;
+
+        return self
+            .with_conn(move |conn| {
+                conn.execute(
+                    "INSERT INTO button_presses (id, tenant, notification_id, data)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![id, tenant, notification_id, data],
+                )
+                .map_err(to_string)?;
+                Ok(())
+            })
+            .await;
+    }
+
+    async fn get_button_presses(
+        &self,
+        tenant: &str,
+        notification_id: &str,
+    ) -> Result<Vec<ButtonPress>, String> {
+        return self
+            .query_all(
+                "SELECT data FROM button_presses WHERE tenant = ?1 AND notification_id = ?2
+                 ORDER BY rowid DESC",
+                vec![tenant.to_string(), notification_id.to_string()],
+            )
+            .await;
+    }
+
+    async fn delete_button_presses(
+        &self,
+        tenant: &str,
+        notification_id: &str,
+    ) -> Result<(), String> {
+        let tenant = tenant.to_string();
+        let notification_id = notification_id.to_string();
+
+        return self
+            .with_conn(move |conn| {
+                conn.execute(
+                    "DELETE FROM button_presses WHERE tenant = ?1 AND notification_id = ?2",
+                    params![tenant, notification_id],
+                )
+                .map_err(to_string)?;
+                Ok(())
+            })
+            .await;
+    }
+
+    async fn persist_subscription(&self, subscription: &Subscription) -> Result<(), String> {
+        let id = subscription.id.clone();
+        let tenant = subscription.tenant.clone();
+        let data = to_json(subscription)?;
+
+        return self
+            .with_conn(move |conn| {
+                conn.execute(
+                    "INSERT OR REPLACE INTO subscriptions (id, tenant, data) VALUES (?1, ?2, ?3)",
+                    params![id, tenant, data],
+                )
+                .map_err(to_string)?;
+                Ok(())
+            })
+            .await;
+    }
+
+    async fn get_all_subscriptions(&self, tenant: &str) -> Result<Vec<Subscription>, String> {
+        return self
+            .query_all(
+                "SELECT data FROM subscriptions WHERE tenant = ?1",
+                vec![tenant.to_string()],
+            )
+            .await;
+    }
+
+    async fn delete_subscription(&self, tenant: &str, id: &str) -> Result<bool, String> {
+        let tenant = tenant.to_string();
+        let id = id.to_string();
+
+        return self
+            .with_conn(move |conn| {
+                let removed = conn
+                    .execute(
+                        "DELETE FROM subscriptions WHERE tenant = ?1 AND id = ?2",
+                        params![tenant, id],
+                    )
+                    .map_err(to_string)?;
+                Ok(removed > 0)
+            })
+            .await;
+    }
+
+    async fn persist_template(&self, template: &Template) -> Result<(), String> {
+        let id = template.id.clone();
+        let tenant = template.tenant.clone();
+        let data = to_json(template)?;
+
+        return self
+            .with_conn(move |conn| {
+                conn.execute(
+                    "INSERT OR REPLACE INTO templates (id, tenant, data) VALUES (?1, ?2, ?3)",
+                    params![id, tenant, data],
+                )
+                .map_err(to_string)?;
+                Ok(())
+            })
+            .await;
+    }
+
+    async fn get_template(&self, tenant: &str, id: &str) -> Result<Option<Template>, String> {
+        let tenant = tenant.to_string();
+        let id = id.to_string();
+
+        return self
+            .with_conn(move |conn| {
+                let data: Option<String> = conn
+                    .query_row(
+                        "SELECT data FROM templates WHERE tenant = ?1 AND id = ?2",
+                        params![tenant, id],
+                        |row| row.get(0),
+                    )
+                    .optional()
+                    .map_err(to_string)?;
+
+                data.map(|d| from_json(&d)).transpose()
+            })
+            .await;
+    }
+
+    async fn get_all_templates(&self, tenant: &str) -> Result<Vec<Template>, String> {
+        return self
+            .query_all(
+                "SELECT data FROM templates WHERE tenant = ?1",
+                vec![tenant.to_string()],
+            )
+            .await;
+    }
+
+    async fn delete_template(&self, tenant: &str, id: &str) -> Result<bool, String> {
+        let tenant = tenant.to_string();
+        let id = id.to_string();
+
+        return self
+            .with_conn(move |conn| {
+                let removed = conn
+                    .execute(
+                        "DELETE FROM templates WHERE tenant = ?1 AND id = ?2",
+                        params![tenant, id],
+                    )
+                    .map_err(to_string)?;
+                Ok(removed > 0)
+            })
+            .await;
+    }
+
+    async fn persist_attachment(&self, attachment: &Attachment, data: &[u8]) -> Result<(), String> {
+        let id = attachment.id.clone();
+        let tenant = attachment.tenant.clone();
+        let json = to_json(attachment)?;
+        let content = data.to_vec();
+
+        return self
+            .with_conn(move |conn| {
+                conn.execute(
+                    "INSERT OR REPLACE INTO attachments (id, tenant, data, content)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![id, tenant, json, content],
+                )
+                .map_err(to_string)?;
+                Ok(())
+            })
+            .await;
+    }
+
+    async fn get_attachment(&self, tenant: &str, id: &str) -> Result<Option<Attachment>, String> {
+        let tenant = tenant.to_string();
+        let id = id.to_string();
+
+        return self
+            .with_conn(move |conn| {
+                let data: Option<String> = conn
+                    .query_row(
+                        "SELECT data FROM attachments WHERE tenant = ?1 AND id = ?2",
+                        params![tenant, id],
+                        |row| row.get(0),
+                    )
+                    .optional()
+                    .map_err(to_string)?;
+
+                data.map(|d| from_json(&d)).transpose()
+            })
+            .await;
+    }
+
+    async fn get_attachment_data(&self, tenant: &str, id: &str) -> Result<Option<Vec<u8>>, String> {
+        let tenant = tenant.to_string();
+        let id = id.to_string();
+
+        return self
+            .with_conn(move |conn| {
+                conn.query_row(
+                    "SELECT content FROM attachments WHERE tenant = ?1 AND id = ?2",
+                    params![tenant, id],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(to_string)
+            })
+            .await;
+    }
+
+    async fn get_all_attachments(&self, tenant: &str) -> Result<Vec<Attachment>, String> {
+        return self
+            .query_all(
+                "SELECT data FROM attachments WHERE tenant = ?1",
+                vec![tenant.to_string()],
+            )
+            .await;
+    }
+
+    async fn delete_attachment(&self, tenant: &str, id: &str) -> Result<bool, String> {
+        let tenant = tenant.to_string();
+        let id = id.to_string();
+
+        return self
+            .with_conn(move |conn| {
+                let removed = conn
+                    .execute(
+                        "DELETE FROM attachments WHERE tenant = ?1 AND id = ?2",
+                        params![tenant, id],
+                    )
+                    .map_err(to_string)?;
+                Ok(removed > 0)
+            })
+            .await;
+    }
+
+    async fn persist_api_key(&self, api_key: &ApiKey) -> Result<(), String> {
+        let id = api_key.id.clone();
+        let key_hash = api_key.key_hash.clone();
+        let data = to_json(api_key)?;
+
+        return self
+            .with_conn(move |conn| {
+                conn.execute(
+                    "INSERT OR REPLACE INTO api_keys (id, key_hash, data) VALUES (?1, ?2, ?3)",
+                    params![id, key_hash, data],
+                )
+                .map_err(to_string)?;
+                Ok(())
+            })
+            .await;
+    }
+
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, String> {
+        let key_hash = key_hash.to_string();
+
+        return self
+            .with_conn(move |conn| {
+                let data: Option<String> = conn
+                    .query_row(
+                        "SELECT data FROM api_keys WHERE key_hash = ?1",
+                        params![key_hash],
+                        |row| row.get(0),
+                    )
+                    .optional()
+                    .map_err(to_string)?;
+
+                data.map(|d| from_json(&d)).transpose()
+            })
+            .await;
+    }
+
+    async fn get_all_api_keys(&self) -> Result<Vec<ApiKey>, String> {
+        return self
+            .query_all("SELECT data FROM api_keys", Vec::new())
+            .await;
+    }
+
+    async fn delete_api_key(&self, id: &str) -> Result<bool, String> {
+        let id = id.to_string();
+
+        return self
+            .with_conn(move |conn| {
+                let removed = conn
+                    .execute("DELETE FROM api_keys WHERE id = ?1", params![id])
+                    .map_err(to_string)?;
+                Ok(removed > 0)
+            })
+            .await;
+    }
+
+    async fn persist_tenant(&self, tenant: &Tenant) -> Result<(), String> {
+        let id = tenant.id.clone();
+        let data = to_json(tenant)?;
+
+        return self
+            .with_conn(move |conn| {
+                conn.execute(
+                    "INSERT OR REPLACE INTO tenants (id, data) VALUES (?1, ?2)",
+                    params![id, data],
+                )
+                .map_err(to_string)?;
+                Ok(())
+            })
+            .await;
+    }
+
+    async fn get_tenant(&self, id: &str) -> Result<Option<Tenant>, String> {
+        let id = id.to_string();
+
+        return self
+            .with_conn(move |conn| {
+                let data: Option<String> = conn
+                    .query_row(
+                        "SELECT data FROM tenants WHERE id = ?1",
+                        params![id],
+                        |row| row.get(0),
+                    )
+                    .optional()
+                    .map_err(to_string)?;
+
+                data.map(|d| from_json(&d)).transpose()
+            })
+            .await;
+    }
+
+    async fn get_all_tenants(&self) -> Result<Vec<Tenant>, String> {
+        return self.query_all("SELECT data FROM tenants", Vec::new()).await;
+    }
+}
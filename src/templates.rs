@@ -0,0 +1,105 @@
+use minijinja::{Environment, Error, UndefinedBehavior};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    formatting,
+    notifications::{MessageFormat, Notification},
+    tenants::default_tenant,
+};
+
+const MAX_TEMPLATE_LENGTH: usize = 16 * 1024;
+
+// Named message text with jinja-like syntax: {{ var }}, {% if %}, {% for %}.
+// Notifications refer to it by id and are rendered on every send
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Template {
+    pub id: String,
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
+    pub name: String,
+    pub body: String,
+    pub created_at: String,         // Stringified UTC date
+    pub updated_at: Option<String>, // Stringified UTC date
+}
+
+// Variables are strict, so typos fail the send instead of
+// silently producing empty text. Their values are escaped for the
+// message format, unless marked with the "safe" filter
+fn environment(format: &MessageFormat) -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+
+    if *format != MessageFormat::Plain {
+        let format = format.clone();
+        env.set_formatter(move |out, _state, value| {
+            let text = value.to_string();
+            let text = match value.is_safe() {
+                true => text,
+                false => formatting::escape(&format, &text),
+            };
+            out.write_str(&text).map_err(Error::from)
+        });
+    }
+
+    return env;
+}
+
+// Checks template syntax without rendering it
+pub fn validate_body(body: &str) -> Result<(), String> {
+    if body.trim().is_empty() {
+        return Err("template body can't be empty".to_string());
+    }
+
+    if body.len() > MAX_TEMPLATE_LENGTH {
+        return Err(format!(
+            "template body can't be longer than {} bytes",
+            MAX_TEMPLATE_LENGTH
+        ));
+    }
+
+    return environment(&MessageFormat::Plain)
+        .template_from_str(body)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid template: {}", e));
+}
+
+// Context is notification template_context along with built-in variables:
+// now, date, time (in notification zone), send_count and notification_id.
+// Built-ins take precedence over context keys with the same name.
+// Rendered text is checked to be valid markup of notification format
+pub fn render(body: &str, notification: &Notification) -> Result<String, String> {
+    let mut context = match &notification.template_context {
+        Some(Value::Object(map)) => map.clone(),
+        Some(_) => return Err("template_context must be an object".to_string()),
+        None => Map::new(),
+    };
+
+    let now = chrono::Utc::now().with_timezone(&notification.tz()?);
+    context.insert("now".to_string(), Value::from(now.to_rfc3339()));
+    context.insert(
+        "date".to_string(),
+        Value::from(now.format("%Y-%m-%d").to_string()),
+    );
+    context.insert(
+        "time".to_string(),
+        Value::from(now.format("%H:%M").to_string()),
+    );
+    context.insert(
+        "send_count".to_string(),
+        Value::from(notification.send_count),
+    );
+    context.insert(
+        "notification_id".to_string(),
+        Value::from(notification.uuid.clone()),
+    );
+
+    let text = environment(&notification.format)
+        .render_str(body, Value::Object(context))
+        .map_err(|e| format!("Failed to render template: {}", e))?;
+
+    formatting::validate(&notification.format, &text)
+        .map_err(|e| format!("Rendered template is invalid: {}", e))?;
+
+    return Ok(text);
+}
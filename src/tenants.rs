@@ -0,0 +1,75 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// Tenant of everything created before tenants were introduced,
+// and of the ADMIN_API_KEY. Its admins manage other tenants
+pub const DEFAULT_TENANT: &str = "default";
+const MAX_TENANT_ID_LENGTH: usize = 64;
+
+pub fn default_tenant() -> String {
+    return DEFAULT_TENANT.to_string();
+}
+
+// Team sharing the deployment. Notifications, dead letters, deliveries
+// and api keys belong to exactly one tenant
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Tenant {
+    pub id: String,
+    // Own bot of the tenant. Server bot is used when not set
+    pub telegram_bot_token: Option<String>,
+    // Events about notifications of the tenant are posted here
+    #[serde(default)]
+    pub events_url: Option<String>,
+    // Webhooks and events of the tenant are signed with it, so tenants can't
+    // forge each other's requests. Default tenant uses WEBHOOK_SECRET
+    #[serde(default)]
+    pub webhook_secret: Option<String>,
+    pub created_at: String, // Stringified UTC date
+}
+
+// Tenant as shown in api responses, without secrets
+#[derive(Debug, Serialize, Clone)]
+pub struct TenantInfo {
+    pub id: String,
+    pub has_telegram_bot_token: bool,
+    pub events_url: Option<String>,
+    pub created_at: String,
+}
+
+impl From<&Tenant> for TenantInfo {
+    fn from(tenant: &Tenant) -> Self {
+        TenantInfo {
+            id: tenant.id.clone(),
+            has_telegram_bot_token: tenant.telegram_bot_token.is_some(),
+            events_url: tenant.events_url.clone(),
+            created_at: tenant.created_at.clone(),
+        }
+    }
+}
+
+// Shown to the admin once, when generated
+pub fn generate_webhook_secret() -> String {
+    let bytes: [u8; 32] = rand::rng().random();
+    return hex::encode(bytes);
+}
+
+// Tenant id is a part of storage keys, so it's kept short and simple
+pub fn validate_tenant_id(id: &str) -> Result<(), String> {
+    if id.is_empty() || id.len() > MAX_TENANT_ID_LENGTH {
+        return Err(format!(
+            "tenant id must be 1 to {} characters long",
+            MAX_TENANT_ID_LENGTH
+        ));
+    }
+
+    if !id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(
+            "tenant id may contain only latin letters, digits, \"-\" and \"_\"".to_string(),
+        );
+    }
+
+    return Ok(());
+}
@@ -1,8 +1,15 @@
 use axum::{Json, http::StatusCode};
-use chrono::{DateTime, Local};
+use chrono::{DateTime, LocalResult, NaiveDateTime, Offset, TimeDelta, TimeZone};
+use chrono_tz::Tz;
 
 use crate::{
-    endpoints::{MessageResponse, NotificationResponse},
+    endpoints::{
+        ApiKeyListResponse, ApiKeyResponse, AttachmentListResponse, AttachmentResponse,
+        ButtonPressListResponse, DeadLetterListResponse, DeliveryListResponse, DeliveryResponse,
+        JobListResponse, MessageResponse, NotificationListResponse, NotificationResponse,
+        SubscriptionListResponse, TelegramQueueResponse, TemplateListResponse, TemplateResponse,
+        TenantListResponse, TenantResponse,
+    },
     notifications::Notification,
 };
 
@@ -43,12 +50,305 @@ impl Response for NotificationResponse {
     }
 }
 
-pub fn rfc3339_to_local(rfc3339: &str) -> Result<DateTime<Local>, String> {
-    let dt = DateTime::parse_from_rfc3339(rfc3339)
-        .map(|dt| dt.with_timezone(&Local))
-        .map_err(|e| e.to_string());
+impl Response for NotificationListResponse {
+    fn with_message(message: String) -> Self {
+        Self {
+            message,
+            notifications: Vec::new(),
+            page: 0,
+            per_page: 0,
+            total: 0,
+        }
+    }
+
+    fn with_existing(message: String, existing: Self) -> Self {
+        Self {
+            message,
+            ..existing
+        }
+    }
+}
+
+impl Response for ApiKeyResponse {
+    fn with_message(message: String) -> Self {
+        Self {
+            message,
+            key: String::new(),
+            api_key: None,
+        }
+    }
+
+    fn with_existing(message: String, existing: Self) -> Self {
+        Self {
+            message,
+            key: existing.key,
+            api_key: existing.api_key,
+        }
+    }
+}
+
+impl Response for ApiKeyListResponse {
+    fn with_message(message: String) -> Self {
+        Self {
+            message,
+            api_keys: Vec::new(),
+        }
+    }
+
+    fn with_existing(message: String, existing: Self) -> Self {
+        Self {
+            message,
+            api_keys: existing.api_keys,
+        }
+    }
+}
+
+impl Response for AttachmentResponse {
+    fn with_message(message: String) -> Self {
+        Self {
+            message,
+            attachment: None,
+        }
+    }
+
+    fn with_existing(message: String, existing: Self) -> Self {
+        Self {
+            message,
+            attachment: existing.attachment,
+        }
+    }
+}
+
+impl Response for AttachmentListResponse {
+    fn with_message(message: String) -> Self {
+        Self {
+            message,
+            attachments: Vec::new(),
+        }
+    }
+
+    fn with_existing(message: String, existing: Self) -> Self {
+        Self {
+            message,
+            attachments: existing.attachments,
+        }
+    }
+}
 
-    return dt;
+impl Response for SubscriptionListResponse {
+    fn with_message(message: String) -> Self {
+        Self {
+            message,
+            subscriptions: Vec::new(),
+        }
+    }
+
+    fn with_existing(message: String, existing: Self) -> Self {
+        Self {
+            message,
+            subscriptions: existing.subscriptions,
+        }
+    }
+}
+
+impl Response for TemplateResponse {
+    fn with_message(message: String) -> Self {
+        Self {
+            message,
+            template: None,
+        }
+    }
+
+    fn with_existing(message: String, existing: Self) -> Self {
+        Self {
+            message,
+            template: existing.template,
+        }
+    }
+}
+
+impl Response for TemplateListResponse {
+    fn with_message(message: String) -> Self {
+        Self {
+            message,
+            templates: Vec::new(),
+        }
+    }
+
+    fn with_existing(message: String, existing: Self) -> Self {
+        Self {
+            message,
+            templates: existing.templates,
+        }
+    }
+}
+
+impl Response for TenantResponse {
+    fn with_message(message: String) -> Self {
+        Self {
+            message,
+            tenant: None,
+            webhook_secret: None,
+        }
+    }
+
+    fn with_existing(message: String, existing: Self) -> Self {
+        Self {
+            message,
+            tenant: existing.tenant,
+            webhook_secret: existing.webhook_secret,
+        }
+    }
+}
+
+impl Response for TenantListResponse {
+    fn with_message(message: String) -> Self {
+        Self {
+            message,
+            tenants: Vec::new(),
+        }
+    }
+
+    fn with_existing(message: String, existing: Self) -> Self {
+        Self {
+            message,
+            tenants: existing.tenants,
+        }
+    }
+}
+
+impl Response for JobListResponse {
+    fn with_message(message: String) -> Self {
+        Self {
+            message,
+            jobs: Vec::new(),
+        }
+    }
+
+    fn with_existing(message: String, existing: Self) -> Self {
+        Self {
+            message,
+            jobs: existing.jobs,
+        }
+    }
+}
+
+impl Response for TelegramQueueResponse {
+    fn with_message(message: String) -> Self {
+        Self { message, queued: 0 }
+    }
+
+    fn with_existing(message: String, existing: Self) -> Self {
+        Self {
+            message,
+            queued: existing.queued,
+        }
+    }
+}
+
+impl Response for DeliveryListResponse {
+    fn with_message(message: String) -> Self {
+        Self {
+            message,
+            deliveries: Vec::new(),
+            page: 0,
+            per_page: 0,
+            total: 0,
+        }
+    }
+
+    fn with_existing(message: String, existing: Self) -> Self {
+        Self {
+            message,
+            ..existing
+        }
+    }
+}
+
+impl Response for DeliveryResponse {
+    fn with_message(message: String) -> Self {
+        Self {
+            message,
+            delivery: None,
+        }
+    }
+
+    fn with_existing(message: String, existing: Self) -> Self {
+        Self {
+            message,
+            delivery: existing.delivery,
+        }
+    }
+}
+
+impl Response for ButtonPressListResponse {
+    fn with_message(message: String) -> Self {
+        Self {
+            message,
+            presses: Vec::new(),
+        }
+    }
+
+    fn with_existing(message: String, existing: Self) -> Self {
+        Self {
+            message,
+            ..existing
+        }
+    }
+}
+
+impl Response for DeadLetterListResponse {
+    fn with_message(message: String) -> Self {
+        Self {
+            message,
+            dead_letters: Vec::new(),
+        }
+    }
+
+    fn with_existing(message: String, existing: Self) -> Self {
+        Self {
+            message,
+            dead_letters: existing.dead_letters,
+        }
+    }
+}
+
+// Parses timestamp into the given zone. RFC3339 strings are converted,
+// strings without offset (2025-04-18T12:00:00) are taken as wall clock time in the zone
+pub fn parse_timestamp_in_tz(input: &str, tz: &Tz) -> Result<DateTime<Tz>, String> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(input) {
+        return Ok(dt.with_timezone(tz));
+    }
+
+    let naive =
+        NaiveDateTime::parse_from_str(input, "%Y-%m-%dT%H:%M:%S%.f").map_err(|e| e.to_string())?;
+
+    return resolve_local(tz, naive).ok_or_else(|| "time does not exist in the zone".to_string());
+}
+
+// Maps wall clock time onto the zone, handling DST transitions:
+// repeated times resolve to the first occurrence, skipped ones
+// are shifted forward by the length of the gap (02:30 -> 03:30)
+pub fn resolve_local(tz: &Tz, naive: NaiveDateTime) -> Option<DateTime<Tz>> {
+    return match tz.from_local_datetime(&naive) {
+        LocalResult::Single(dt) => Some(dt),
+        LocalResult::Ambiguous(earliest, _) => Some(earliest),
+        LocalResult::None => {
+            // offset in effect before the transition
+            let before = tz.offset_from_utc_datetime(&(naive - TimeDelta::days(1)));
+            let utc = naive - TimeDelta::seconds(before.fix().local_minus_utc() as i64);
+            Some(tz.from_utc_datetime(&utc))
+        }
+    };
+}
+
+pub fn parse_timezone(name: &str) -> Result<Tz, String> {
+    return name.parse::<Tz>().map_err(|_| {
+        format!(
+            "Unknown time zone \"{}\". Expected IANA name like Europe/Berlin",
+            name
+        )
+    });
 }
 
 pub struct ResponseFabric {}
@@ -61,6 +361,20 @@ impl ResponseFabric {
         );
     }
 
+    pub fn unauthorized<T: Response>(message: &str) -> (StatusCode, Json<T>) {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(T::with_message(message.to_string())),
+        );
+    }
+
+    pub fn forbidden<T: Response>(message: &str) -> (StatusCode, Json<T>) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(T::with_message(message.to_string())),
+        );
+    }
+
     pub fn not_found<T: Response>(message: &str) -> (StatusCode, Json<T>) {
         return (
             StatusCode::NOT_FOUND,
@@ -68,6 +382,13 @@ impl ResponseFabric {
         );
     }
 
+    pub fn conflict<T: Response>(message: &str) -> (StatusCode, Json<T>) {
+        return (
+            StatusCode::CONFLICT,
+            Json(T::with_message(message.to_string())),
+        );
+    }
+
     pub fn internal_server_error<T: Response>(message: &str) -> (StatusCode, Json<T>) {
         return (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -94,4 +415,63 @@ impl ResponseFabric {
             }),
         );
     }
+
+    pub fn accepted_with_id(
+        message: &str,
+        notification_id: String,
+    ) -> (StatusCode, Json<MessageResponse>) {
+        return (
+            StatusCode::ACCEPTED,
+            Json(MessageResponse {
+                message: message.to_string(),
+                notification_id,
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive(s: &str) -> NaiveDateTime {
+        return NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S").unwrap();
+    }
+
+    #[test]
+    fn resolve_local_regular_time() {
+        let tz: Tz = "Europe/Berlin".parse().unwrap();
+        let dt = resolve_local(&tz, naive("2025-01-10T12:00:00")).unwrap();
+        assert_eq!(dt.to_rfc3339(), "2025-01-10T12:00:00+01:00");
+    }
+
+    #[test]
+    fn resolve_local_shifts_skipped_time_forward() {
+        let tz: Tz = "Europe/Berlin".parse().unwrap();
+        let dt = resolve_local(&tz, naive("2025-03-30T02:30:00")).unwrap();
+        assert_eq!(dt.to_rfc3339(), "2025-03-30T03:30:00+02:00");
+
+        let tz: Tz = "America/New_York".parse().unwrap();
+        let dt = resolve_local(&tz, naive("2025-03-09T02:15:00")).unwrap();
+        assert_eq!(dt.to_rfc3339(), "2025-03-09T03:15:00-04:00");
+    }
+
+    #[test]
+    fn resolve_local_takes_first_of_repeated_time() {
+        let tz: Tz = "Europe/Berlin".parse().unwrap();
+        let dt = resolve_local(&tz, naive("2025-10-26T02:30:00")).unwrap();
+        assert_eq!(dt.to_rfc3339(), "2025-10-26T02:30:00+02:00");
+    }
+
+    #[test]
+    fn parse_timestamp_in_tz_converts_offsets() {
+        let tz: Tz = "Asia/Tokyo".parse().unwrap();
+        let dt = parse_timestamp_in_tz("2025-01-10T00:00:00Z", &tz).unwrap();
+        assert_eq!(dt.to_rfc3339(), "2025-01-10T09:00:00+09:00");
+
+        let dt = parse_timestamp_in_tz("2025-01-10T09:00:00", &tz).unwrap();
+        assert_eq!(dt.to_rfc3339(), "2025-01-10T09:00:00+09:00");
+
+        assert!(parse_timestamp_in_tz("yesterday", &tz).is_err());
+    }
 }